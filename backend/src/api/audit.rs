@@ -0,0 +1,61 @@
+use actix_web::{web, HttpResponse, Result};
+use actix_web::http::StatusCode;
+use serde_json::json;
+use mongodb::bson::Document;
+use crate::api::fhir::{fhir_error, fhir_ok};
+use crate::audit::date_condition;
+use crate::codecs::audit::{AuditCodec, AuditFilter};
+use crate::fhir::audit::audit_bundle;
+
+const DEFAULT_AUDIT_COUNT: usize = 50;
+const MAX_AUDIT_COUNT: usize = 500;
+
+// Audit service status: number of recorded events
+pub async fn audit_service() -> Result<HttpResponse> {
+    match AuditCodec::new().count().await {
+        Ok(count) => Ok(HttpResponse::Ok().json(json!({
+            "service": "Audit Service",
+            "status": "running",
+            "message": "Every request is recorded as a FHIR AuditEvent",
+            "total_events": count,
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        }))),
+        Err(e) => Ok(HttpResponse::ServiceUnavailable().json(json!({
+            "service": "Audit Service",
+            "status": "error",
+            "message": format!("Failed to read audit events: {}", e),
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        }))),
+    }
+}
+
+// GET /fhir/AuditEvent?date=ge2025-01-01&date=lt2025-02-01&actor=..&action=R|C|U|D|E&_count=N&_offset=N
+pub async fn fhir_audit_search(
+    query: web::Query<Vec<(String, String)>>
+) -> Result<HttpResponse> {
+    let mut filter = AuditFilter { actor: None, action: None, recorded: None };
+    let mut count = DEFAULT_AUDIT_COUNT;
+    let mut offset = 0;
+
+    for (name, value) in query.into_inner() {
+        match name.as_str() {
+            "actor" | "agent" => filter.actor = Some(value),
+            "action" => filter.action = Some(value.to_uppercase()),
+            "date" => {
+                let Some(condition) = date_condition(&value) else {
+                    return Ok(fhir_error(StatusCode::BAD_REQUEST, "value", &format!("Invalid date parameter: {}", value)));
+                };
+                // Repeated date parameters narrow the range
+                filter.recorded.get_or_insert_with(Document::new).extend(condition);
+            },
+            "_count" => count = value.parse::<usize>().unwrap_or(DEFAULT_AUDIT_COUNT).min(MAX_AUDIT_COUNT),
+            "_offset" => offset = value.parse::<usize>().unwrap_or(0),
+            _ => {},
+        }
+    }
+
+    match AuditCodec::new().search(filter, offset, count.max(1)).await {
+        Ok((records, total)) => Ok(fhir_ok(audit_bundle(&records, total))),
+        Err(e) => Ok(fhir_error(StatusCode::INTERNAL_SERVER_ERROR, "exception", &format!("Audit search failed: {}", e))),
+    }
+}
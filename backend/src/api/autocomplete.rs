@@ -1,8 +1,10 @@
 use actix_web::{web, HttpResponse, Result};
 use serde::{Deserialize, Serialize};
-use crate::dbcodes::redis::{RedisClient, AutocompleteSuggestion, BulkSuggestion};
-use crate::codecs::icd::IcdCodec;
-use crate::codecs::namaste::NamasteCodec;
+use crate::dbcodes::redis::{RedisClient, AutocompleteSuggestion};
+use crate::autocomplete::ranking::Contribution;
+use crate::api::jobs;
+use crate::codecs::jobs::JobCodec;
+use crate::jobs::JobKind;
 use serde_json::json;
 
 #[derive(Serialize, Deserialize)]
@@ -10,6 +12,8 @@ pub struct AutocompleteRequest {
     pub query: String,
     pub category: Option<String>, // "icd", "namaste", or "all"
     pub limit: Option<usize>,
+    // Include each suggestion's score components
+    pub explain: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -30,6 +34,8 @@ pub struct FormattedSuggestion {
     pub source: String,
     pub system: String,
     pub relevance_score: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<Vec<Contribution>>,
 }
 
 // Main autocomplete endpoint
@@ -40,6 +46,7 @@ pub async fn autocomplete_suggestions(
     let search_query = query.query.trim();
     let category = query.category.as_deref().unwrap_or("all");
     let limit = query.limit.unwrap_or(3);
+    let explain = query.explain.unwrap_or(false);
 
     if search_query.is_empty() {
         return Ok(HttpResponse::BadRequest().json(json!({
@@ -57,14 +64,14 @@ pub async fn autocomplete_suggestions(
             match category {
                 "icd" => {
                     let icd_suggestions = redis_client
-                        .get_autocomplete_suggestions("icd", search_query, limit)
+                        .get_autocomplete_suggestions("icd", search_query, limit, explain)
                         .await
                         .unwrap_or_default();
                     all_suggestions.extend(format_icd_suggestions(icd_suggestions));
                 }
                 "namaste" => {
                     let namaste_suggestions = redis_client
-                        .get_autocomplete_suggestions("namaste", search_query, limit)
+                        .get_autocomplete_suggestions("namaste", search_query, limit, explain)
                         .await
                         .unwrap_or_default();
                     all_suggestions.extend(format_namaste_suggestions(namaste_suggestions));
@@ -72,11 +79,11 @@ pub async fn autocomplete_suggestions(
                 _ => {
                     // Search both categories
                     let icd_suggestions = redis_client
-                        .get_autocomplete_suggestions("icd", search_query, limit)
+                        .get_autocomplete_suggestions("icd", search_query, limit, explain)
                         .await
                         .unwrap_or_default();
                     let namaste_suggestions = redis_client
-                        .get_autocomplete_suggestions("namaste", search_query, limit)
+                        .get_autocomplete_suggestions("namaste", search_query, limit, explain)
                         .await
                         .unwrap_or_default();
                     
@@ -111,79 +118,31 @@ pub async fn autocomplete_suggestions(
     }
 }
 
-// Initialize autocomplete data
-pub async fn initialize_autocomplete_data() -> Result<HttpResponse> {
-    match RedisClient::get_instance().await {
-        Ok(redis_manager) => {
-            let redis_client = RedisClient { manager: redis_manager.clone() };
-            
-            println!("🔄 Initializing autocomplete data...");
-            
-            // Load ICD data
-            let icd_codec = IcdCodec::new();
-            if let Ok(icd_codes) = icd_codec.get_all_codes(Some(1000)).await {
-                let icd_suggestions: Vec<BulkSuggestion> = icd_codes
-                    .into_iter()
-                    .map(|code| BulkSuggestion {
-                        category: "icd".to_string(),
-                        text: format!("{} {}", code.code, code.title),
-                        score: 1.0,
-                        payload: Some(json!({
-                            "id": code.id,
-                            "code": code.code,
-                            "title": code.title,
-                            "definition": code.definition,
-                            "source": "ICD-11",
-                            "system": "Biomedicine"
-                        }).to_string()),
-                    })
-                    .collect();
-                
-                if let Err(e) = redis_client.bulk_add_suggestions(icd_suggestions).await {
-                    println!("Failed to add ICD suggestions: {}", e);
-                }
-            }
-            
-            // Load NAMASTE data
-            let namaste_codec = NamasteCodec::new();
-            if let Ok(namaste_codes) = namaste_codec.get_all_codes(Some(1000)).await {
-                let namaste_suggestions: Vec<BulkSuggestion> = namaste_codes
-                    .into_iter()
-                    .map(|code| BulkSuggestion {
-                        category: "namaste".to_string(),
-                        text: format!("{} {}", code.namc_id, code.namc_term),
-                        score: 1.0,
-                        payload: Some(json!({
-                            "id": code.namc_id,
-                            "code": code.namc_id,
-                            "title": code.namc_term,
-                            "definition": code.namc_term,
-                            "source": "NAMASTE",
-                            "system": "Ayurveda"
-                        }).to_string()),
-                    })
-                    .collect();
-                
-                if let Err(e) = redis_client.bulk_add_suggestions(namaste_suggestions).await {
-                    println!("Failed to add NAMASTE suggestions: {}", e);
-                }
-            }
-            
-            println!("✅ Autocomplete data initialization completed");
-            
-            Ok(HttpResponse::Ok().json(json!({
-                "status": "success",
-                "message": "Autocomplete data initialized successfully",
-                "timestamp": chrono::Utc::now().to_rfc3339()
-            })))
-        }
-        Err(e) => {
-            Ok(HttpResponse::InternalServerError().json(json!({
-                "error": "Redis connection failed",
-                "message": e.to_string(),
-                "timestamp": chrono::Utc::now().to_rfc3339()
-            })))
-        }
+#[derive(Deserialize)]
+pub struct InitializeParams {
+    // Rebuild from scratch instead of only re-indexing changed concepts
+    pub full: Option<bool>,
+}
+
+// Index the whole corpus as a background job, followed at /jobs/{id}
+pub async fn initialize_autocomplete_data(params: web::Query<InitializeParams>) -> Result<HttpResponse> {
+    let full = params.full.unwrap_or(false);
+    Ok(jobs::start_response(JobKind::Autocomplete, json!({ "full": full })).await)
+}
+
+// The latest autocomplete indexing jobs
+pub async fn autocomplete_status() -> Result<HttpResponse> {
+    match JobCodec::new().list(Some(JobKind::Autocomplete.as_str()), 5).await {
+        Ok(records) => Ok(HttpResponse::Ok().json(json!({
+            "running": records.iter().any(|r| matches!(r.state.as_str(), "queued" | "running" | "cancelling")),
+            "jobs": records,
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        }))),
+        Err(e) => Ok(HttpResponse::ServiceUnavailable().json(json!({
+            "status": "error",
+            "message": format!("Failed to read jobs: {}", e),
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        }))),
     }
 }
 
@@ -191,18 +150,18 @@ fn format_icd_suggestions(suggestions: Vec<AutocompleteSuggestion>) -> Vec<Forma
     suggestions
         .into_iter()
         .filter_map(|suggestion| {
-            if let Some(payload_str) = suggestion.payload {
-                if let Ok(payload) = serde_json::from_str::<serde_json::Value>(&payload_str) {
-                    return Some(FormattedSuggestion {
-                        id: payload.get("id").and_then(|v| v.as_str()).unwrap_or("").to_string(),
-                        code: payload.get("code").and_then(|v| v.as_str()).unwrap_or("").to_string(),
-                        title: payload.get("title").and_then(|v| v.as_str()).unwrap_or("").to_string(),
-                        definition: payload.get("definition").and_then(|v| v.as_str()).map(|s| s.to_string()),
-                        source: "ICD-11".to_string(),
-                        system: "Biomedicine".to_string(),
-                        relevance_score: suggestion.score,
-                    });
-                }
+            if let Some(payload_str) = suggestion.payload
+                && let Ok(payload) = serde_json::from_str::<serde_json::Value>(&payload_str) {
+                return Some(FormattedSuggestion {
+                    id: payload.get("id").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                    code: payload.get("code").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                    title: payload.get("title").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                    definition: payload.get("definition").and_then(|v| v.as_str()).map(|s| s.to_string()),
+                    source: "ICD-11".to_string(),
+                    system: "Biomedicine".to_string(),
+                    relevance_score: suggestion.score,
+                    explanation: suggestion.explanation,
+                });
             }
             None
         })
@@ -213,18 +172,18 @@ fn format_namaste_suggestions(suggestions: Vec<AutocompleteSuggestion>) -> Vec<F
     suggestions
         .into_iter()
         .filter_map(|suggestion| {
-            if let Some(payload_str) = suggestion.payload {
-                if let Ok(payload) = serde_json::from_str::<serde_json::Value>(&payload_str) {
-                    return Some(FormattedSuggestion {
-                        id: payload.get("id").and_then(|v| v.as_str()).unwrap_or("").to_string(),
-                        code: payload.get("code").and_then(|v| v.as_str()).unwrap_or("").to_string(),
-                        title: payload.get("title").and_then(|v| v.as_str()).unwrap_or("").to_string(),
-                        definition: payload.get("definition").and_then(|v| v.as_str()).map(|s| s.to_string()),
-                        source: "NAMASTE".to_string(),
-                        system: "Ayurveda".to_string(),
-                        relevance_score: suggestion.score,
-                    });
-                }
+            if let Some(payload_str) = suggestion.payload
+                && let Ok(payload) = serde_json::from_str::<serde_json::Value>(&payload_str) {
+                return Some(FormattedSuggestion {
+                    id: payload.get("id").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                    code: payload.get("code").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                    title: payload.get("title").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                    definition: payload.get("definition").and_then(|v| v.as_str()).map(|s| s.to_string()),
+                    source: "NAMASTE".to_string(),
+                    system: "Ayurveda".to_string(),
+                    relevance_score: suggestion.score,
+                    explanation: suggestion.explanation,
+                });
             }
             None
         })
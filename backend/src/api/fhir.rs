@@ -0,0 +1,541 @@
+use actix_web::{web, HttpResponse, Result};
+use actix_web::http::StatusCode;
+use std::collections::HashMap;
+use crate::codecs::icd::{IcdCode, IcdCodec, IcdFilter};
+use crate::codecs::namaste::{self, Language, NamasteCode, NamasteCodec, NamasteFilter};
+use crate::codecs::mapping::MappingCodec;
+use crate::fhir::{self, codesystem, conceptmap, lookup, CodeSystemKind, FHIR_JSON};
+use crate::fhir::valueset::{self, ExpansionPage, ImplicitValueSet};
+use crate::fhir::validate::{self, CodeValidation};
+use crate::fhir::capability::{self, CodeSystemSummary, FhirRoutes};
+
+const DEFAULT_EXPAND_COUNT: usize = 100;
+const MAX_EXPAND_COUNT: usize = 1000;
+
+// A concept resolved by exact code in one of our code systems
+pub enum FoundConcept {
+    Namaste(NamasteCode),
+    Icd(IcdCode),
+}
+
+impl FoundConcept {
+    pub fn display(&self) -> &str {
+        match self {
+            FoundConcept::Namaste(code) => &code.namc_term,
+            FoundConcept::Icd(code) => &code.title,
+        }
+    }
+
+    // Every accepted spelling: the three NAMASTE scripts, or the ICD title and its synonyms
+    pub fn display_variants(&self) -> Vec<&str> {
+        match self {
+            FoundConcept::Namaste(code) => vec![
+                code.namc_term.as_str(),
+                code.namc_term_diacritical.as_str(),
+                code.namc_term_devanagari.as_str(),
+            ],
+            FoundConcept::Icd(code) => std::iter::once(code.title.as_str())
+                .chain(code.synonyms.as_deref().unwrap_or_default().split(';').map(str::trim))
+                .filter(|s| !s.is_empty())
+                .collect(),
+        }
+    }
+}
+
+pub async fn find_concept(
+    kind: CodeSystemKind,
+    code: &str,
+) -> std::result::Result<Option<FoundConcept>, Box<dyn std::error::Error>> {
+    let found = match kind.icd_discipline() {
+        None => NamasteCodec::new().find_by_code(code).await?.map(FoundConcept::Namaste),
+        Some(discipline) => IcdCodec::new()
+            .find_by_code(code, Some(discipline))
+            .await?
+            .map(FoundConcept::Icd),
+    };
+    Ok(found)
+}
+
+// Whether a concept already known to be in the code system also falls inside the value set
+async fn in_value_set(
+    concept: &FoundConcept,
+    value_set: &ImplicitValueSet,
+) -> std::result::Result<bool, Box<dyn std::error::Error>> {
+    let ImplicitValueSet::IsA(_, root) = value_set else {
+        return Ok(true);
+    };
+    match concept {
+        FoundConcept::Namaste(code) => {
+            let code = code.parse_codes().0;
+            Ok(code == *root || namaste::ancestor_candidates(&code).contains(root))
+        },
+        FoundConcept::Icd(code) => {
+            // Walk up the parent chain; ICD-11 is far shallower than this bound
+            let codec = IcdCodec::new();
+            let mut current = Some(code.clone());
+            for _ in 0..64 {
+                let Some(entity) = current else { break };
+                if entity.concept_code() == *root {
+                    return Ok(true);
+                }
+                current = match entity.parent.as_deref().filter(|p| !p.is_empty()) {
+                    Some(parent_id) => codec.find_by_id(parent_id).await?,
+                    None => None,
+                };
+            }
+            Ok(false)
+        },
+    }
+}
+
+// Check a code and optional display against a code system, and optionally a value set within it
+pub async fn validate_code(
+    kind: CodeSystemKind,
+    code: &str,
+    display: Option<&str>,
+    value_set: Option<&ImplicitValueSet>,
+) -> std::result::Result<CodeValidation, Box<dyn std::error::Error>> {
+    let Some(concept) = find_concept(kind, code).await? else {
+        return Ok(CodeValidation::invalid(format!("Unknown code {} in {}", code, kind.url())));
+    };
+
+    if let Some(value_set) = value_set
+        && !in_value_set(&concept, value_set).await? {
+        return Ok(CodeValidation::invalid(format!("Code {} is not in value set {}", code, value_set.url())));
+    }
+
+    let canonical = Some(concept.display().to_string());
+    if let Some(display) = display
+        && !validate::display_matches(&concept.display_variants(), display, matches!(concept, FoundConcept::Namaste(_))) {
+        return Ok(CodeValidation {
+            result: false,
+            message: format!("Display \"{}\" does not match any display for {} (expected \"{}\")", display, code, concept.display()),
+            display: canonical,
+        });
+    }
+
+    Ok(CodeValidation {
+        result: true,
+        message: format!("Code {} is valid in {}", code, kind.url()),
+        display: canonical,
+    })
+}
+
+// Parent concept code, resolved against the store
+async fn find_parent_code(
+    concept: &FoundConcept,
+) -> std::result::Result<Option<String>, Box<dyn std::error::Error>> {
+    match concept {
+        FoundConcept::Namaste(code) => {
+            let codec = NamasteCodec::new();
+            for candidate in namaste::ancestor_candidates(&code.parse_codes().0) {
+                if codec.find_by_code(&candidate).await?.is_some() {
+                    return Ok(Some(candidate));
+                }
+            }
+            Ok(None)
+        },
+        FoundConcept::Icd(code) => match code.parent.as_deref().filter(|p| !p.is_empty()) {
+            Some(parent_id) => Ok(IcdCodec::new().find_by_id(parent_id).await?.map(|p| p.concept_code())),
+            None => Ok(None),
+        },
+    }
+}
+
+pub fn fhir_ok(resource: serde_json::Value) -> HttpResponse {
+    HttpResponse::Ok().content_type(FHIR_JSON).json(resource)
+}
+
+pub fn fhir_error(status: StatusCode, code: &str, diagnostics: &str) -> HttpResponse {
+    HttpResponse::build(status)
+        .content_type(FHIR_JSON)
+        .json(fhir::operation_outcome("error", code, diagnostics))
+}
+
+// Version and concept count of every code system, as loaded in Mongo
+async fn code_system_summaries() -> std::result::Result<Vec<CodeSystemSummary>, Box<dyn std::error::Error>> {
+    let mut summaries = Vec::new();
+    for kind in CodeSystemKind::ALL {
+        let summary = match kind.icd_discipline() {
+            None => {
+                let filter = NamasteFilter { code: None, language: Language::Both, search_term: None };
+                let (_, count) = NamasteCodec::new().search_codes_paged(filter, 0, 1).await?;
+                CodeSystemSummary { kind, version: None, count }
+            },
+            Some(discipline) => {
+                let filter = IcdFilter { discipline: Some(discipline), search_term: None, parent_filter: None };
+                let (sample, count) = IcdCodec::new().search_codes_paged(filter, 0, 1).await?;
+                CodeSystemSummary { kind, version: sample.first().and_then(|code| code.release()), count }
+            },
+        };
+        summaries.push(summary);
+    }
+    Ok(summaries)
+}
+
+// GET /fhir/metadata - CapabilityStatement, or TerminologyCapabilities with ?mode=terminology
+pub async fn fhir_metadata(
+    routes: web::Data<FhirRoutes>,
+    query: web::Query<HashMap<String, String>>
+) -> Result<HttpResponse> {
+    if query.get("mode").map(String::as_str) != Some("terminology") {
+        return Ok(fhir_ok(capability::capability_statement(&routes)));
+    }
+    match code_system_summaries().await {
+        Ok(summaries) => Ok(fhir_ok(capability::terminology_capabilities(&summaries))),
+        Err(e) => Ok(fhir_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "exception",
+            &format!("Failed to read code systems: {}", e),
+        )),
+    }
+}
+
+// GET /fhir/CodeSystem/{id} - full CodeSystem resource built from the stored codes
+pub async fn fhir_codesystem_read(path: web::Path<String>) -> Result<HttpResponse> {
+    let id = path.into_inner();
+    let Some(kind) = CodeSystemKind::from_id(&id) else {
+        return Ok(fhir_error(
+            StatusCode::NOT_FOUND,
+            "not-found",
+            &format!("CodeSystem/{} is not known to this server", id),
+        ));
+    };
+
+    println!("📘 Building FHIR CodeSystem/{}", kind.id());
+
+    let resource = match kind {
+        CodeSystemKind::Namaste => NamasteCodec::new()
+            .get_all_codes(None)
+            .await
+            .map(|codes| codesystem::namaste_code_system(&codes)),
+        CodeSystemKind::Icd11Tm2 => IcdCodec::new()
+            .get_tm2_codes(None)
+            .await
+            .map(|codes| codesystem::icd_code_system(kind, &codes)),
+        CodeSystemKind::Icd11Biomedicine => IcdCodec::new()
+            .get_biomedicine_codes(None)
+            .await
+            .map(|codes| codesystem::icd_code_system(kind, &codes)),
+    };
+
+    match resource {
+        Ok(resource) => Ok(fhir_ok(resource)),
+        Err(e) => Ok(fhir_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "exception",
+            &format!("Failed to load CodeSystem/{}: {}", kind.id(), e),
+        )),
+    }
+}
+
+// GET /fhir/CodeSystem/$lookup?system=...&code=... - exact concept details as a Parameters resource
+pub async fn fhir_codesystem_lookup(
+    query: web::Query<HashMap<String, String>>
+) -> Result<HttpResponse> {
+    let (Some(system), Some(code)) = (query.get("system"), query.get("code").map(|c| c.trim())) else {
+        return Ok(fhir_error(StatusCode::BAD_REQUEST, "required", "Both system and code parameters are required"));
+    };
+    let Some(kind) = CodeSystemKind::from_url(system) else {
+        return Ok(fhir_error(StatusCode::NOT_FOUND, "not-found", &format!("Unknown code system: {}", system)));
+    };
+
+    println!("🔎 $lookup {} in {}", code, kind.id());
+
+    let concept = match find_concept(kind, code).await {
+        Ok(Some(concept)) => concept,
+        Ok(None) => {
+            return Ok(HttpResponse::NotFound().content_type(FHIR_JSON).json(lookup::not_found(kind, code)));
+        },
+        Err(e) => {
+            return Ok(fhir_error(StatusCode::INTERNAL_SERVER_ERROR, "exception", &format!("Lookup failed: {}", e)));
+        },
+    };
+
+    let parent = find_parent_code(&concept).await.unwrap_or_else(|e| {
+        println!("⚠️  Failed to resolve parent of {}: {}", code, e);
+        None
+    });
+
+    let parameters = match &concept {
+        FoundConcept::Namaste(namaste_code) => lookup::namaste_lookup(namaste_code, parent),
+        FoundConcept::Icd(icd_code) => lookup::icd_lookup(kind, icd_code, parent),
+    };
+    Ok(fhir_ok(parameters))
+}
+
+// GET /fhir/ConceptMap/{id} - the persisted NAMASTE -> ICD-11 map
+pub async fn fhir_conceptmap_read(path: web::Path<String>) -> Result<HttpResponse> {
+    let id = path.into_inner();
+    if id != conceptmap::CONCEPT_MAP_ID {
+        return Ok(fhir_error(StatusCode::NOT_FOUND, "not-found", &format!("ConceptMap/{} is not known to this server", id)));
+    }
+
+    let codec = MappingCodec::new();
+    let elements = match codec.ensure_seeded().await {
+        Ok(()) => codec.all_elements().await,
+        Err(e) => Err(e),
+    };
+    match elements {
+        Ok(elements) => Ok(fhir_ok(conceptmap::concept_map(&elements))),
+        Err(e) => Ok(fhir_error(StatusCode::INTERNAL_SERVER_ERROR, "exception", &format!("Failed to load concept map: {}", e))),
+    }
+}
+
+// GET /fhir/ConceptMap/$translate?system=...&code=...[&target=...][&reverse=true]
+pub async fn fhir_conceptmap_translate(
+    query: web::Query<HashMap<String, String>>
+) -> Result<HttpResponse> {
+    let (Some(system), Some(code)) = (query.get("system"), query.get("code").map(|c| c.trim())) else {
+        return Ok(fhir_error(StatusCode::BAD_REQUEST, "required", "Both system and code parameters are required"));
+    };
+    let Some(kind) = CodeSystemKind::from_url(system) else {
+        return Ok(fhir_error(StatusCode::NOT_FOUND, "not-found", &format!("Unknown code system: {}", system)));
+    };
+    let target = query.get("targetsystem").or_else(|| query.get("target"));
+    let target_kind = match target.map(|t| CodeSystemKind::from_url(t)) {
+        Some(None) => {
+            return Ok(fhir_error(StatusCode::NOT_FOUND, "not-found", &format!("Unknown target system: {}", target.unwrap())));
+        },
+        Some(Some(target_kind)) => Some(target_kind),
+        None => None,
+    };
+    let reverse = query.get("reverse").is_some_and(|r| r == "true");
+
+    println!("🔁 $translate {} from {} (reverse: {})", code, kind.id(), reverse);
+
+    let codec = MappingCodec::new();
+    let elements = match codec.ensure_seeded().await {
+        Ok(()) => codec.translate(kind.url(), code, target_kind.map(|t| t.url()), reverse).await,
+        Err(e) => Err(e),
+    };
+    let elements = match elements {
+        Ok(elements) => elements,
+        Err(e) => return Ok(fhir_error(StatusCode::INTERNAL_SERVER_ERROR, "exception", &format!("Translate failed: {}", e))),
+    };
+
+    let matches: Vec<(serde_json::Value, &str)> = elements
+        .iter()
+        .map(|element| {
+            let (system, code, display, equivalence) = if reverse {
+                (&element.source_system, &element.source_code, &element.source_display, element.equivalence.reversed())
+            } else {
+                (&element.target_system, &element.target_code, &element.target_display, element.equivalence)
+            };
+            let mut coding = serde_json::json!({ "system": system, "code": code });
+            if let Some(display) = display {
+                coding["display"] = serde_json::json!(display);
+            }
+            (coding, equivalence.as_str())
+        })
+        .collect();
+
+    let message = if matches.is_empty() {
+        format!("No mapping found for {}|{}", kind.url(), code)
+    } else {
+        format!("{} mapping(s) found for {}|{}", matches.len(), kind.url(), code)
+    };
+    Ok(fhir_ok(conceptmap::translate_result(&matches, &message)))
+}
+
+// GET /fhir/ValueSet/$expand?url=[system]?fhir_vs[=isa/code]&filter=..&count=..&offset=..
+pub async fn fhir_valueset_expand(
+    query: web::Query<HashMap<String, String>>
+) -> Result<HttpResponse> {
+    let Some(url) = query.get("url") else {
+        return Ok(fhir_error(StatusCode::BAD_REQUEST, "required", "The url parameter is required"));
+    };
+    match ImplicitValueSet::from_url(url) {
+        Some(value_set) => expand_value_set(value_set, &query).await,
+        None => Ok(fhir_error(StatusCode::NOT_FOUND, "not-found", &format!("Unknown value set: {}", url))),
+    }
+}
+
+// GET /fhir/ValueSet/{id}/$expand - the whole of one code system
+pub async fn fhir_valueset_expand_by_id(
+    path: web::Path<String>,
+    query: web::Query<HashMap<String, String>>
+) -> Result<HttpResponse> {
+    let id = path.into_inner();
+    match ImplicitValueSet::from_id(&id) {
+        Some(value_set) => expand_value_set(value_set, &query).await,
+        None => Ok(fhir_error(StatusCode::NOT_FOUND, "not-found", &format!("ValueSet/{} is not known to this server", id))),
+    }
+}
+
+async fn expand_value_set(value_set: ImplicitValueSet, query: &HashMap<String, String>) -> Result<HttpResponse> {
+    let filter = query.get("filter").map(|f| f.trim().to_string()).filter(|f| !f.is_empty());
+    let offset = query.get("offset").and_then(|o| o.parse().ok()).unwrap_or(0);
+    let count = query.get("count")
+        .and_then(|c| c.parse().ok())
+        .unwrap_or(DEFAULT_EXPAND_COUNT)
+        .min(MAX_EXPAND_COUNT);
+    let include_designations = query.get("includeDesignations").is_some_and(|d| d == "true");
+
+    println!("📋 $expand {} (filter: {:?}, offset: {}, count: {})", value_set.url(), filter, offset, count);
+
+    let kind = value_set.kind();
+    let result: std::result::Result<(Vec<serde_json::Value>, u64), Box<dyn std::error::Error>> = match &value_set {
+        ImplicitValueSet::All(CodeSystemKind::Namaste) => {
+            let namaste_filter = NamasteFilter {
+                code: None,
+                language: Language::Both,
+                search_term: filter.clone(),
+            };
+            NamasteCodec::new()
+                .search_codes_paged(namaste_filter, offset, count)
+                .await
+                .map(|(codes, total)| {
+                    let contains = codes.iter().map(|c| valueset::namaste_contains(c, include_designations)).collect();
+                    (contains, total)
+                })
+        },
+        ImplicitValueSet::All(kind) => {
+            let icd_filter = IcdFilter {
+                discipline: kind.icd_discipline(),
+                search_term: filter.clone(),
+                parent_filter: None,
+            };
+            IcdCodec::new()
+                .search_codes_paged(icd_filter, offset, count)
+                .await
+                .map(|(codes, total)| (codes.iter().map(|c| valueset::icd_contains(*kind, c)).collect(), total))
+        },
+        ImplicitValueSet::IsA(CodeSystemKind::Namaste, root) => {
+            NamasteCodec::new().get_all_codes(None).await.map(|codes| {
+                let members: Vec<&NamasteCode> = codes
+                    .iter()
+                    .filter(|c| {
+                        let code = c.parse_codes().0;
+                        code == *root || namaste::ancestor_candidates(&code).contains(root)
+                    })
+                    .filter(|c| filter.as_deref().is_none_or(|f| text_matches(f, &[&c.namc_term, &c.namc_term_diacritical, &c.namc_term_devanagari])))
+                    .collect();
+                let total = members.len() as u64;
+                let contains = members
+                    .into_iter()
+                    .skip(offset)
+                    .take(count)
+                    .map(|c| valueset::namaste_contains(c, include_designations))
+                    .collect();
+                (contains, total)
+            })
+        },
+        ImplicitValueSet::IsA(kind, root) => {
+            let codec = IcdCodec::new();
+            match codec.find_by_code(root, kind.icd_discipline()).await {
+                Ok(Some(root_entity)) => codec.get_descendants(&root_entity.id).await.map(|codes| {
+                    let members: Vec<&IcdCode> = codes
+                        .iter()
+                        .filter(|c| filter.as_deref().is_none_or(|f| text_matches(f, &[&c.title, &c.code])))
+                        .collect();
+                    let total = members.len() as u64;
+                    let contains = members
+                        .into_iter()
+                        .skip(offset)
+                        .take(count)
+                        .map(|c| valueset::icd_contains(*kind, c))
+                        .collect();
+                    (contains, total)
+                }),
+                Ok(None) => {
+                    return Ok(fhir_error(
+                        StatusCode::NOT_FOUND,
+                        "not-found",
+                        &format!("Code {} was not found in {}", root, kind.url()),
+                    ));
+                },
+                Err(e) => Err(e),
+            }
+        },
+    };
+
+    match result {
+        Ok((contains, total)) => Ok(fhir_ok(valueset::expansion(&value_set, ExpansionPage {
+            contains,
+            total,
+            offset,
+            count,
+            filter,
+        }))),
+        Err(e) => Ok(fhir_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "exception",
+            &format!("Failed to expand {} ({}): {}", value_set.url(), kind.id(), e),
+        )),
+    }
+}
+
+// Case-insensitive substring match used when filtering in memory
+fn text_matches(filter: &str, fields: &[&str]) -> bool {
+    let filter = filter.to_lowercase();
+    fields.iter().any(|field| field.to_lowercase().contains(&filter))
+}
+
+fn validation_response(result: std::result::Result<CodeValidation, Box<dyn std::error::Error>>) -> HttpResponse {
+    match result {
+        Ok(validation) => fhir_ok(validate::validation_parameters(&validation)),
+        Err(e) => fhir_error(StatusCode::INTERNAL_SERVER_ERROR, "exception", &format!("Validation failed: {}", e)),
+    }
+}
+
+// GET /fhir/CodeSystem/$validate-code?url=...&code=...[&display=...]
+pub async fn fhir_codesystem_validate_code(
+    query: web::Query<HashMap<String, String>>
+) -> Result<HttpResponse> {
+    let system = query.get("url").or_else(|| query.get("system"));
+    let (Some(system), Some(code)) = (system, query.get("code").map(|c| c.trim())) else {
+        return Ok(fhir_error(StatusCode::BAD_REQUEST, "required", "Both url and code parameters are required"));
+    };
+    let Some(kind) = CodeSystemKind::from_url(system) else {
+        return Ok(fhir_error(StatusCode::NOT_FOUND, "not-found", &format!("Unknown code system: {}", system)));
+    };
+
+    println!("✔️  $validate-code {} in {}", code, kind.id());
+    Ok(validation_response(validate_code(kind, code, query.get("display").map(|d| d.as_str()), None).await))
+}
+
+// GET /fhir/ValueSet/$validate-code?url=[value set]&system=...&code=...[&display=...]
+pub async fn fhir_valueset_validate_code(
+    query: web::Query<HashMap<String, String>>
+) -> Result<HttpResponse> {
+    let Some(url) = query.get("url") else {
+        return Ok(fhir_error(StatusCode::BAD_REQUEST, "required", "The url parameter is required"));
+    };
+    match ImplicitValueSet::from_url(url) {
+        Some(value_set) => validate_in_value_set(value_set, &query).await,
+        None => Ok(fhir_error(StatusCode::NOT_FOUND, "not-found", &format!("Unknown value set: {}", url))),
+    }
+}
+
+// GET /fhir/ValueSet/{id}/$validate-code?code=...[&system=...][&display=...]
+pub async fn fhir_valueset_validate_code_by_id(
+    path: web::Path<String>,
+    query: web::Query<HashMap<String, String>>
+) -> Result<HttpResponse> {
+    let id = path.into_inner();
+    match ImplicitValueSet::from_id(&id) {
+        Some(value_set) => validate_in_value_set(value_set, &query).await,
+        None => Ok(fhir_error(StatusCode::NOT_FOUND, "not-found", &format!("ValueSet/{} is not known to this server", id))),
+    }
+}
+
+async fn validate_in_value_set(value_set: ImplicitValueSet, query: &HashMap<String, String>) -> Result<HttpResponse> {
+    let Some(code) = query.get("code").map(|c| c.trim()) else {
+        return Ok(fhir_error(StatusCode::BAD_REQUEST, "required", "The code parameter is required"));
+    };
+    let kind = value_set.kind();
+
+    // A code from another system can never be in a single-system value set
+    if let Some(system) = query.get("system")
+        && CodeSystemKind::from_url(system) != Some(kind) {
+        return Ok(fhir_ok(validate::validation_parameters(&CodeValidation::invalid(
+            format!("System {} is not included in value set {}", system, value_set.url()),
+        ))));
+    }
+
+    println!("✔️  $validate-code {} in {}", code, value_set.url());
+    Ok(validation_response(
+        validate_code(kind, code, query.get("display").map(|d| d.as_str()), Some(&value_set)).await,
+    ))
+}
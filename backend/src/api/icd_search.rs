@@ -1,6 +1,7 @@
 use actix_web::{web, HttpResponse, Result};
 use serde::{Deserialize, Serialize};
 use crate::codecs::icd::{IcdCodec, IcdFilter, IcdDiscipline};
+use crate::fulltext;
 
 // Basic response structure
 #[derive(Serialize, Deserialize)]
@@ -32,10 +33,16 @@ pub async fn icd_search(
     match codec.search_codes(filter, query.get("limit").and_then(|l| l.parse().ok())).await {
         Ok(codes) => {
             let formatted = codec.format_response(codes);
+            // Spelling correction from the indexed vocabulary when nothing matched
+            let did_you_mean = match query.get("search") {
+                Some(term) if formatted.is_empty() => fulltext::suggest_icd(term).map(|(suggestion, _)| suggestion),
+                _ => None,
+            };
             Ok(HttpResponse::Ok().json(serde_json::json!({
                 "service": "ICD-11 Search",
                 "total": formatted.len(),
                 "results": formatted,
+                "did_you_mean": did_you_mean,
                 "timestamp": chrono::Utc::now().to_rfc3339()
             })))
         },
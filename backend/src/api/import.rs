@@ -0,0 +1,77 @@
+use std::path::Path;
+use actix_web::{web, HttpResponse, Result};
+use serde::Deserialize;
+use serde_json::json;
+use crate::api::jobs;
+use crate::config;
+use crate::importer::{self, ImportOptions};
+use crate::jobs::JobKind;
+
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    #[serde(default)]
+    pub dry_run: bool,
+    #[serde(default)]
+    pub strict: bool,
+}
+
+// Import the NAMASTE sheet posted as text/csv, or the configured one (files.namaste_csv) when the body is empty.
+// Dry runs report inline; real imports run as a background job whose result is the report
+pub async fn import_namaste(query: web::Query<ImportQuery>, body: web::Bytes) -> Result<HttpResponse> {
+    if !query.dry_run {
+        let upload = match std::str::from_utf8(&body) {
+            Ok("") => Ok(None),
+            Ok(csv) => importer::stage_upload(Path::new(&config::get().files.uploads), csv).map(Some),
+            Err(e) => return Ok(HttpResponse::BadRequest().json(json!({
+                "service": "Import Service",
+                "status": "error",
+                "message": format!("The sheet is not UTF-8 text: {}", e),
+                "timestamp": chrono::Utc::now().to_rfc3339()
+            }))),
+        };
+        return match upload {
+            Ok(upload) => Ok(jobs::start_response(JobKind::ImportNamaste, json!({ "upload": upload, "strict": query.strict })).await),
+            Err(e) => Ok(HttpResponse::InternalServerError().json(json!({
+                "service": "Import Service",
+                "status": "error",
+                "message": format!("Could not store the sheet: {}", e),
+                "timestamp": chrono::Utc::now().to_rfc3339()
+            }))),
+        };
+    }
+
+    let options = ImportOptions { dry_run: query.dry_run, strict: query.strict };
+    let result = if body.is_empty() {
+        let path = &config::get().files.namaste_csv;
+        match std::fs::File::open(path) {
+            Ok(file) => importer::import_namaste(path, file, options).await,
+            Err(e) => Err(e.into()),
+        }
+    } else {
+        importer::import_namaste("request body", body.as_ref(), options).await
+    };
+
+    match result {
+        Ok(report) => {
+            let refused = options.strict && !report.errors.is_empty();
+            let (mut response, status, message) = match (refused, &report.version) {
+                (true, _) => (HttpResponse::UnprocessableEntity(), "error", "Strict import refused: the sheet has invalid rows".to_string()),
+                (false, Some(version)) => (HttpResponse::Ok(), "success", format!("Imported NAMASTE version {}", version)),
+                (false, None) => (HttpResponse::Ok(), "success", "Dry run: nothing was written".to_string()),
+            };
+            Ok(response.json(json!({
+                "service": "Import Service",
+                "status": status,
+                "message": message,
+                "report": report,
+                "timestamp": chrono::Utc::now().to_rfc3339()
+            })))
+        },
+        Err(e) => Ok(HttpResponse::InternalServerError().json(json!({
+            "service": "Import Service",
+            "status": "error",
+            "message": format!("Import failed: {}", e),
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        }))),
+    }
+}
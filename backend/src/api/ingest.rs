@@ -0,0 +1,221 @@
+use actix_web::{web, HttpResponse, Result};
+use actix_web::http::StatusCode;
+use serde_json::{json, Value};
+use crate::api::fhir::{fhir_error, validate_code};
+use crate::codecs::clinical::ClinicalCodec;
+use crate::codecs::mapping::MappingCodec;
+use crate::fhir::{CodeSystemKind, FHIR_JSON};
+use crate::fhir::bundle::{self, issue, BundleKind, INGESTIBLE_TYPES};
+
+type IngestResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+// Validate every coding of a CodeableConcept and complete the NAMASTE / ICD-11 dual coding
+async fn process_codeable_concept(concept: &mut Value, path: &str, issues: &mut Vec<Value>) -> IngestResult<()> {
+    let Some(codings) = concept.get_mut("coding").and_then(Value::as_array_mut) else {
+        return Ok(());
+    };
+
+    let mut namaste_codes = Vec::new();
+    let mut icd_codes = Vec::new();
+    for coding in codings.iter_mut() {
+        let (Some(system), Some(code)) = (coding["system"].as_str(), coding["code"].as_str()) else {
+            issues.push(issue("warning", "incomplete", &format!("{}: coding without system and code was not checked", path)));
+            continue;
+        };
+        let Some(kind) = CodeSystemKind::from_url(system) else {
+            continue; // Not one of ours (SNOMED CT, LOINC, ...)
+        };
+        let (system, code) = (system.to_string(), code.to_string());
+
+        let validation = validate_code(kind, &code, coding["display"].as_str(), None).await?;
+        if !validation.result {
+            // A known code with the wrong display is tolerated; an unknown code is not
+            let severity = if validation.display.is_some() { "warning" } else { "error" };
+            issues.push(issue(severity, "code-invalid", &format!("{}: {}", path, validation.message)));
+            if validation.display.is_none() {
+                continue;
+            }
+        } else if coding.get("display").is_none()
+            && let Some(display) = validation.display {
+            coding["display"] = json!(display);
+        }
+
+        if kind == CodeSystemKind::Namaste {
+            namaste_codes.push(code);
+        } else {
+            icd_codes.push((system, code));
+        }
+    }
+
+    let mut added = Vec::new();
+    if !namaste_codes.is_empty() {
+        // NAMASTE present: add any ICD-11 targets the sender left out
+        for code in &namaste_codes {
+            let elements = MappingCodec::new().translate(CodeSystemKind::Namaste.url(), code, None, false).await?;
+            if elements.is_empty() && icd_codes.is_empty() {
+                issues.push(issue("warning", "not-found", &format!("{}: no ICD-11 mapping for NAMASTE code {}", path, code)));
+            }
+            for element in elements {
+                added.push((element.target_system, element.target_code, element.target_display, element.equivalence, code.clone()));
+            }
+        }
+    } else {
+        // ICD-11 only: add the NAMASTE concepts that map onto it
+        for (system, code) in &icd_codes {
+            for element in MappingCodec::new().translate(system, code, Some(CodeSystemKind::Namaste.url()), true).await? {
+                let equivalence = element.equivalence.reversed();
+                added.push((element.source_system, element.source_code, element.source_display, equivalence, code.clone()));
+            }
+        }
+    }
+
+    for (system, code, display, equivalence, from) in added {
+        if bundle::has_coding(codings, &system, &code) {
+            continue;
+        }
+        let mut coding = json!({ "system": system, "code": code, "userSelected": false });
+        if let Some(display) = display {
+            coding["display"] = json!(display);
+        }
+        codings.push(coding);
+        issues.push(issue(
+            "information",
+            "informational",
+            &format!("{}: added {}|{} from {} ({})", path, system, code, from, equivalence.as_str()),
+        ));
+    }
+    Ok(())
+}
+
+// Check one entry and fill in its dual codes. Returns the resource ready to store, with its issues
+async fn process_entry(entry: &Value, index: usize) -> IngestResult<(Option<Value>, Vec<Value>)> {
+    let mut issues = Vec::new();
+    let Some(mut resource) = entry.get("resource").cloned() else {
+        issues.push(issue("error", "required", &format!("entry[{}] has no resource", index)));
+        return Ok((None, issues));
+    };
+    let resource_type = resource["resourceType"].as_str().unwrap_or_default().to_string();
+    if !INGESTIBLE_TYPES.contains(&resource_type.as_str()) {
+        issues.push(issue(
+            "error",
+            "not-supported",
+            &format!("entry[{}]: {} is not accepted, expected one of {}", index, resource_type, INGESTIBLE_TYPES.join(", ")),
+        ));
+        return Ok((None, issues));
+    }
+    // The store stamps meta.versionId and meta.lastUpdated
+    if resource.get("meta").is_some_and(|meta| !meta.is_object()) {
+        issues.push(issue("error", "invalid", &format!("entry[{}]: {}.meta must be an object", index, resource_type)));
+        return Ok((None, issues));
+    }
+    let method = entry["request"]["method"].as_str().unwrap_or("POST");
+    if method != "POST" && method != "PUT" {
+        issues.push(issue("error", "not-supported", &format!("entry[{}]: method {} is not supported", index, method)));
+        return Ok((None, issues));
+    }
+
+    // Condition.code carries the diagnosis; category codings are checked too when they use our systems
+    if let Some(code) = resource.get_mut("code") {
+        process_codeable_concept(code, &format!("{}.code", resource_type), &mut issues).await?;
+    }
+    if let Some(categories) = resource.get_mut("category").and_then(Value::as_array_mut) {
+        for (i, category) in categories.iter_mut().enumerate() {
+            process_codeable_concept(category, &format!("{}.category[{}]", resource_type, i), &mut issues).await?;
+        }
+    }
+
+    // POST always gets a server id; PUT keeps the one the client chose
+    if method == "POST" || resource["id"].as_str().is_none_or(str::is_empty) {
+        resource["id"] = json!(uuid::Uuid::new_v4().to_string());
+    }
+
+    Ok((Some(resource), issues))
+}
+
+fn location(resource: &Value) -> String {
+    format!(
+        "{}/{}/_history/{}",
+        resource["resourceType"].as_str().unwrap_or_default(),
+        resource["id"].as_str().unwrap_or_default(),
+        resource["meta"]["versionId"].as_str().unwrap_or("1"),
+    )
+}
+
+// POST /fhir - transaction or batch Bundle of Conditions / problem Lists
+pub async fn fhir_bundle_ingest(body: web::Json<Value>) -> Result<HttpResponse> {
+    let bundle = body.into_inner();
+    if bundle["resourceType"] != "Bundle" {
+        return Ok(fhir_error(StatusCode::BAD_REQUEST, "invalid", "Expected a Bundle resource"));
+    }
+    let Some(kind) = bundle["type"].as_str().and_then(BundleKind::from_type) else {
+        return Ok(fhir_error(StatusCode::BAD_REQUEST, "invalid", "Bundle.type must be transaction or batch"));
+    };
+    let entries = bundle["entry"].as_array().cloned().unwrap_or_default();
+
+    if let Err(e) = MappingCodec::new().ensure_seeded().await {
+        return Ok(fhir_error(StatusCode::INTERNAL_SERVER_ERROR, "exception", &format!("Concept map unavailable: {}", e)));
+    }
+
+    let mut processed = Vec::with_capacity(entries.len());
+    for (index, entry) in entries.iter().enumerate() {
+        match process_entry(entry, index).await {
+            Ok(result) => processed.push(result),
+            Err(e) => {
+                return Ok(fhir_error(StatusCode::INTERNAL_SERVER_ERROR, "exception", &format!("entry[{}]: {}", index, e)));
+            },
+        }
+    }
+
+    let codec = ClinicalCodec::new();
+    let accepted = |(resource, issues): &(Option<Value>, Vec<Value>)| resource.is_some() && !bundle::has_errors(issues);
+    let transaction_failed = kind == BundleKind::Transaction && !processed.iter().all(accepted);
+
+    let mut response_entries = Vec::with_capacity(processed.len());
+    if kind == BundleKind::Transaction && !transaction_failed {
+        let resources: Vec<Value> = processed.iter().filter_map(|(r, _)| r.clone()).collect();
+        match codec.store(&resources).await {
+            // Every entry was accepted, so the stored resources line up with them
+            Ok(stored) => {
+                for (entry, resource) in processed.iter_mut().zip(stored) {
+                    entry.0 = Some(resource);
+                }
+            },
+            Err(e) => {
+                return Ok(fhir_error(StatusCode::INTERNAL_SERVER_ERROR, "exception", &format!("Failed to store bundle: {}", e)));
+            },
+        }
+    }
+
+    for entry in processed.iter() {
+        let (resource, mut issues) = entry.clone();
+        let response = if !accepted(entry) {
+            bundle::entry_response("400 Bad Request", None, bundle::entry_outcome(issues), None)
+        } else if transaction_failed {
+            issues.push(issue("information", "informational", "Not stored: another entry in the transaction failed"));
+            bundle::entry_response("400 Bad Request", None, bundle::entry_outcome(issues), None)
+        } else {
+            let mut resource = resource.unwrap_or_default();
+            if kind == BundleKind::Batch {
+                match codec.store(std::slice::from_ref(&resource)).await {
+                    Ok(mut stored) => resource = stored.remove(0),
+                    Err(e) => {
+                        issues.push(issue("error", "exception", &format!("Failed to store resource: {}", e)));
+                        response_entries.push(bundle::entry_response("500 Internal Server Error", None, bundle::entry_outcome(issues), None));
+                        continue;
+                    },
+                }
+            }
+            let location = location(&resource);
+            bundle::entry_response("201 Created", Some(&location), bundle::entry_outcome(issues), Some(resource))
+        };
+        response_entries.push(response);
+    }
+
+    println!("📥 Ingested {} bundle with {} entries{}", kind.response_type(), entries.len(),
+        if transaction_failed { " (rolled back)" } else { "" });
+
+    let status = if transaction_failed { StatusCode::BAD_REQUEST } else { StatusCode::OK };
+    Ok(HttpResponse::build(status)
+        .content_type(FHIR_JSON)
+        .json(bundle::response_bundle(kind, response_entries)))
+}
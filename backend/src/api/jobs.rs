@@ -0,0 +1,134 @@
+use std::path::Path;
+use actix_web::{web, HttpResponse, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use crate::codecs::jobs::{JobCodec, JobRecord};
+use crate::config;
+use crate::importer;
+use crate::jobs::{self, JobKind, StartError};
+
+#[derive(Deserialize)]
+pub struct StartJobRequest {
+    pub kind: String,
+    pub params: Option<Value>,
+}
+
+#[derive(Deserialize)]
+pub struct JobListQuery {
+    pub kind: Option<String>,
+    pub limit: Option<i64>,
+}
+
+fn job_json(record: &JobRecord) -> Value {
+    let mut value = serde_json::to_value(record).unwrap_or_default();
+    value["status_url"] = json!(format!("/jobs/{}", record.job_id));
+    value
+}
+
+// 202 with the queued job, or 409 naming the job of the same kind that is still running
+pub async fn start_response(kind: JobKind, params: Value) -> HttpResponse {
+    match jobs::start(kind, params).await {
+        Ok(record) => HttpResponse::Accepted()
+            .insert_header(("Location", format!("/jobs/{}", record.job_id)))
+            .json(job_json(&record)),
+        Err(StartError::AlreadyRunning(job_id)) => HttpResponse::Conflict().json(json!({
+            "status": "already_running",
+            "message": format!("A {} job is already running", kind.as_str()),
+            "job_id": job_id,
+            "status_url": format!("/jobs/{}", job_id),
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        })),
+        Err(e) => HttpResponse::ServiceUnavailable().json(json!({
+            "status": "error",
+            "message": e.to_string(),
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        })),
+    }
+}
+
+// POST /jobs {"kind": "embeddings" | "autocomplete" | "import_namaste", "params": {...}}
+pub async fn job_start(body: web::Json<StartJobRequest>) -> Result<HttpResponse> {
+    let Some(kind) = JobKind::parse(&body.kind) else {
+        let kinds: Vec<&str> = JobKind::ALL.iter().map(JobKind::as_str).collect();
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "status": "error",
+            "message": format!("Unknown job kind '{}' (expected one of {})", body.kind, kinds.join(", ")),
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        })));
+    };
+    let mut params = body.params.clone().unwrap_or_else(|| json!({}));
+    // A sheet given inline is staged to a file rather than kept in the job record
+    if kind == JobKind::ImportNamaste
+        && let Some(csv) = params.get("csv").and_then(Value::as_str) {
+        match importer::stage_upload(Path::new(&config::get().files.uploads), csv) {
+            Ok(upload) => {
+                if let Some(params) = params.as_object_mut() {
+                    params.remove("csv");
+                    params.insert("upload".to_string(), upload);
+                }
+            },
+            Err(e) => return Ok(HttpResponse::InternalServerError().json(json!({
+                "status": "error",
+                "message": format!("Could not store the sheet: {}", e),
+                "timestamp": chrono::Utc::now().to_rfc3339()
+            }))),
+        }
+    }
+    Ok(start_response(kind, params).await)
+}
+
+pub async fn job_list(query: web::Query<JobListQuery>) -> Result<HttpResponse> {
+    let limit = query.limit.unwrap_or(20).clamp(1, 200);
+    match JobCodec::new().list(query.kind.as_deref(), limit).await {
+        Ok(records) => Ok(HttpResponse::Ok().json(json!({
+            "jobs": records.iter().map(job_json).collect::<Vec<_>>(),
+            "total": records.len(),
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        }))),
+        Err(e) => Ok(HttpResponse::ServiceUnavailable().json(json!({
+            "status": "error",
+            "message": format!("Failed to read jobs: {}", e),
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        }))),
+    }
+}
+
+pub async fn job_status(path: web::Path<String>) -> Result<HttpResponse> {
+    match JobCodec::new().get(&path).await {
+        Ok(Some(record)) => Ok(HttpResponse::Ok().json(job_json(&record))),
+        Ok(None) => Ok(HttpResponse::NotFound().json(json!({
+            "status": "error",
+            "message": format!("No job {}", path),
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        }))),
+        Err(e) => Ok(HttpResponse::ServiceUnavailable().json(json!({
+            "status": "error",
+            "message": format!("Failed to read job: {}", e),
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        }))),
+    }
+}
+
+// The job stops at its next checkpoint
+pub async fn job_cancel(path: web::Path<String>) -> Result<HttpResponse> {
+    if jobs::cancel(&path).await {
+        return Ok(HttpResponse::Accepted().json(json!({
+            "status": "cancelling",
+            "job_id": path.as_str(),
+            "status_url": format!("/jobs/{}", path),
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        })));
+    }
+    match JobCodec::new().get(&path).await {
+        Ok(Some(record)) => Ok(HttpResponse::Conflict().json(json!({
+            "status": "error",
+            "message": format!("Job {} is {}, not running", record.job_id, record.state),
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        }))),
+        _ => Ok(HttpResponse::NotFound().json(json!({
+            "status": "error",
+            "message": format!("No running job {}", path),
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        }))),
+    }
+}
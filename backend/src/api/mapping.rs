@@ -0,0 +1,130 @@
+use actix_web::{web, HttpResponse, Result};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+use crate::api::fhir::find_concept;
+use crate::codecs::mapping::{Equivalence, MappingCodec, MappingElement, CURATED_ORIGIN};
+use crate::fhir::CodeSystemKind;
+
+#[derive(Deserialize)]
+pub struct MappingRequest {
+    pub source_code: String,
+    pub target_system: String, // canonical URL or CodeSystem id ("icd11-tm2", "icd11-biomedicine")
+    pub target_code: String,
+    pub equivalence: Equivalence,
+    pub comment: Option<String>,
+}
+
+// Mapping service status: element counts per origin and target system
+pub async fn mapping_service() -> Result<HttpResponse> {
+    let codec = MappingCodec::new();
+    match codec.all_elements().await {
+        Ok(elements) => {
+            let mut by_origin: BTreeMap<String, usize> = BTreeMap::new();
+            let mut by_target: BTreeMap<String, usize> = BTreeMap::new();
+            for element in &elements {
+                *by_origin.entry(element.origin.clone()).or_default() += 1;
+                *by_target.entry(element.target_system.clone()).or_default() += 1;
+            }
+            Ok(HttpResponse::Ok().json(json!({
+                "service": "Mapping Service",
+                "status": "running",
+                "total_elements": elements.len(),
+                "by_origin": by_origin,
+                "by_target_system": by_target,
+                "timestamp": chrono::Utc::now().to_rfc3339()
+            })))
+        },
+        Err(e) => Ok(HttpResponse::ServiceUnavailable().json(json!({
+            "service": "Mapping Service",
+            "status": "error",
+            "message": format!("Failed to read concept map: {}", e),
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        }))),
+    }
+}
+
+// Re-derive the NAMASTE -> TM2 elements from the NAMASTE sheet
+pub async fn mapping_rebuild() -> Result<HttpResponse> {
+    match MappingCodec::new().rebuild_from_namaste().await {
+        Ok(count) => Ok(HttpResponse::Ok().json(json!({
+            "service": "Mapping Service",
+            "status": "success",
+            "message": format!("Concept map rebuilt with {} NAMASTE -> TM2 elements", count),
+            "elements": count,
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        }))),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(json!({
+            "service": "Mapping Service",
+            "status": "error",
+            "message": format!("Rebuild failed: {}", e),
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        }))),
+    }
+}
+
+// Add a curated NAMASTE -> ICD-11 element (e.g. a Biomedicine target the sheet does not carry)
+pub async fn mapping_add(body: web::Json<MappingRequest>) -> Result<HttpResponse> {
+    let request = body.into_inner();
+    let target_kind = CodeSystemKind::from_url(&request.target_system)
+        .or_else(|| CodeSystemKind::from_id(&request.target_system))
+        .filter(|kind| *kind != CodeSystemKind::Namaste);
+    let Some(target_kind) = target_kind else {
+        return Ok(HttpResponse::BadRequest().json(json!({
+            "service": "Mapping Service",
+            "status": "error",
+            "message": format!("Unsupported target system: {}", request.target_system),
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        })));
+    };
+
+    let source = find_concept(CodeSystemKind::Namaste, &request.source_code).await;
+    let target = find_concept(target_kind, &request.target_code).await;
+    let (source, target) = match (source, target) {
+        (Ok(Some(source)), Ok(Some(target))) => (source, target),
+        (Err(e), _) | (_, Err(e)) => {
+            return Ok(HttpResponse::InternalServerError().json(json!({
+                "service": "Mapping Service",
+                "status": "error",
+                "message": format!("Failed to validate codes: {}", e),
+                "timestamp": chrono::Utc::now().to_rfc3339()
+            })));
+        },
+        (source, _) => {
+            let missing = if matches!(source, Ok(None)) { &request.source_code } else { &request.target_code };
+            return Ok(HttpResponse::NotFound().json(json!({
+                "service": "Mapping Service",
+                "status": "error",
+                "message": format!("Unknown code: {}", missing),
+                "timestamp": chrono::Utc::now().to_rfc3339()
+            })));
+        },
+    };
+
+    let element = MappingElement {
+        source_system: CodeSystemKind::Namaste.url().to_string(),
+        source_code: request.source_code.clone(),
+        source_display: Some(source.display().to_string()),
+        target_system: target_kind.url().to_string(),
+        target_code: request.target_code.clone(),
+        target_display: Some(target.display().to_string()),
+        equivalence: request.equivalence,
+        origin: CURATED_ORIGIN.to_string(),
+        comment: request.comment,
+    };
+
+    match MappingCodec::new().upsert(element.clone()).await {
+        Ok(()) => Ok(HttpResponse::Ok().json(json!({
+            "service": "Mapping Service",
+            "status": "success",
+            "element": element,
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        }))),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(json!({
+            "service": "Mapping Service",
+            "status": "error",
+            "message": format!("Failed to store mapping: {}", e),
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        }))),
+    }
+}
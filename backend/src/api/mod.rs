@@ -1,20 +1,35 @@
-use actix_web::{HttpResponse, Result};
+use actix_web::{web, HttpResponse, Result};
 use serde::{Deserialize, Serialize};
 use crate::dbcodes::{mongo, redis};
+use crate::auth::Authenticator;
 
 // Declare submodules
 pub mod icd_search;
 pub mod namaste_search;
 pub mod terminology_search;
 pub mod autocomplete;
+pub mod fhir;
+pub mod mapping;
+pub mod ingest;
+pub mod audit;
+pub mod import;
+pub mod jobs;
 
-pub use autocomplete::{autocomplete_suggestions, initialize_autocomplete_data};
+pub use autocomplete::{autocomplete_suggestions, autocomplete_status, initialize_autocomplete_data};
 
 
 // Re-export functions from submodules
 pub use icd_search::{icd_search, icd_all, icd_biomedicine, icd_tm2};
 pub use namaste_search::{namaste_search, namaste_all};
 pub use terminology_search::terminology_search;
+pub use fhir::{fhir_metadata, fhir_codesystem_read, fhir_codesystem_lookup, fhir_conceptmap_read, fhir_conceptmap_translate,
+    fhir_valueset_expand, fhir_valueset_expand_by_id, fhir_codesystem_validate_code,
+    fhir_valueset_validate_code, fhir_valueset_validate_code_by_id};
+pub use ingest::fhir_bundle_ingest;
+pub use audit::{audit_service, fhir_audit_search};
+pub use mapping::{mapping_service, mapping_rebuild, mapping_add};
+pub use import::import_namaste;
+pub use jobs::{job_start, job_list, job_status, job_cancel};
 
 // Basic response structure (shared across modules)
 #[derive(Serialize, Deserialize)]
@@ -37,11 +52,21 @@ pub async fn health_check() -> Result<HttpResponse> {
 }
 
 // API Gateway handler
-pub async fn api_gateway() -> Result<HttpResponse> {
+pub async fn api_gateway(authenticator: web::Data<Authenticator>) -> Result<HttpResponse> {
+    let (status, message) = match authenticator.config() {
+        Some(config) => (
+            "active",
+            format!(
+                "Bearer tokens validated against {} signing keys from {} (issuer {})",
+                authenticator.key_count(), config.jwks, config.issuer
+            ),
+        ),
+        None => ("disabled", "No JWKS configured, requests are not authenticated".to_string()),
+    };
     Ok(HttpResponse::Ok().json(ApiResponse {
         service: "API Gateway & OAuth 2.0 + ABHA Authentication".to_string(),
-        status: "active".to_string(),
-        message: "Authentication layer is operational".to_string(),
+        status: status.to_string(),
+        message,
         timestamp: chrono::Utc::now().to_rfc3339(),
     }))
 }
@@ -58,21 +83,28 @@ pub async fn rest_api() -> Result<HttpResponse> {
 
 // Backend Services
 pub async fn terminology_service() -> Result<HttpResponse> {
-    Ok(HttpResponse::Ok().json(ApiResponse {
-        service: "Terminology Service".to_string(),
-        status: "running".to_string(),
-        message: "Managing medical terminologies and codes".to_string(),
-        timestamp: chrono::Utc::now().to_rfc3339(),
-    }))
-}
-
-pub async fn mapping_service() -> Result<HttpResponse> {
-    Ok(HttpResponse::Ok().json(ApiResponse {
-        service: "Mapping Service".to_string(),
-        status: "running".to_string(),
-        message: "Handling terminology mappings".to_string(),
-        timestamp: chrono::Utc::now().to_rfc3339(),
-    }))
+    // Embeddings per semantic search index; null while an index is loading
+    let vector_indexes: serde_json::Map<String, serde_json::Value> = crate::vector::sizes()
+        .into_iter()
+        .map(|(name, size)| (name.to_string(), serde_json::json!(size)))
+        .collect();
+    // Codes per full-text index; null while an index is loading
+    let fulltext_indexes: serde_json::Map<String, serde_json::Value> = crate::fulltext::sizes()
+        .into_iter()
+        .map(|(name, size)| (name.to_string(), serde_json::json!(size)))
+        .collect();
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "service": "Terminology Service",
+        "status": "running",
+        "message": "Managing medical terminologies and codes",
+        // Where concepts are read from: "mongo" or "memory"
+        "storage": crate::repository::get().name(),
+        "vector_indexes": vector_indexes,
+        // Semantic search compares only vectors of this model
+        "embedding_model": crate::embedding::active_model_key(),
+        "fulltext_indexes": fulltext_indexes,
+        "timestamp": chrono::Utc::now().to_rfc3339()
+    })))
 }
 
 pub async fn sync_service() -> Result<HttpResponse> {
@@ -84,15 +116,6 @@ pub async fn sync_service() -> Result<HttpResponse> {
     }))
 }
 
-pub async fn audit_service() -> Result<HttpResponse> {
-    Ok(HttpResponse::Ok().json(ApiResponse {
-        service: "Audit Service".to_string(),
-        status: "running".to_string(),
-        message: "Logging and auditing system activities".to_string(),
-        timestamp: chrono::Utc::now().to_rfc3339(),
-    }))
-}
-
 // Core Components
 pub async fn fhir_engine() -> Result<HttpResponse> {
     Ok(HttpResponse::Ok().json(ApiResponse {
@@ -224,7 +247,7 @@ pub async fn mongodb_collections() -> Result<HttpResponse> {
 pub async fn ayurveda_terminology() -> Result<HttpResponse> {
     match mongo::MongoClient::get_instance().await {
         Ok(client) => {
-            let ayurveda_db = client.get_database_by_name("ayurveda_db");
+            let ayurveda_db = client.get_database_by_name(&crate::config::get().terminology.namaste.database);
             match ayurveda_db.list_collection_names(None).await {
                 Ok(collections) => Ok(HttpResponse::Ok().json(serde_json::json!({
                     "service": "Ayurveda Terminology",
@@ -237,7 +260,7 @@ pub async fn ayurveda_terminology() -> Result<HttpResponse> {
                 Err(e) => Ok(HttpResponse::InternalServerError().json(ApiResponse {
                     service: "Ayurveda Terminology".to_string(),
                     status: "error".to_string(),
-                    message: format!("Failed to access ayurveda_db: {}", e),
+                    message: format!("Failed to access {}: {}", ayurveda_db.name(), e),
                     timestamp: chrono::Utc::now().to_rfc3339(),
                 }))
             }
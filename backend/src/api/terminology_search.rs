@@ -1,19 +1,14 @@
 use actix_web::{web, HttpResponse, Result};
-use serde::{Deserialize, Serialize};
-use crate::codecs::namaste::{NamasteCodec, NamasteFilter, Language};
-use crate::codecs::icd::{IcdCodec, IcdFilter};
-use mongodb::{bson::{doc, Document}, Collection};
-use futures::stream::TryStreamExt;
-use crate::dbcodes::mongo::MongoClient;
-use crate::gemini::embedding::call_gemini_embedding_api;
-
-#[derive(Serialize, Deserialize)]
-pub struct ApiResponse {
-    pub service: String,
-    pub status: String,
-    pub message: String,
-    pub timestamp: String,
-}
+use crate::codecs::namaste::{NamasteCode, NamasteCodec, NamasteFilter, Language};
+use crate::codecs::icd::{IcdCode, IcdCodec, IcdFilter};
+use mongodb::bson::Document;
+use crate::embedding::{self, stored};
+use crate::fulltext;
+use crate::repository;
+use crate::vector::{self, IndexSpec};
+
+// Reciprocal rank fusion constant; larger values flatten the gap between top ranks
+const RRF_K: f64 = 60.0;
 
 #[derive(Debug, Clone)]
 struct SimilarityResult {
@@ -25,7 +20,7 @@ struct SimilarityResult {
 enum SearchMethod {
     Semantic,
     Regex,
-    Auto, // Default: try semantic first, fallback to regex
+    Auto, // Default: lexical and semantic results fused
 }
 
 impl SearchMethod {
@@ -33,7 +28,7 @@ impl SearchMethod {
         match s.to_lowercase().as_str() {
             "semantic" | "vector" | "embedding" => SearchMethod::Semantic,
             "regex" | "text" | "keyword" => SearchMethod::Regex,
-            "auto" | "hybrid" | _ => SearchMethod::Auto,
+            _ => SearchMethod::Auto, // "auto", "hybrid" and anything unrecognised
         }
     }
 }
@@ -55,71 +50,75 @@ fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
     }
 }
 
-// Perform semantic search using application-level cosine similarity
+// Perform semantic search: nearest neighbours from the vector index, or a scan of every
+// stored embedding while the index is still loading. Only vectors of the query's model
+// are compared
 async fn semantic_search_local(
     query_embedding: &[f32],
+    model_key: &str,
     limit: usize,
     threshold: f32,
-    collection_name: &str,
-    database_name: &str,
+    spec: &IndexSpec,
 ) -> anyhow::Result<Vec<SimilarityResult>> {
-    let client = MongoClient::get_instance().await?;
-    let db = client.get_database_by_name(database_name);
-    let collection: Collection<Document> = db.collection(collection_name);
-
-    // Find all documents with embeddings
-    let filter = doc! {"embedding": {"$exists": true, "$ne": []}};
-    let mut cursor = collection.find(filter, None).await?;
-    let mut candidates = Vec::new();
-
-    println!("🔍 Searching in collection: {}.{}", database_name, collection_name);
-
-    // Calculate similarity for each document
-    while let Some(doc) = cursor.try_next().await? {
-        if let Ok(embedding_array) = doc.get_array("embedding") {
-            let embedding_vec: Vec<f32> = embedding_array
-                .iter()
-                .filter_map(|v| v.as_f64().map(|f| f as f32))
+    let repository = repository::get();
+    let hits = match vector::search(spec, model_key, query_embedding, limit) {
+        Some(hits) => {
+            let hits: Vec<(String, f32)> = hits.into_iter().filter(|(_, similarity)| *similarity >= threshold).collect();
+            println!("🧭 Vector index {} returned {} candidates above threshold {}", spec.name, hits.len(), threshold);
+            hits
+        },
+        None => {
+            println!("🔍 Vector index {} not loaded, scanning its stored embeddings", spec.name);
+            let mut candidates: Vec<(String, f32)> = repository
+                .embeddings(spec, model_key)
+                .await?
+                .into_iter()
+                .filter(|(_, embedding)| embedding.len() == query_embedding.len())
+                .map(|(key, embedding)| (key, cosine_similarity(query_embedding, &embedding)))
+                .filter(|(_, similarity)| *similarity >= threshold)
                 .collect();
+            println!("🎯 Found {} candidates above threshold {}", candidates.len(), threshold);
 
-            if !embedding_vec.is_empty() && embedding_vec.len() == query_embedding.len() {
-                let similarity = cosine_similarity(query_embedding, &embedding_vec);
-                if similarity >= threshold {
-                    candidates.push(SimilarityResult {
-                        document: doc,
-                        similarity,
-                    });
-                }
-            }
-        }
+            // Sort by similarity (descending)
+            candidates.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+            candidates.truncate(limit);
+            candidates
+        },
+    };
+    if hits.is_empty() {
+        return Ok(Vec::new());
     }
 
-    println!("🎯 Found {} candidates above threshold {}", candidates.len(), threshold);
-
-    // Sort by similarity (descending)
-    candidates.sort_by(|a, b| b.similarity.partial_cmp(&a.similarity).unwrap_or(std::cmp::Ordering::Equal));
-    candidates.truncate(limit);
-    Ok(candidates)
+    let keys: Vec<String> = hits.iter().map(|(key, _)| key.clone()).collect();
+    let mut documents = repository.documents(spec, &keys).await?;
+    // Keep the ranking; entries deleted since they were indexed drop out
+    Ok(hits
+        .into_iter()
+        .filter_map(|(key, similarity)| documents.remove(&key).map(|document| SimilarityResult { document, similarity }))
+        .collect())
 }
 
 // Format NAMASTE results
 fn format_namaste_results(results: Vec<SimilarityResult>, include_similarity: bool) -> Vec<serde_json::Value> {
     results.into_iter().map(|result| {
         let doc = &result.document;
+        let (nam_code, icd_code) = mongodb::bson::from_document::<NamasteCode>(doc.clone())
+            .map(|code| code.parse_codes())
+            .unwrap_or_default();
         let mut json_result = serde_json::json!({
             "id": doc.get_object_id("_id").map(|oid| oid.to_hex()).unwrap_or_default(),
-            "code": doc.get_str("field_1").unwrap_or_default(),
-            "title": doc.get_str("vyAdhi-viniScayaH").unwrap_or(
-                doc.get_str("vyādhi-viniścayaḥ").unwrap_or(
-                    doc.get_str("व्याधि-विनिश्चयः").unwrap_or_default()
+            "code": &nam_code,
+            "title": doc.get_str("namc_term").unwrap_or(
+                doc.get_str("namc_term_diacritical").unwrap_or(
+                    doc.get_str("namc_term_devanagari").unwrap_or_default()
                 )
             ),
-            "definition": doc.get_str("AYU").unwrap_or_default(),
+            "definition": doc.get_str("namc_code").unwrap_or_default(),
             "source": "NAMASTE",
             "system": "Ayurveda",
             "code_system": "NAMASTE",
-            "nam_code": doc.get_str("field_1").unwrap_or_default(),
-            "icd_code": serde_json::Value::Null
+            "nam_code": &nam_code,
+            "icd_code": icd_code
         });
         
         if include_similarity {
@@ -174,29 +173,57 @@ fn format_icd_results(results: Vec<SimilarityResult>, include_similarity: bool)
 
 // Helper function to extract code system from brackets
 fn extract_code_system(label: &str) -> Option<String> {
-    if let Some(start) = label.find('(') {
-        if let Some(end) = label.find(')') {
-            if end > start {
-                return Some(label[start + 1..end].to_string());
-            }
-        }
+    if let Some(start) = label.find('(')
+        && let Some(end) = label.find(')')
+        && end > start {
+        return Some(label[start + 1..end].to_string());
     }
     None
 }
 
-// Perform regex-based search
-async fn perform_regex_search(
-    query: &web::Query<std::collections::HashMap<String, String>>
-) -> Result<(Vec<serde_json::Value>, usize, usize)> {
-    let search_term = query.get("search").cloned();
-    let limit = query.get("limit").and_then(|l| l.parse().ok());
-    let language = query.get("language")
+// Search fields shared by NAMASTE results from every retriever
+fn decorate_namaste(result: &mut serde_json::Value) {
+    let code_system = result.get("term").and_then(|v| v.as_str()).and_then(extract_code_system)
+        .or_else(|| result.get("display").and_then(|v| v.as_str()).and_then(extract_code_system))
+        .unwrap_or_else(|| "NAMASTE".to_string());
+    let map = result.as_object_mut().unwrap();
+    map.insert("source".to_string(), serde_json::Value::String("NAMASTE".to_string()));
+    map.insert("system".to_string(), serde_json::Value::String("Ayurveda".to_string()));
+    map.insert("code_system".to_string(), serde_json::Value::String(code_system));
+}
+
+fn decorate_icd(result: &mut serde_json::Value) {
+    let map = result.as_object_mut().unwrap();
+    let code_value = map.get("code")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+
+    map.insert("nam_code".to_string(), serde_json::Value::Null);
+    map.insert("icd_code".to_string(),
+              code_value.map(serde_json::Value::String)
+              .unwrap_or(serde_json::Value::Null));
+    map.insert("source".to_string(), serde_json::Value::String("ICD-11".to_string()));
+    map.insert("system".to_string(), serde_json::Value::String("Biomedicine".to_string()));
+    map.insert("code_system".to_string(), serde_json::Value::String("ICD".to_string()));
+}
+
+fn query_language(query: &web::Query<std::collections::HashMap<String, String>>) -> Language {
+    query.get("language")
         .map(|l| match l.as_str() {
             "hindi" => Language::Hindi,
             "english" => Language::English,
             _ => Language::Both,
         })
-        .unwrap_or(Language::Both);
+        .unwrap_or(Language::Both)
+}
+
+// Perform BM25 keyword search; results keep the "regex" search_type clients already expect
+async fn perform_lexical_search(
+    query: &web::Query<std::collections::HashMap<String, String>>,
+    limit: Option<usize>,
+) -> Result<(Vec<serde_json::Value>, usize, usize)> {
+    let search_term = query.get("search").cloned();
+    let language = query_language(query);
 
     let mut combined_results = Vec::new();
     let mut namaste_count = 0;
@@ -210,29 +237,18 @@ async fn perform_regex_search(
         search_term: search_term.clone(),
     };
 
-    match namaste_codec.search_codes(namaste_filter, limit).await {
-        Ok(codes) => {
-            namaste_count = codes.len();
+    match namaste_codec.search_codes_ranked(namaste_filter, limit).await {
+        Ok(ranked) => {
+            namaste_count = ranked.len();
+            let (codes, scores): (Vec<_>, Vec<_>) = ranked.into_iter().unzip();
             let formatted = namaste_codec.format_response(codes, language.clone());
-            for mut result in formatted {
-                result.as_object_mut().unwrap().insert("source".to_string(), serde_json::Value::String("NAMASTE".to_string()));
-                result.as_object_mut().unwrap().insert("system".to_string(), serde_json::Value::String("Ayurveda".to_string()));
-                result.as_object_mut().unwrap().insert("search_type".to_string(), serde_json::Value::String("regex".to_string()));
-
-                let code_system = if let Some(term) = result.get("term").and_then(|v| v.as_str()) {
-                    extract_code_system(term)
-                } else if let Some(display) = result.get("display").and_then(|v| v.as_str()) {
-                    extract_code_system(display)
-                } else {
-                    None
-                };
-
-                if let Some(cs) = code_system {
-                    result.as_object_mut().unwrap().insert("code_system".to_string(), serde_json::Value::String(cs));
-                } else {
-                    result.as_object_mut().unwrap().insert("code_system".to_string(), serde_json::Value::String("NAMASTE".to_string()));
+            for (mut result, score) in formatted.into_iter().zip(scores) {
+                decorate_namaste(&mut result);
+                let map = result.as_object_mut().unwrap();
+                if let Some(score) = score {
+                    map.insert("score".to_string(), serde_json::json!(score));
                 }
-
+                map.insert("search_type".to_string(), serde_json::Value::String("regex".to_string()));
                 combined_results.push(result);
             }
         },
@@ -247,31 +263,32 @@ async fn perform_regex_search(
         parent_filter: None,
     };
 
-    match icd_codec.search_codes(icd_filter, limit).await {
-        Ok(codes) => {
-            icd_count = codes.len();
+    match icd_codec.search_codes_ranked(icd_filter, limit).await {
+        Ok(ranked) => {
+            icd_count = ranked.len();
+            let (codes, scores): (Vec<_>, Vec<_>) = ranked.into_iter().unzip();
             let icd_formatted = icd_codec.format_response(codes);
-            for mut result in icd_formatted {
-                if let Some(map) = result.as_object_mut() {
-                    let code_value = map.get("code")
-                        .and_then(|v| v.as_str())
-                        .map(|s| s.to_string());
-
-                    map.insert("nam_code".to_string(), serde_json::Value::Null);
-                    map.insert("icd_code".to_string(),
-                              code_value.map(serde_json::Value::String)
-                              .unwrap_or(serde_json::Value::Null));
-                    map.insert("source".to_string(), serde_json::Value::String("ICD-11".to_string()));
-                    map.insert("system".to_string(), serde_json::Value::String("Biomedicine".to_string()));
-                    map.insert("code_system".to_string(), serde_json::Value::String("ICD".to_string()));
-                    map.insert("search_type".to_string(), serde_json::Value::String("regex".to_string()));
+            for (mut result, score) in icd_formatted.into_iter().zip(scores) {
+                decorate_icd(&mut result);
+                let map = result.as_object_mut().unwrap();
+                if let Some(score) = score {
+                    map.insert("score".to_string(), serde_json::json!(score));
                 }
+                map.insert("search_type".to_string(), serde_json::Value::String("regex".to_string()));
                 combined_results.push(result);
             }
         },
         Err(e) => eprintln!("ICD search failed: {}", e),
     }
 
+    // Best BM25 score first across both code systems; the sort is stable, so unscored
+    // Mongo results keep their order
+    combined_results.sort_by(|a, b| {
+        let score_a = a["score"].as_f64().unwrap_or(0.0);
+        let score_b = b["score"].as_f64().unwrap_or(0.0);
+        score_b.total_cmp(&score_a)
+    });
+
     // Apply global limit if specified
     if let Some(global_limit) = limit {
         combined_results.truncate(global_limit);
@@ -280,6 +297,116 @@ async fn perform_regex_search(
     Ok((combined_results, namaste_count, icd_count))
 }
 
+// Nearest neighbours from both collections in the lexical result shape, most similar first
+async fn perform_semantic_candidates(
+    query_embedding: &[f32],
+    model_key: &str,
+    limit: usize,
+    threshold: f32,
+    language: Language,
+) -> Vec<serde_json::Value> {
+    let mut candidates = Vec::new();
+
+    match semantic_search_local(query_embedding, model_key, limit, threshold, &vector::NAMASTE).await {
+        Ok(results) => {
+            let codec = NamasteCodec::new();
+            for result in results {
+                let Ok(code) = mongodb::bson::from_document::<NamasteCode>(result.document) else { continue };
+                for mut formatted in codec.format_response(vec![code], language.clone()) {
+                    decorate_namaste(&mut formatted);
+                    formatted.as_object_mut().unwrap().insert("similarity".to_string(), serde_json::json!(result.similarity));
+                    candidates.push(formatted);
+                }
+            }
+        },
+        Err(e) => println!("❌ NAMASTE semantic search failed: {}", e),
+    }
+
+    match semantic_search_local(query_embedding, model_key, limit, threshold, &vector::ICD).await {
+        Ok(results) => {
+            let codec = IcdCodec::new();
+            for result in results {
+                let Ok(code) = mongodb::bson::from_document::<IcdCode>(result.document) else { continue };
+                for mut formatted in codec.format_response(vec![code]) {
+                    decorate_icd(&mut formatted);
+                    formatted.as_object_mut().unwrap().insert("similarity".to_string(), serde_json::json!(result.similarity));
+                    candidates.push(formatted);
+                }
+            }
+        },
+        Err(e) => println!("❌ ICD semantic search failed: {}", e),
+    }
+
+    candidates.sort_by(|a, b| {
+        let score_a = a["similarity"].as_f64().unwrap_or(0.0);
+        let score_b = b["similarity"].as_f64().unwrap_or(0.0);
+        score_b.total_cmp(&score_a)
+    });
+    candidates
+}
+
+// Identity of a concept across retrievers
+fn result_key(result: &serde_json::Value) -> Option<String> {
+    match result["source"].as_str()? {
+        "NAMASTE" => Some(format!("namaste:{}", result["namc_id"])),
+        _ => result["id"].as_str().map(|id| format!("icd:{}", id)),
+    }
+}
+
+// Whether the search term is the result's NAMASTE or ICD code, e.g. "SR11" or "AAA-1"
+fn is_exact_code_hit(result: &serde_json::Value, search_term: &str) -> bool {
+    let term = search_term.trim();
+    ["code", "nam_code", "icd_code"]
+        .iter()
+        .filter_map(|field| result[*field].as_str())
+        .any(|code| !code.is_empty() && code.trim().eq_ignore_ascii_case(term))
+}
+
+// Reciprocal rank fusion: each list adds 1 / (k + rank). Exact code hits always come first.
+// Results carry their component scores and ranks
+fn fuse_rankings(
+    lexical: Vec<serde_json::Value>,
+    semantic: Vec<serde_json::Value>,
+    search_term: &str,
+    limit: usize,
+) -> Vec<serde_json::Value> {
+    let mut fused: Vec<(String, serde_json::Value, f64, bool)> = Vec::new();
+    let mut positions: std::collections::HashMap<String, usize> = std::collections::HashMap::new();
+
+    for (list, rank_field, score_field) in [(lexical, "lexical_rank", "score"), (semantic, "semantic_rank", "similarity")] {
+        for (index, result) in list.into_iter().enumerate() {
+            let Some(key) = result_key(&result) else { continue };
+            let rank = index + 1;
+            let contribution = 1.0 / (RRF_K + rank as f64);
+            let position = *positions.entry(key.clone()).or_insert_with(|| {
+                let exact = is_exact_code_hit(&result, search_term);
+                fused.push((key, result.clone(), 0.0, exact));
+                fused.len() - 1
+            });
+            let entry = &mut fused[position];
+            entry.2 += contribution;
+            let map = entry.1.as_object_mut().unwrap();
+            map.insert(rank_field.to_string(), serde_json::json!(rank));
+            if let Some(score) = result.get(score_field) {
+                map.insert(score_field.to_string(), score.clone());
+            }
+        }
+    }
+
+    fused.sort_by(|a, b| b.3.cmp(&a.3).then(b.2.total_cmp(&a.2)));
+    fused
+        .into_iter()
+        .take(limit)
+        .map(|(_, mut result, rrf_score, exact)| {
+            let map = result.as_object_mut().unwrap();
+            map.insert("rrf_score".to_string(), serde_json::json!(rrf_score));
+            map.insert("exact_code_match".to_string(), serde_json::json!(exact));
+            map.insert("search_type".to_string(), serde_json::Value::String("hybrid".to_string()));
+            result
+        })
+        .collect()
+}
+
 // Main search function with explicit search method control
 pub async fn terminology_search(
     query: web::Query<std::collections::HashMap<String, String>>
@@ -309,29 +436,32 @@ pub async fn terminology_search(
         SearchMethod::Regex => {
             // Force regex search
             println!("📝 Performing regex search (forced)");
-            let (results, namaste_count, icd_count) = perform_regex_search(&query).await?;
+            let (results, namaste_count, icd_count) = perform_lexical_search(&query, query.get("limit").and_then(|l| l.parse().ok())).await?;
             
-            return Ok(HttpResponse::Ok().json(serde_json::json!({
+            let did_you_mean = if results.is_empty() { fulltext::did_you_mean(&search_term) } else { None };
+
+            Ok(HttpResponse::Ok().json(serde_json::json!({
                 "service": "Regex Terminology Search",
                 "search_term": search_term,
                 "total_results": results.len(),
                 "namaste_results": namaste_count,
                 "icd_results": icd_count,
                 "results": results,
+                "did_you_mean": did_you_mean,
                 "search_type": "regex",
                 "method_requested": "regex",
                 "timestamp": chrono::Utc::now().to_rfc3339()
-            })));
+            })))
         },
         SearchMethod::Semantic => {
             // Force semantic search (fail if not possible)
-            let api_key = match std::env::var("GEMINI_KEY") {
-                Ok(key) if !key.is_empty() => key,
-                _ => {
+            let provider = match embedding::provider() {
+                Some(provider) => provider,
+                None => {
                     return Ok(HttpResponse::BadRequest().json(serde_json::json!({
                         "service": "Semantic Terminology Search",
                         "status": "error",
-                        "message": "Semantic search requested but GEMINI_KEY not available",
+                        "message": "Semantic search requested but no embedding provider is configured (set EMBEDDING_PROVIDER or GEMINI_KEY)",
                         "search_term": search_term,
                         "method_requested": "semantic",
                         "timestamp": chrono::Utc::now().to_rfc3339()
@@ -339,7 +469,7 @@ pub async fn terminology_search(
                 }
             };
 
-            let query_embedding = match call_gemini_embedding_api(&api_key, &search_term).await {
+            let query_embedding = match provider.embed(&search_term).await {
                 Ok(embedding) => {
                     println!("✅ Generated query embedding with {} dimensions", embedding.len());
                     embedding
@@ -357,13 +487,14 @@ pub async fn terminology_search(
             };
 
             println!("🔍 Performing semantic search (forced)");
+            let model_key = stored::key_of(provider.as_ref());
             
             let mut all_results = Vec::new();
             let mut semantic_namaste_count = 0;
             let mut semantic_icd_count = 0;
 
             // Semantic search on NAMASTE collection
-            match semantic_search_local(&query_embedding, limit, threshold, "namc_codes", "ayurveda_db").await {
+            match semantic_search_local(&query_embedding, &model_key, limit, threshold, &vector::NAMASTE).await {
                 Ok(results) => {
                     semantic_namaste_count = results.len();
                     if semantic_namaste_count > 0 {
@@ -376,7 +507,7 @@ pub async fn terminology_search(
             }
 
             // Semantic search on ICD collection  
-            match semantic_search_local(&query_embedding, limit, threshold, "icd11_entities", "icd11_database").await {
+            match semantic_search_local(&query_embedding, &model_key, limit, threshold, &vector::ICD).await {
                 Ok(results) => {
                     semantic_icd_count = results.len();
                     if semantic_icd_count > 0 {
@@ -398,7 +529,7 @@ pub async fn terminology_search(
             // Apply global limit
             all_results.truncate(limit);
 
-            return Ok(HttpResponse::Ok().json(serde_json::json!({
+            Ok(HttpResponse::Ok().json(serde_json::json!({
                 "service": "Semantic Terminology Search",
                 "search_term": search_term,
                 "total_results": all_results.len(),
@@ -407,133 +538,98 @@ pub async fn terminology_search(
                 "results": all_results,
                 "search_type": "semantic",
                 "method_requested": "semantic",
+                "embedding_provider": provider.name(),
+                "embedding_model": provider.model(),
                 "threshold": threshold,
                 "timestamp": chrono::Utc::now().to_rfc3339()
-            })));
+            })))
         },
         SearchMethod::Auto => {
-            // Auto mode: try semantic first, fallback to regex
-            let api_key = match std::env::var("GEMINI_KEY") {
-                Ok(key) if !key.is_empty() => key,
-                _ => {
-                    println!("❌ No GEMINI_KEY found, falling back to regex search");
-                    let (results, namaste_count, icd_count) = perform_regex_search(&query).await?;
-                    
-                    return Ok(HttpResponse::Ok().json(serde_json::json!({
-                        "service": "Auto Terminology Search (Regex Fallback)",
-                        "search_term": search_term,
-                        "total_results": results.len(),
-                        "namaste_results": namaste_count,
-                        "icd_results": icd_count,
-                        "results": results,
-                        "search_type": "regex",
-                        "method_requested": "auto",
-                        "fallback_reason": "no_gemini_key",
-                        "timestamp": chrono::Utc::now().to_rfc3339()
-                    })));
-                }
-            };
-
-            let query_embedding = match call_gemini_embedding_api(&api_key, &search_term).await {
-                Ok(embedding) => {
-                    println!("✅ Generated query embedding with {} dimensions", embedding.len());
-                    embedding
+            // Auto mode: lexical and semantic retrievers together, merged by reciprocal rank fusion
+            let (lexical_results, _, _) = perform_lexical_search(&query, Some(limit)).await?;
+
+            let mut fallback_reason = None;
+            let mut provider_name = None;
+            let mut model_name = None;
+            let mut semantic_results = Vec::new();
+            match embedding::provider() {
+                None => {
+                    println!("❌ No embedding provider configured, ranking by keywords only");
+                    fallback_reason = Some("no_embedding_provider");
                 },
-                Err(e) => {
-                    println!("❌ Failed to generate embedding: {}, falling back to regex search", e);
-                    let (results, namaste_count, icd_count) = perform_regex_search(&query).await?;
-                    
-                    return Ok(HttpResponse::Ok().json(serde_json::json!({
-                        "service": "Auto Terminology Search (Regex Fallback)",
-                        "search_term": search_term,
-                        "total_results": results.len(),
-                        "namaste_results": namaste_count,
-                        "icd_results": icd_count,
-                        "results": results,
-                        "search_type": "regex",
-                        "method_requested": "auto",
-                        "fallback_reason": "embedding_generation_failed",
-                        "timestamp": chrono::Utc::now().to_rfc3339()
-                    })));
-                }
-            };
-
-            println!("🔍 Performing semantic search (auto mode)");
-
-            let mut all_results = Vec::new();
-            let mut semantic_namaste_count = 0;
-            let mut semantic_icd_count = 0;
-
-            // Semantic search on NAMASTE collection
-            match semantic_search_local(&query_embedding, limit, threshold, "namc_codes", "ayurveda_db").await {
-                Ok(results) => {
-                    semantic_namaste_count = results.len();
-                    if semantic_namaste_count > 0 {
-                        println!("✅ Found {} NAMASTE semantic results", semantic_namaste_count);
-                    }
-                    let formatted_results = format_namaste_results(results, true);
-                    all_results.extend(formatted_results);
+                Some(provider) => match provider.embed(&search_term).await {
+                    Ok(query_embedding) => {
+                        println!("✅ Generated query embedding with {} dimensions", query_embedding.len());
+                        provider_name = Some(provider.name());
+                        model_name = Some(provider.model().to_string());
+                        let model_key = stored::key_of(provider.as_ref());
+                        semantic_results = perform_semantic_candidates(&query_embedding, &model_key, limit, threshold, query_language(&query)).await;
+                    },
+                    Err(e) => {
+                        println!("❌ Failed to generate embedding: {}, ranking by keywords only", e);
+                        fallback_reason = Some("embedding_generation_failed");
+                    },
                 },
-                Err(e) => println!("❌ NAMASTE semantic search failed: {}", e),
             }
 
-            // Semantic search on ICD collection  
-            match semantic_search_local(&query_embedding, limit, threshold, "icd11_entities", "icd11_database").await {
-                Ok(results) => {
-                    semantic_icd_count = results.len();
-                    if semantic_icd_count > 0 {
-                        println!("✅ Found {} ICD semantic results", semantic_icd_count);
-                    }
-                    let formatted_results = format_icd_results(results, true);
-                    all_results.extend(formatted_results);
-                },
-                Err(e) => println!("❌ ICD semantic search failed: {}", e),
-            }
-
-            // Sort by similarity score (descending)
-            all_results.sort_by(|a, b| {
-                let score_a = a["similarity"].as_f64().unwrap_or(0.0);
-                let score_b = b["similarity"].as_f64().unwrap_or(0.0);
-                score_b.partial_cmp(&score_a).unwrap_or(std::cmp::Ordering::Equal)
-            });
+            let lexical_count = lexical_results.len();
+            let semantic_count = semantic_results.len();
+            let results = fuse_rankings(lexical_results, semantic_results, &search_term, limit);
+            let namaste_count = results.iter().filter(|r| r["source"] == "NAMASTE").count();
+            let icd_count = results.len() - namaste_count;
 
-            // Apply global limit
-            all_results.truncate(limit);
-
-            // If semantic search returned no results, fall back to regex search
-            if all_results.is_empty() {
-                println!("❌ No semantic results found, falling back to regex search");
-                let (results, namaste_count, icd_count) = perform_regex_search(&query).await?;
-                
-                return Ok(HttpResponse::Ok().json(serde_json::json!({
-                    "service": "Auto Terminology Search (Regex Fallback)",
-                    "search_term": search_term,
-                    "total_results": results.len(),
-                    "namaste_results": namaste_count,
-                    "icd_results": icd_count,
-                    "results": results,
-                    "search_type": "regex",
-                    "method_requested": "auto",
-                    "fallback_reason": "no_semantic_results",
-                    "threshold": threshold,
-                    "timestamp": chrono::Utc::now().to_rfc3339()
-                })));
-            }
+            println!("✅ Hybrid search fused {} lexical and {} semantic candidates into {} results",
+                     lexical_count, semantic_count, results.len());
 
-            println!("✅ Semantic search completed with {} total results", all_results.len());
+            let did_you_mean = if results.is_empty() { fulltext::did_you_mean(&search_term) } else { None };
 
-            return Ok(HttpResponse::Ok().json(serde_json::json!({
-                "service": "Auto Semantic Terminology Search",
+            Ok(HttpResponse::Ok().json(serde_json::json!({
+                "service": "Auto Hybrid Terminology Search",
                 "search_term": search_term,
-                "total_results": all_results.len(),
-                "namaste_results": semantic_namaste_count,
-                "icd_results": semantic_icd_count,
-                "results": all_results,
-                "search_type": "semantic",
+                "total_results": results.len(),
+                "namaste_results": namaste_count,
+                "icd_results": icd_count,
+                "lexical_candidates": lexical_count,
+                "semantic_candidates": semantic_count,
+                "results": results,
+                "did_you_mean": did_you_mean,
+                "search_type": "hybrid",
                 "method_requested": "auto",
+                "fusion": "reciprocal_rank_fusion",
+                "rrf_k": RRF_K,
+                "fallback_reason": fallback_reason,
+                "embedding_provider": provider_name,
+                "embedding_model": model_name,
                 "threshold": threshold,
                 "timestamp": chrono::Utc::now().to_rfc3339()
-            })));
+            })))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn icd(id: &str, code: &str) -> serde_json::Value {
+        serde_json::json!({ "id": id, "code": code, "icd_code": code, "source": "ICD-11" })
+    }
+
+    #[test]
+    fn test_fusion_rewards_agreement_and_pins_exact_codes() {
+        let lexical = vec![icd("a", "SR11"), icd("b", "SR12"), icd("c", "SR13")];
+        let semantic = vec![icd("d", "1A00"), icd("c", "SR13"), icd("e", "1A01")];
+
+        let fused = fuse_rankings(lexical, semantic, "sr11", 10);
+        let ids: Vec<&str> = fused.iter().map(|r| r["id"].as_str().unwrap()).collect();
+        // The exact code hit leads; "c" appears in both lists and outranks single-list results
+        assert_eq!(ids, vec!["a", "c", "d", "b", "e"]);
+        assert_eq!(fused[0]["exact_code_match"], true);
+        assert_eq!(fused[1]["lexical_rank"], 3);
+        assert_eq!(fused[1]["semantic_rank"], 2);
+        assert!(fused[2].get("lexical_rank").is_none());
+
+        assert_eq!(fuse_rankings(vec![icd("a", "SR11")], Vec::new(), "fever", 10)[0]["exact_code_match"], false);
+        assert_eq!(fuse_rankings(vec![icd("a", "SR11"), icd("b", "SR12")], Vec::new(), "x", 1).len(), 1);
+    }
+}
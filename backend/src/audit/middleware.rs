@@ -0,0 +1,113 @@
+use std::sync::atomic::{AtomicI64, AtomicUsize, Ordering};
+use std::time::Instant;
+use actix_web::{body, web, Error, HttpMessage};
+use actix_web::body::{BodySize, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header;
+use actix_web::middleware::Next;
+use mongodb::bson;
+
+use crate::auth::Claims;
+use crate::codecs::audit::{AuditCodec, AuditRecord};
+use crate::config::{self, Storage};
+use super::{classify, distinct_codes, outcome, MAX_AUDITED_BODY, MAX_AUDITED_CODES};
+
+// Writes waiting on MongoDB at once; more are dropped rather than piling up
+const MAX_PENDING_WRITES: usize = 64;
+// After a failed write, events are dropped (and the warning held back) for this long
+const RETRY_AFTER_SECONDS: i64 = 60;
+
+static PENDING_WRITES: AtomicUsize = AtomicUsize::new(0);
+// Unix time until which MongoDB is taken to be unreachable
+static UNAVAILABLE_UNTIL: AtomicI64 = AtomicI64::new(0);
+
+// Store the event in the background, unless there is no database to store it in
+fn store(record: AuditRecord) {
+    if config::get().terminology.storage == Storage::Memory {
+        return;
+    }
+    if chrono::Utc::now().timestamp() < UNAVAILABLE_UNTIL.load(Ordering::Relaxed) {
+        return;
+    }
+    if PENDING_WRITES.fetch_add(1, Ordering::Relaxed) >= MAX_PENDING_WRITES {
+        PENDING_WRITES.fetch_sub(1, Ordering::Relaxed);
+        return;
+    }
+    actix_web::rt::spawn(async move {
+        let result = AuditCodec::new().insert(&record).await;
+        PENDING_WRITES.fetch_sub(1, Ordering::Relaxed);
+        if let Err(e) = result {
+            let now = chrono::Utc::now().timestamp();
+            // Only the first failure of a window is reported
+            if UNAVAILABLE_UNTIL.swap(now + RETRY_AFTER_SECONDS, Ordering::Relaxed) <= now {
+                println!("⚠️  Failed to store audit event for {} {}: {} (dropping events for {}s)", record.method, record.path, e, RETRY_AFTER_SECONDS);
+            }
+        }
+    });
+}
+
+// Record the request as an AuditEvent once the response is ready. Runs outside the bearer
+// middleware so rejected requests are logged too; the write happens in the background
+pub async fn audit_trail<B: MessageBody + 'static>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let started = Instant::now();
+    let method = req.method().clone();
+    let path = req.path().to_string();
+    let query = web::Query::<Vec<(String, String)>>::from_query(req.query_string())
+        .map(web::Query::into_inner)
+        .unwrap_or_default();
+    let client_address = req.connection_info().realip_remote_addr().map(str::to_string);
+
+    let response = next.call(req).await?;
+    let actor = response.request().extensions().get::<Claims>().and_then(|claims| claims.sub.clone());
+    let status = response.status().as_u16();
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.contains("json"));
+
+    // Buffer small JSON bodies to see which codes were returned, then hand them on unchanged;
+    // large or streamed ones pass through untouched and their codes go unrecorded
+    let (request, response) = response.into_parts();
+    let (head, response_body) = response.into_parts();
+    let small = match response_body.size() {
+        BodySize::None => true,
+        BodySize::Sized(size) => size <= MAX_AUDITED_BODY,
+        BodySize::Stream => false,
+    };
+    let (codes, response) = if !is_json {
+        (Some(Vec::new()), head.set_body(response_body).map_into_boxed_body())
+    } else if small {
+        let bytes = body::to_bytes(response_body)
+            .await
+            .map_err(|e| actix_web::error::ErrorInternalServerError(e.into()))?;
+        let codes = serde_json::from_slice(&bytes).map(|json| distinct_codes(&json)).unwrap_or_default();
+        (Some(codes), head.set_body(bytes).map_into_boxed_body())
+    } else {
+        (None, head.set_body(response_body).map_into_boxed_body())
+    };
+
+    let (action, interaction) = classify(&method, &path);
+    let record = AuditRecord {
+        event_id: uuid::Uuid::new_v4().to_string(),
+        recorded: bson::DateTime::now(),
+        actor,
+        client_address,
+        method: method.to_string(),
+        path,
+        action: action.to_string(),
+        interaction: interaction.to_string(),
+        query,
+        codes_total: codes.as_ref().map(|codes| codes.len() as i64),
+        codes: codes.unwrap_or_default().into_iter().take(MAX_AUDITED_CODES).collect(),
+        status: status as i32,
+        outcome: outcome(status).to_string(),
+        latency_ms: started.elapsed().as_millis() as i64,
+    };
+    store(record);
+
+    Ok(ServiceResponse::new(request, response))
+}
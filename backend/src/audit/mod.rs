@@ -0,0 +1,138 @@
+// Access logging: every request is stored as an AuditEvent (India EHR Standards 2016)
+pub mod middleware;
+
+use actix_web::http::Method;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use mongodb::bson::{self, doc, Document};
+use serde_json::Value;
+
+pub use middleware::audit_trail;
+
+// Codes kept per event; the total is still recorded
+pub const MAX_AUDITED_CODES: usize = 100;
+// Responses read for their codes; larger ones (full CodeSystems, expansions) stream through
+pub const MAX_AUDITED_BODY: u64 = 1024 * 1024;
+
+// Requests that run a job rather than read or write a resource, when not a GET
+const OPERATION_PATHS: [&str; 5] = [
+    "/autocomplete/initialize",
+    "/services/generate-embeddings",
+    "/services/mapping/rebuild",
+    "/services/import/namaste",
+    "/jobs",
+];
+
+// FHIR AuditEventAction and restful-interaction code for a request
+pub fn classify(method: &Method, path: &str) -> (&'static str, &'static str) {
+    let path = path.trim_end_matches('/');
+    let runs_job = OPERATION_PATHS.contains(&path) || (path.starts_with("/jobs/") && path.ends_with("/cancel"));
+    if (runs_job && *method != Method::GET) || path.contains('$') {
+        return ("E", "operation");
+    }
+    match *method {
+        Method::POST if path == "/fhir" => ("C", "transaction"),
+        Method::POST => ("C", "create"),
+        Method::PUT | Method::PATCH => ("U", "update"),
+        Method::DELETE => ("D", "delete"),
+        _ if path.split('/').filter(|s| !s.is_empty()).count() == 3 && path.starts_with("/fhir/") => ("R", "read"),
+        _ => ("R", "search-type"),
+    }
+}
+
+pub fn outcome(status: u16) -> &'static str {
+    match status {
+        500.. => "8",
+        400.. => "4",
+        _ => "0",
+    }
+}
+
+// Codes in a response: "code" next to a display/title/system, and the nam_code / icd_code search fields
+fn collect_codes(value: &Value, codes: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            let is_concept = ["display", "title", "system"].iter().any(|key| map.contains_key(*key));
+            if is_concept && let Some(Value::String(code)) = map.get("code") {
+                codes.push(code.clone());
+            }
+            for key in ["nam_code", "icd_code"] {
+                if let Some(Value::String(code)) = map.get(key)
+                    && !code.is_empty() {
+                    codes.push(code.clone());
+                }
+            }
+            map.values().for_each(|child| collect_codes(child, codes));
+        },
+        Value::Array(items) => items.iter().for_each(|item| collect_codes(item, codes)),
+        _ => {},
+    }
+}
+
+// Distinct codes in first-seen order
+pub fn distinct_codes(body: &Value) -> Vec<String> {
+    let mut codes = Vec::new();
+    collect_codes(body, &mut codes);
+    let mut seen = std::collections::HashSet::new();
+    codes.retain(|code| seen.insert(code.clone()));
+    codes
+}
+
+// A FHIR date search value, e.g. "ge2025-01-01" or "lt2025-01-01T10:00:00Z", as a Mongo comparison.
+// A date covers the whole day, a dateTime the whole second
+pub fn date_condition(value: &str) -> Option<Document> {
+    let (prefix, date) = match value.get(..2) {
+        Some(p @ ("eq" | "ne" | "gt" | "lt" | "ge" | "le")) => (p, &value[2..]),
+        _ => ("eq", value),
+    };
+    let (start, end) = match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+        Ok(day) => {
+            let start = day.and_hms_opt(0, 0, 0)?.and_utc();
+            (start, start + Duration::days(1))
+        },
+        Err(_) => {
+            let start = DateTime::parse_from_rfc3339(date).ok()?.with_timezone(&Utc);
+            (start, start + Duration::seconds(1))
+        },
+    };
+    let start = bson::DateTime::from_millis(start.timestamp_millis());
+    let end = bson::DateTime::from_millis(end.timestamp_millis());
+    Some(match prefix {
+        "eq" => doc! { "$gte": start, "$lt": end },
+        "ne" => doc! { "$not": { "$gte": start, "$lt": end } },
+        "gt" => doc! { "$gte": end },
+        "ge" => doc! { "$gte": start },
+        "lt" => doc! { "$lt": start },
+        _ => doc! { "$lt": end },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_classification_codes_and_date_conditions() {
+        assert_eq!(classify(&Method::POST, "/autocomplete/initialize"), ("E", "operation"));
+        assert_eq!(classify(&Method::POST, "/jobs"), ("E", "operation"));
+        assert_eq!(classify(&Method::POST, "/jobs/42/cancel"), ("E", "operation"));
+        assert_eq!(classify(&Method::GET, "/jobs"), ("R", "search-type"));
+        assert_eq!(classify(&Method::GET, "/fhir/CodeSystem/$lookup"), ("E", "operation"));
+        assert_eq!(classify(&Method::GET, "/fhir/CodeSystem/namaste"), ("R", "read"));
+        assert_eq!(classify(&Method::GET, "/icd/search"), ("R", "search-type"));
+        assert_eq!(outcome(403), "4");
+
+        let body = json!({
+            "results": [{ "nam_code": "AAA-1", "icd_code": "SR11" }, { "code": "AAB", "title": "x" }],
+            "issue": [{ "code": "invalid", "severity": "error" }]
+        });
+        assert_eq!(distinct_codes(&body), vec!["AAA-1", "SR11", "AAB"]);
+
+        let day = date_condition("ge2025-03-01").unwrap();
+        assert_eq!(day.get_datetime("$gte").unwrap().try_to_rfc3339_string().unwrap(), "2025-03-01T00:00:00Z");
+        let le = date_condition("le2025-03-01").unwrap();
+        assert_eq!(le.get_datetime("$lt").unwrap().try_to_rfc3339_string().unwrap(), "2025-03-02T00:00:00Z");
+        assert!(date_condition("eq2025-03-01T10:00:00+05:30").is_some());
+        assert!(date_condition("yesterday").is_none());
+    }
+}
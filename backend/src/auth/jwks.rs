@@ -0,0 +1,103 @@
+use std::path::PathBuf;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+use jsonwebtoken::{Algorithm, DecodingKey};
+use jsonwebtoken::jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet};
+
+// A JWKS URL is re-fetched at most this often, when a token names a key id we do not know
+const REFRESH_INTERVAL: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone)]
+pub enum JwksSource {
+    File(PathBuf),
+    Url(String),
+}
+
+impl std::fmt::Display for JwksSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JwksSource::File(path) => write!(f, "{}", path.display()),
+            JwksSource::Url(url) => write!(f, "{}", url),
+        }
+    }
+}
+
+// Signing keys of the token issuer, loaded from a JWKS file or URL
+pub struct JwksStore {
+    source: JwksSource,
+    keys: RwLock<JwkSet>,
+    last_refresh: RwLock<Instant>,
+}
+
+impl JwksStore {
+    pub async fn load(source: JwksSource) -> Result<Self, Box<dyn std::error::Error>> {
+        let keys = Self::fetch(&source).await?;
+        println!("🔑 Loaded {} signing keys from {}", keys.keys.len(), source);
+        Ok(JwksStore {
+            source,
+            keys: RwLock::new(keys),
+            last_refresh: RwLock::new(Instant::now()),
+        })
+    }
+
+    async fn fetch(source: &JwksSource) -> Result<JwkSet, Box<dyn std::error::Error>> {
+        let body = match source {
+            JwksSource::File(path) => tokio::fs::read_to_string(path).await?,
+            JwksSource::Url(url) => reqwest::get(url).await?.error_for_status()?.text().await?,
+        };
+        Ok(serde_json::from_str(&body)?)
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.read().map(|keys| keys.keys.len()).unwrap_or(0)
+    }
+
+    fn find(&self, kid: Option<&str>) -> Option<Jwk> {
+        let keys = self.keys.read().ok()?;
+        match kid {
+            Some(kid) => keys.find(kid).cloned(),
+            // Without a kid only an unambiguous single-key set can be used
+            None if keys.keys.len() == 1 => keys.keys.first().cloned(),
+            None => None,
+        }
+    }
+
+    // Re-fetch a JWKS URL so rotated keys are picked up; files are read once at startup
+    async fn refresh(&self) {
+        let JwksSource::Url(_) = self.source else { return };
+        let due = self.last_refresh.read().map(|at| at.elapsed() >= REFRESH_INTERVAL).unwrap_or(false);
+        if !due {
+            return;
+        }
+        if let Ok(mut at) = self.last_refresh.write() {
+            *at = Instant::now();
+        }
+        match Self::fetch(&self.source).await {
+            Ok(keys) => {
+                if let Ok(mut current) = self.keys.write() {
+                    *current = keys;
+                }
+            },
+            Err(e) => println!("⚠️  Failed to refresh JWKS from {}: {}", self.source, e),
+        }
+    }
+
+    // The verification key for a token, and the one algorithm that key may be used with
+    pub async fn decoding_key(&self, kid: Option<&str>) -> Result<(DecodingKey, Algorithm), String> {
+        let jwk = match self.find(kid) {
+            Some(jwk) => jwk,
+            None => {
+                self.refresh().await;
+                self.find(kid).ok_or_else(|| format!("No signing key matches kid {:?}", kid))?
+            },
+        };
+
+        let algorithm = match &jwk.algorithm {
+            AlgorithmParameters::RSA(_) => Algorithm::RS256,
+            AlgorithmParameters::EllipticCurve(params) if params.curve == EllipticCurve::P256 => Algorithm::ES256,
+            _ => return Err("Signing key is neither RSA nor P-256".to_string()),
+        };
+        let key = DecodingKey::from_jwk(&jwk).map_err(|e| format!("Unusable signing key: {}", e))?;
+        Ok((key, algorithm))
+    }
+}
@@ -0,0 +1,108 @@
+use actix_web::{web, Error, HttpMessage, HttpResponse};
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header;
+use actix_web::middleware::Next;
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use serde_json::json;
+
+use super::{required_access, Access, AuthError, Authenticator};
+
+fn reject<B>(req: ServiceRequest, error: AuthError) -> ServiceResponse<EitherBody<B>> {
+    // RFC 6750 error codes; a request without any token gets no error code
+    let (mut response, challenge) = match &error {
+        AuthError::MissingToken => (HttpResponse::Unauthorized(), "Bearer realm=\"codevedas\"".to_string()),
+        AuthError::InvalidToken(_) => (
+            HttpResponse::Unauthorized(),
+            format!("Bearer realm=\"codevedas\", error=\"invalid_token\", error_description=\"{}\"", error.to_string().replace('"', "'")),
+        ),
+        AuthError::InsufficientScope(scope) => (
+            HttpResponse::Forbidden(),
+            format!("Bearer realm=\"codevedas\", error=\"insufficient_scope\", scope=\"{}\"", scope),
+        ),
+    };
+
+    let response = response
+        .insert_header((header::WWW_AUTHENTICATE, challenge))
+        .json(json!({
+            "service": "API Gateway & OAuth 2.0 + ABHA Authentication",
+            "status": "error",
+            "message": error.to_string(),
+            "timestamp": chrono::Utc::now().to_rfc3339()
+        }));
+    req.into_response(response).map_into_right_body()
+}
+
+// Validate the bearer token and the scope the route needs; verified claims are left in the request extensions
+pub async fn bearer_auth<B: MessageBody + 'static>(
+    mut req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    let access = required_access(req.method(), req.path());
+    let authenticator = req
+        .app_data::<web::Data<Authenticator>>()
+        .filter(|auth| auth.config().is_some())
+        .cloned();
+    let Some(authenticator) = authenticator.filter(|_| access != Access::Public) else {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    };
+
+    let token = match req.extract::<BearerAuth>().await {
+        Ok(bearer) => bearer.token().to_string(),
+        Err(_) => return Ok(reject(req, AuthError::MissingToken)),
+    };
+    let claims = match authenticator.verify(&token).await {
+        Ok(claims) => claims,
+        Err(e) => return Ok(reject(req, e)),
+    };
+    if let Err(e) = authenticator.authorize(&claims, access) {
+        return Ok(reject(req, e));
+    }
+
+    req.extensions_mut().insert(claims);
+    next.call(req).await.map(ServiceResponse::map_into_left_body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{middleware::from_fn, test, App};
+    use crate::auth::tests::{claims, sign, test_authenticator};
+
+    #[actix_web::test]
+    async fn test_routes_are_gated_by_scope() {
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_authenticator().await))
+                .wrap(from_fn(bearer_auth))
+                .route("/health", web::get().to(HttpResponse::Ok))
+                .route("/icd/search", web::get().to(HttpResponse::Ok))
+                .route("/fhir/AuditEvent", web::get().to(HttpResponse::Ok))
+                .route("/autocomplete/initialize", web::post().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let read = format!("Bearer {}", sign("rsa", claims("terminology.read")));
+        let write = format!("Bearer {}", sign("ec", claims("terminology.write")));
+        let audit = format!("Bearer {}", sign("rsa", claims("terminology.audit")));
+        let cases = [
+            (test::TestRequest::get().uri("/health"), None, 200),
+            (test::TestRequest::get().uri("/icd/search"), None, 401),
+            (test::TestRequest::get().uri("/icd/search"), Some("Bearer not-a-jwt"), 401),
+            (test::TestRequest::get().uri("/icd/search"), Some(read.as_str()), 200),
+            (test::TestRequest::post().uri("/autocomplete/initialize"), Some(read.as_str()), 403),
+            (test::TestRequest::post().uri("/autocomplete/initialize"), Some(write.as_str()), 200),
+            (test::TestRequest::get().uri("/fhir/AuditEvent"), Some(read.as_str()), 403),
+            (test::TestRequest::get().uri("/fhir/AuditEvent"), Some(write.as_str()), 403),
+            (test::TestRequest::get().uri("/fhir/AuditEvent"), Some(audit.as_str()), 200),
+        ];
+        for (request, authorization, status) in cases {
+            let request = match authorization {
+                Some(value) => request.insert_header((header::AUTHORIZATION, value)),
+                None => request,
+            };
+            let response = test::call_service(&app, request.to_request()).await;
+            assert_eq!(response.status().as_u16(), status);
+        }
+    }
+}
@@ -0,0 +1,280 @@
+// OAuth 2.0 bearer token validation for ABHA / ABDM issued JWTs
+pub mod jwks;
+pub mod middleware;
+
+use std::collections::HashSet;
+use actix_web::http::Method;
+use jsonwebtoken::{decode, decode_header, Algorithm, Validation};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{self, OAuthConfig};
+use jwks::{JwksSource, JwksStore};
+
+pub use middleware::bearer_auth;
+
+pub const DEFAULT_READ_SCOPE: &str = "terminology.read";
+pub const DEFAULT_WRITE_SCOPE: &str = "terminology.write";
+pub const DEFAULT_AUDIT_SCOPE: &str = "terminology.audit";
+
+// What a route needs from the caller
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Public,
+    Read,
+    // Indexing, embedding generation, ingestion and mapping changes
+    Write,
+    // The audit trail says who looked up what, so neither read nor write grants it
+    Audit,
+}
+
+pub fn required_access(method: &Method, path: &str) -> Access {
+    let path = path.trim_end_matches('/');
+    if method == Method::OPTIONS || matches!(path, "/health" | "/fhir/metadata") {
+        return Access::Public;
+    }
+    if path == "/fhir/AuditEvent" {
+        return Access::Audit;
+    }
+    if matches!(path, "/autocomplete/initialize" | "/services/generate-embeddings") {
+        return Access::Write;
+    }
+    if method == Method::GET || method == Method::HEAD {
+        Access::Read
+    } else {
+        Access::Write
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthConfig {
+    pub jwks: JwksSource,
+    pub issuer: String,
+    pub audience: String,
+    pub read_scope: String,
+    pub write_scope: String,
+    pub audit_scope: String,
+}
+
+impl AuthConfig {
+    // None when no JWKS is configured, which leaves the server open (local development).
+    // The configuration was validated at startup, so issuer and audience are set with a JWKS
+    pub fn from_config(oauth: &OAuthConfig) -> Result<Option<Self>, String> {
+        let jwks = match (&oauth.jwks_file, &oauth.jwks_url) {
+            (Some(path), _) => JwksSource::File(path.into()),
+            (_, Some(url)) => JwksSource::Url(url.clone()),
+            _ => return Ok(None),
+        };
+        let required = |value: &Option<String>, name: &str| value.clone().ok_or_else(|| format!("oauth.{} must be set when a JWKS is configured", name));
+        Ok(Some(AuthConfig {
+            jwks,
+            issuer: required(&oauth.issuer, "issuer")?,
+            audience: required(&oauth.audience, "audience")?,
+            read_scope: oauth.read_scope.clone(),
+            write_scope: oauth.write_scope.clone(),
+            audit_scope: oauth.audit_scope.clone(),
+        }))
+    }
+}
+
+// "scp" is sent either as a list or as a space separated string
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ScopeList {
+    List(Vec<String>),
+    Text(String),
+}
+
+// Claims we use; exp, iss and aud are checked while decoding
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    pub sub: Option<String>,
+    pub scope: Option<String>,
+    pub scp: Option<ScopeList>,
+    pub exp: u64,
+}
+
+impl Claims {
+    pub fn scopes(&self) -> HashSet<&str> {
+        let mut scopes: HashSet<&str> = self.scope.as_deref().unwrap_or_default().split_whitespace().collect();
+        match &self.scp {
+            Some(ScopeList::List(list)) => scopes.extend(list.iter().map(String::as_str)),
+            Some(ScopeList::Text(text)) => scopes.extend(text.split_whitespace()),
+            None => {},
+        }
+        scopes
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthError {
+    MissingToken,
+    InvalidToken(String),
+    InsufficientScope(String),
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::MissingToken => write!(f, "Bearer token required"),
+            AuthError::InvalidToken(reason) => write!(f, "Invalid token: {}", reason),
+            AuthError::InsufficientScope(scope) => write!(f, "Scope {} required", scope),
+        }
+    }
+}
+
+pub struct Authenticator {
+    config: Option<AuthConfig>,
+    keys: Option<JwksStore>,
+}
+
+impl Authenticator {
+    pub fn disabled() -> Self {
+        Authenticator { config: None, keys: None }
+    }
+
+    pub async fn new(config: AuthConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let keys = JwksStore::load(config.jwks.clone()).await?;
+        Ok(Authenticator { config: Some(config), keys: Some(keys) })
+    }
+
+    pub async fn from_config() -> Result<Self, Box<dyn std::error::Error>> {
+        match AuthConfig::from_config(&config::get().oauth)? {
+            Some(config) => Self::new(config).await,
+            None => Ok(Self::disabled()),
+        }
+    }
+
+    pub fn config(&self) -> Option<&AuthConfig> {
+        self.config.as_ref()
+    }
+
+    pub fn key_count(&self) -> usize {
+        self.keys.as_ref().map(JwksStore::len).unwrap_or(0)
+    }
+
+    // Signature (RS256 / ES256 only), exp, iss and aud
+    pub async fn verify(&self, token: &str) -> Result<Claims, AuthError> {
+        let (Some(config), Some(keys)) = (&self.config, &self.keys) else {
+            return Err(AuthError::InvalidToken("authentication is not configured".to_string()));
+        };
+        let header = decode_header(token).map_err(|e| AuthError::InvalidToken(e.to_string()))?;
+        if header.alg != Algorithm::RS256 && header.alg != Algorithm::ES256 {
+            return Err(AuthError::InvalidToken(format!("algorithm {:?} is not accepted", header.alg)));
+        }
+
+        let (key, key_algorithm) = keys.decoding_key(header.kid.as_deref()).await.map_err(AuthError::InvalidToken)?;
+        if key_algorithm != header.alg {
+            return Err(AuthError::InvalidToken(format!("key does not sign with {:?}", header.alg)));
+        }
+
+        let mut validation = Validation::new(key_algorithm);
+        validation.set_issuer(&[&config.issuer]);
+        validation.set_audience(&[&config.audience]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+
+        decode::<Claims>(token, &key, &validation)
+            .map(|data| data.claims)
+            .map_err(|e| AuthError::InvalidToken(e.to_string()))
+    }
+
+    // The write scope also grants read access
+    pub fn authorize(&self, claims: &Claims, access: Access) -> Result<(), AuthError> {
+        let Some(config) = &self.config else {
+            return Ok(());
+        };
+        let scopes = claims.scopes();
+        let allowed = match access {
+            Access::Public => true,
+            Access::Read => scopes.contains(config.read_scope.as_str()) || scopes.contains(config.write_scope.as_str()),
+            Access::Write => scopes.contains(config.write_scope.as_str()),
+            Access::Audit => scopes.contains(config.audit_scope.as_str()),
+        };
+        if allowed {
+            return Ok(());
+        }
+        let needed = match access {
+            Access::Write => &config.write_scope,
+            Access::Audit => &config.audit_scope,
+            _ => &config.read_scope,
+        };
+        Err(AuthError::InsufficientScope(needed.clone()))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::json;
+
+    pub const ISSUER: &str = "https://abdm.example/realms/test";
+    pub const AUDIENCE: &str = "codevedas-terminology";
+
+    fn fixture(name: &str) -> std::path::PathBuf {
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/auth").join(name)
+    }
+
+    pub async fn test_authenticator() -> Authenticator {
+        Authenticator::new(AuthConfig {
+            jwks: JwksSource::File(fixture("jwks.json")),
+            issuer: ISSUER.to_string(),
+            audience: AUDIENCE.to_string(),
+            read_scope: DEFAULT_READ_SCOPE.to_string(),
+            write_scope: DEFAULT_WRITE_SCOPE.to_string(),
+            audit_scope: DEFAULT_AUDIT_SCOPE.to_string(),
+        })
+        .await
+        .unwrap()
+    }
+
+    // Sign a token with one of the fixture keys ("rsa" -> RS256, "ec" -> ES256)
+    pub fn sign(key: &str, claims: serde_json::Value) -> String {
+        let pem = std::fs::read(fixture(&format!("{}_private.pem", key))).unwrap();
+        let (algorithm, encoding_key) = match key {
+            "rsa" => (Algorithm::RS256, EncodingKey::from_rsa_pem(&pem).unwrap()),
+            _ => (Algorithm::ES256, EncodingKey::from_ec_pem(&pem).unwrap()),
+        };
+        let mut header = Header::new(algorithm);
+        header.kid = Some(format!("test-{}", key));
+        encode(&header, &claims, &encoding_key).unwrap()
+    }
+
+    pub fn claims(scope: &str) -> serde_json::Value {
+        json!({
+            "sub": "practitioner-1",
+            "iss": ISSUER,
+            "aud": AUDIENCE,
+            "exp": chrono::Utc::now().timestamp() + 300,
+            "scope": scope
+        })
+    }
+
+    #[tokio::test]
+    async fn test_verifies_tokens_against_jwks_fixture() {
+        let auth = test_authenticator().await;
+
+        let verified = auth.verify(&sign("rsa", claims("terminology.read"))).await.unwrap();
+        assert!(verified.scopes().contains("terminology.read"));
+        assert!(auth.verify(&sign("ec", claims("terminology.write"))).await.is_ok());
+
+        let mut wrong_audience = claims("terminology.read");
+        wrong_audience["aud"] = json!("someone-else");
+        assert!(auth.verify(&sign("rsa", wrong_audience)).await.is_err());
+
+        let mut expired = claims("terminology.read");
+        expired["exp"] = json!(chrono::Utc::now().timestamp() - 3600);
+        assert!(auth.verify(&sign("ec", expired)).await.is_err());
+
+        let hs256 = encode(&Header::default(), &claims("terminology.write"), &EncodingKey::from_secret(b"secret")).unwrap();
+        assert!(auth.verify(&hs256).await.is_err());
+
+        let read_only = auth.verify(&sign("rsa", claims("terminology.read"))).await.unwrap();
+        assert_eq!(
+            auth.authorize(&read_only, Access::Write),
+            Err(AuthError::InsufficientScope(DEFAULT_WRITE_SCOPE.to_string()))
+        );
+        assert_eq!(required_access(&Method::GET, "/services/generate-embeddings"), Access::Write);
+        assert_eq!(required_access(&Method::GET, "/icd/search"), Access::Read);
+        assert_eq!(required_access(&Method::GET, "/fhir/AuditEvent/"), Access::Audit);
+    }
+}
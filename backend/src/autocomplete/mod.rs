@@ -0,0 +1,267 @@
+// Autocomplete indexing of the whole corpus, run as a background job. A full build writes
+// into a fresh Redis namespace and swaps it in once complete, so searches never see a
+// half-built index; an incremental run compares fingerprints and only rewrites concepts
+// that changed
+pub mod ranking;
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::codecs::icd::IcdCodec;
+use crate::codecs::namaste::NamasteCodec;
+use crate::dbcodes::redis::{BulkSuggestion, RedisClient, PIPELINE_BATCH};
+use crate::jobs::{Cancelled, JobContext};
+
+pub const CATEGORIES: [&str; 2] = ["icd", "namaste"];
+
+// Per-category progress, reported as the job's detail
+#[derive(Clone, Debug, Serialize)]
+pub struct Progress {
+    pub category: String,
+    // "full" or "incremental"
+    pub mode: &'static str,
+    pub namespace: Option<String>,
+    // Concepts to write or remove, and how many of them are done
+    pub total: usize,
+    pub processed: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+    pub done: bool,
+}
+
+// A run over several categories. The checkpoint lists the finished categories and the
+// namespace a full build was writing, which a resumed run discards and builds again
+struct Run<'a> {
+    context: &'a JobContext,
+    progress: Vec<Progress>,
+    building: Option<String>,
+}
+
+impl Run<'_> {
+    fn current(&mut self) -> &mut Progress {
+        self.progress.last_mut().unwrap()
+    }
+
+    async fn report(&self) {
+        let total = self.progress.iter().map(|p| p.total).sum();
+        let processed = self.progress.iter().map(|p| p.processed).sum();
+        let done: Vec<&str> = self.progress.iter().filter(|p| p.done).map(|p| p.category.as_str()).collect();
+        let checkpoint = json!({ "done": done, "building": self.building });
+        self.context.report(total, processed, json!({ "categories": self.progress }), Some(checkpoint)).await;
+    }
+}
+
+// Params: "full" (rebuild instead of updating changed concepts) and "categories" (default both)
+pub async fn run_job(context: &JobContext) -> anyhow::Result<Value> {
+    let full = context.params.get("full").and_then(Value::as_bool).unwrap_or(false);
+    let categories: Vec<String> = match context.params.get("categories").and_then(Value::as_array) {
+        Some(list) => list.iter().filter_map(Value::as_str).map(str::to_string).collect(),
+        None => CATEGORIES.iter().map(|c| c.to_string()).collect(),
+    };
+    let checkpoint = context.checkpoint.clone().unwrap_or_default();
+    let done: Vec<&str> = checkpoint["done"].as_array().map(|d| d.iter().filter_map(Value::as_str).collect()).unwrap_or_default();
+
+    let manager = RedisClient::get_instance().await?;
+    let client = RedisClient { manager: manager.clone() };
+
+    // A full build the previous attempt left behind was never swapped in
+    if let Some(orphan) = checkpoint["building"].as_str() {
+        let dropped = client.drop_namespace(orphan).await?;
+        println!("🧹 Dropped {} keys of unfinished autocomplete index {}", dropped, orphan);
+    }
+
+    let mut run = Run { context, progress: Vec::new(), building: None };
+    for category in categories.iter().filter(|c| !done.contains(&c.as_str())) {
+        context.ensure_active()?;
+        run.progress.push(Progress {
+            category: category.clone(),
+            mode: if full { "full" } else { "incremental" },
+            namespace: None,
+            total: 0,
+            processed: 0,
+            unchanged: 0,
+            removed: 0,
+            done: false,
+        });
+        reindex(&client, &mut run, category, full).await?;
+        run.current().done = true;
+        run.report().await;
+        println!("✅ Autocomplete index {} is up to date", category);
+    }
+    Ok(json!({ "categories": run.progress, "skipped": done }))
+}
+
+// Every concept of a category as an autocomplete entry
+async fn load_suggestions(category: &str) -> Result<Vec<BulkSuggestion>, Box<dyn std::error::Error>> {
+    let suggestions = match category {
+        "icd" => IcdCodec::new()
+            .get_all_codes(None)
+            .await?
+            .into_iter()
+            .map(|code| BulkSuggestion {
+                id: code.id.clone(),
+                text: format!("{} {}", code.code, code.title),
+                score: 1.0,
+                payload: Some(json!({
+                    "id": code.id,
+                    "code": code.code,
+                    "title": code.title,
+                    "definition": code.definition,
+                    "source": "ICD-11",
+                    "system": "Biomedicine"
+                }).to_string()),
+            })
+            .collect(),
+        "namaste" => NamasteCodec::new()
+            .get_all_codes(None)
+            .await?
+            .into_iter()
+            .map(|code| BulkSuggestion {
+                id: code.namc_id.to_string(),
+                text: format!("{} {}", code.namc_id, code.namc_term),
+                score: 1.0,
+                payload: Some(json!({
+                    "id": code.namc_id,
+                    "code": code.namc_id,
+                    "title": code.namc_term,
+                    "definition": code.namc_term,
+                    "source": "NAMASTE",
+                    "system": "Ayurveda"
+                }).to_string()),
+            })
+            .collect(),
+        other => return Err(format!("unknown autocomplete category {}", other).into()),
+    };
+    Ok(suggestions)
+}
+
+// Concepts whose fingerprint differs from the indexed one (new ones included), and the
+// indexed ids that no longer exist
+pub fn plan<'a>(
+    suggestions: &'a [BulkSuggestion],
+    fingerprints: &HashMap<String, String>,
+) -> (Vec<&'a BulkSuggestion>, Vec<String>) {
+    let changed = suggestions
+        .iter()
+        .filter(|s| fingerprints.get(&s.id) != Some(&s.fingerprint()))
+        .collect();
+    let current: HashSet<&str> = suggestions.iter().map(|s| s.id.as_str()).collect();
+    let mut removed: Vec<String> = fingerprints.keys().filter(|id| !current.contains(id.as_str())).cloned().collect();
+    removed.sort();
+    (changed, removed)
+}
+
+async fn reindex(client: &RedisClient, run: &mut Run<'_>, category: &str, full: bool) -> anyhow::Result<()> {
+    let suggestions = load_suggestions(category).await.map_err(|e| anyhow::anyhow!(e.to_string()))?;
+
+    // Incremental runs need a swapped-in namespace to compare against
+    match client.active_namespace(category).await? {
+        Some(namespace) if !full => update_in_place(client, run, &namespace, &suggestions).await,
+        _ => {
+            run.current().mode = "full";
+            rebuild(client, run, category, &suggestions).await
+        },
+    }
+}
+
+async fn rebuild(client: &RedisClient, run: &mut Run<'_>, category: &str, suggestions: &[BulkSuggestion]) -> anyhow::Result<()> {
+    let namespace = format!("autocomplete:{}@{}", category, Utc::now().timestamp_millis());
+    println!("🔄 Building autocomplete index {} ({} concepts)", namespace, suggestions.len());
+    run.building = Some(namespace.clone());
+    run.current().namespace = Some(namespace.clone());
+    run.current().total = suggestions.len();
+    run.report().await;
+
+    let all: Vec<&BulkSuggestion> = suggestions.iter().collect();
+    for chunk in all.chunks(PIPELINE_BATCH) {
+        // Nothing reads the half-built namespace, so a cancelled build is discarded
+        if run.context.is_cancelled() {
+            client.drop_namespace(&namespace).await?;
+            run.building = None;
+            run.report().await;
+            return Err(Cancelled.into());
+        }
+        client.index_batch(&namespace, chunk, &[]).await?;
+        run.current().processed += chunk.len();
+        run.report().await;
+    }
+
+    let previous = client.swap_namespace(category, &namespace).await?;
+    run.building = None;
+    let dropped = client.drop_namespace(&previous).await?;
+    println!("🔁 Autocomplete {} now reads {} (dropped {} keys of {})", category, namespace, dropped, previous);
+    Ok(())
+}
+
+// Each batch is atomic, so stopping between batches leaves a consistent index that the
+// next run finishes from the fingerprints
+async fn update_in_place(
+    client: &RedisClient,
+    run: &mut Run<'_>,
+    namespace: &str,
+    suggestions: &[BulkSuggestion],
+) -> anyhow::Result<()> {
+    let (fingerprints, entries) = client.indexed_concepts(namespace).await?;
+    let (changed, removed) = plan(suggestions, &fingerprints);
+    println!(
+        "🔄 Updating autocomplete index {}: {} changed, {} removed, {} unchanged",
+        namespace, changed.len(), removed.len(), suggestions.len() - changed.len()
+    );
+    let progress = run.current();
+    progress.namespace = Some(namespace.to_string());
+    progress.total = changed.len() + removed.len();
+    progress.unchanged = suggestions.len() - changed.len();
+    progress.removed = removed.len();
+    run.report().await;
+
+    // The entry a concept was indexed under, to take out before writing its new one
+    let stale_of = |id: &String| entries.get(id).map(|entry| (id.clone(), entry.clone()));
+
+    for chunk in changed.chunks(PIPELINE_BATCH) {
+        run.context.ensure_active()?;
+        let stale: Vec<(String, String)> = chunk.iter().filter_map(|s| stale_of(&s.id)).collect();
+        client.index_batch(namespace, chunk, &stale).await?;
+        run.current().processed += chunk.len();
+        run.report().await;
+    }
+    for chunk in removed.chunks(PIPELINE_BATCH) {
+        run.context.ensure_active()?;
+        let stale: Vec<(String, String)> = chunk.iter().filter_map(stale_of).collect();
+        client.index_batch(namespace, &[], &stale).await?;
+        run.current().processed += chunk.len();
+        run.report().await;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn suggestion(id: &str, text: &str) -> BulkSuggestion {
+        BulkSuggestion {
+            id: id.to_string(),
+            text: text.to_string(),
+            score: 1.0,
+            payload: None,
+        }
+    }
+
+    #[test]
+    fn test_plan_only_touches_changed_and_removed_concepts() {
+        let indexed = [suggestion("1", "1A00 Cholera"), suggestion("2", "1A01 Typhoid"), suggestion("3", "1A02 Gone")];
+        let fingerprints: HashMap<String, String> = indexed.iter().map(|s| (s.id.clone(), s.fingerprint())).collect();
+
+        let current = [suggestion("1", "1A00 Cholera"), suggestion("2", "1A01 Typhoid fever"), suggestion("4", "1A03 New")];
+        let (changed, removed) = plan(&current, &fingerprints);
+        let changed: Vec<&str> = changed.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(changed, vec!["2", "4"]);
+        assert_eq!(removed, vec!["3".to_string()]);
+
+        let (changed, removed) = plan(&indexed, &fingerprints);
+        assert!(changed.is_empty() && removed.is_empty());
+    }
+}
@@ -0,0 +1,295 @@
+// Autocomplete relevance as a ranking profile per category, read from a JSON file
+// (AUTOCOMPLETE_RANKING_FILE, default ranking.json) and reloaded whenever it changes, so
+// weights and term lists can be tuned on a running server. Every score can be explained
+// as the sum of its components
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_RANKING_FILE: &str = "ranking.json";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchType {
+    ExactTitle,    // Query exactly matches the title
+    StartsWith,    // Title starts with query
+    WordStart,     // A word in the title starts with query
+    Contains,      // Title contains query
+    Related,       // Related/partial match
+    Fuzzy(usize),  // A word starts within this many typos of the query
+}
+
+impl MatchType {
+    // Classify how an entry ("<code> <title>") matched the query
+    pub fn of(query: &str, entry: &str) -> MatchType {
+        let query_lower = query.to_lowercase();
+        let entry_lower = entry.to_lowercase();
+
+        // Extract title from entry (after the code)
+        let title = match entry_lower.find(' ') {
+            Some(space_idx) => &entry_lower[space_idx + 1..],
+            None => &entry_lower,
+        };
+
+        if title == query_lower {
+            MatchType::ExactTitle
+        } else if title.starts_with(&query_lower) {
+            MatchType::StartsWith
+        } else if entry_lower.split_whitespace().any(|word| word.starts_with(&query_lower)) {
+            MatchType::WordStart
+        } else if entry_lower.contains(&query_lower) {
+            MatchType::Contains
+        } else {
+            MatchType::Related
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            MatchType::ExactTitle => "exact_title",
+            MatchType::StartsWith => "starts_with",
+            MatchType::WordStart => "word_start",
+            MatchType::Contains => "contains",
+            MatchType::Related => "related",
+            MatchType::Fuzzy(_) => "fuzzy",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MatchWeights {
+    pub exact_title: f64,    // "Fever" matches "Fever"
+    pub starts_with: f64,    // "Fev" matches "Fever"
+    pub word_start: f64,     // "Acute" matches "Acute kidney injury"
+    pub contains: f64,       // "fever" matches "rheumatic fever"
+    pub related: f64,
+    pub fuzzy: f64,          // "pnuem" matches "Pneumonia", less this per typo
+    pub per_typo: f64,
+}
+
+impl Default for MatchWeights {
+    fn default() -> Self {
+        MatchWeights {
+            exact_title: 10.0,
+            starts_with: 8.0,
+            word_start: 5.0,
+            contains: 2.0,
+            related: 1.0,
+            fuzzy: 5.0,
+            per_typo: 2.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RankingProfile {
+    pub base: f64,
+    pub matches: MatchWeights,
+    // Times query length / entry length, preferring short, specific entries
+    pub length_ratio: f64,
+    // Added once for each listed term the entry contains
+    pub boost_terms: Vec<String>,
+    pub boost_weight: f64,
+    // Subtracted once for each listed phrase the entry contains
+    pub penalty_terms: Vec<String>,
+    pub penalty_weight: f64,
+    // Added when the entry's code is at most this long (short ICD codes are usually primary)
+    pub short_code_length: Option<usize>,
+    pub short_code_weight: f64,
+    pub min_score: f64,
+}
+
+impl Default for RankingProfile {
+    fn default() -> Self {
+        RankingProfile {
+            base: 1.0,
+            matches: MatchWeights::default(),
+            length_ratio: 2.0,
+            boost_terms: Vec::new(),
+            boost_weight: 1.5,
+            penalty_terms: Vec::new(),
+            penalty_weight: 2.0,
+            short_code_length: None,
+            short_code_weight: 3.0,
+            min_score: 0.1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Contribution {
+    pub component: String,
+    pub value: f64,
+}
+
+impl RankingProfile {
+    // The profile used before scoring was configurable: biomedical English heuristics
+    pub fn icd() -> Self {
+        let terms = |list: &[&str]| list.iter().map(|t| t.to_string()).collect();
+        RankingProfile {
+            boost_terms: terms(&[
+                "fever", "pain", "infection", "acute", "chronic", "syndrome",
+                "disease", "disorder", "injury", "fracture", "diabetes",
+                "hypertension", "pneumonia", "cancer", "tumor", "inflammation",
+            ]),
+            penalty_terms: terms(&[
+                "unspecified", "not elsewhere classified", "other specified",
+                "without mention", "with mention", "sequela",
+            ]),
+            short_code_length: Some(4),
+            ..RankingProfile::default()
+        }
+    }
+
+    // Score and the components it is made of, which add up to it
+    pub fn explain(&self, query: &str, entry: &str, match_type: MatchType) -> (f64, Vec<Contribution>) {
+        let query_lower = query.to_lowercase();
+        let entry_lower = entry.to_lowercase();
+        let mut parts = vec![Contribution { component: "base".to_string(), value: self.base }];
+        let mut add = |component: String, value: f64| parts.push(Contribution { component, value });
+
+        let weights = &self.matches;
+        let match_value = match match_type {
+            MatchType::ExactTitle => weights.exact_title,
+            MatchType::StartsWith => weights.starts_with,
+            MatchType::WordStart => weights.word_start,
+            MatchType::Contains => weights.contains,
+            MatchType::Related => weights.related,
+            MatchType::Fuzzy(typos) => weights.fuzzy - weights.per_typo * typos as f64,
+        };
+        let match_name = match match_type {
+            MatchType::Fuzzy(typos) => format!("match:fuzzy({} typos)", typos),
+            other => format!("match:{}", other.name()),
+        };
+        add(match_name, match_value);
+
+        if !entry_lower.is_empty() && self.length_ratio != 0.0 {
+            add("length_ratio".to_string(), query_lower.len() as f64 / entry_lower.len() as f64 * self.length_ratio);
+        }
+
+        for term in &self.boost_terms {
+            if entry_lower.contains(&term.to_lowercase()) {
+                add(format!("boost:{}", term), self.boost_weight);
+            }
+        }
+        for term in &self.penalty_terms {
+            if entry_lower.contains(&term.to_lowercase()) {
+                add(format!("penalty:{}", term), -self.penalty_weight);
+            }
+        }
+
+        if let (Some(max), Some(code)) = (self.short_code_length, entry.split_whitespace().next())
+            && code.len() <= max {
+            add("short_code".to_string(), self.short_code_weight);
+        }
+
+        let total: f64 = parts.iter().map(|p| p.value).sum();
+        if total < self.min_score {
+            parts.push(Contribution { component: "min_score".to_string(), value: self.min_score - total });
+        }
+        (total.max(self.min_score), parts)
+    }
+
+    pub fn score(&self, query: &str, entry: &str, match_type: MatchType) -> f64 {
+        self.explain(query, entry, match_type).0
+    }
+}
+
+// Profiles by category with the file's modification time, None when read without a file
+struct Loaded {
+    modified: Option<SystemTime>,
+    profiles: HashMap<String, RankingProfile>,
+}
+
+static PROFILES: OnceLock<RwLock<Option<Loaded>>> = OnceLock::new();
+
+fn ranking_file() -> String {
+    crate::config::get().files.ranking.clone()
+}
+
+fn built_in() -> HashMap<String, RankingProfile> {
+    HashMap::from([
+        ("icd".to_string(), RankingProfile::icd()),
+        ("namaste".to_string(), RankingProfile::default()),
+    ])
+}
+
+pub fn parse(json: &str) -> Result<HashMap<String, RankingProfile>, serde_json::Error> {
+    serde_json::from_str(json)
+}
+
+// Re-read the file when its modification time moved. A file that fails to parse keeps the
+// profiles in use; a missing one means the built-in profiles
+fn refresh() {
+    let path = ranking_file();
+    let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+    let lock = PROFILES.get_or_init(|| RwLock::new(None));
+    if lock.read().unwrap().as_ref().is_some_and(|loaded| loaded.modified == modified) {
+        return;
+    }
+
+    let profiles = match modified.map(|_| std::fs::read_to_string(&path)) {
+        None => built_in(),
+        Some(Ok(json)) => match parse(&json) {
+            Ok(profiles) => {
+                println!("📐 Autocomplete ranking profiles loaded from {} ({:?})", path, profiles.keys().collect::<Vec<_>>());
+                profiles
+            },
+            Err(e) => {
+                println!("⚠️  Ranking file {} is invalid, keeping current profiles: {}", path, e);
+                let mut loaded = lock.write().unwrap();
+                let profiles = loaded.take().map(|l| l.profiles).unwrap_or_else(built_in);
+                *loaded = Some(Loaded { modified, profiles });
+                return;
+            },
+        },
+        Some(Err(e)) => {
+            println!("⚠️  Ranking file {} could not be read: {}", path, e);
+            built_in()
+        },
+    };
+    *lock.write().unwrap() = Some(Loaded { modified, profiles });
+}
+
+// The current profile for a category; categories the file does not list use defaults
+pub fn profile(category: &str) -> RankingProfile {
+    refresh();
+    let loaded = PROFILES.get().unwrap().read().unwrap();
+    loaded
+        .as_ref()
+        .and_then(|l| l.profiles.get(category).cloned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contributions_add_up_and_follow_the_profile() {
+        let icd = RankingProfile::icd();
+        let (score, parts) = icd.explain("fev", "1A00 Fever unspecified", MatchType::of("fev", "1A00 Fever unspecified"));
+        let components: Vec<&str> = parts.iter().map(|p| p.component.as_str()).collect();
+        assert_eq!(components, vec!["base", "match:starts_with", "length_ratio", "boost:fever", "penalty:unspecified", "short_code"]);
+        assert!((parts.iter().map(|p| p.value).sum::<f64>() - score).abs() < 1e-9);
+
+        // A NAMASTE profile from the file knows nothing of English terms or ICD code shapes
+        let profiles = parse(r#"{"namaste": {"boost_terms": ["jvara"], "matches": {"starts_with": 6}}}"#).unwrap();
+        let namaste = &profiles["namaste"];
+        let (_, parts) = namaste.explain("jv", "12 jvara fever", MatchType::StartsWith);
+        assert_eq!(parts[1], Contribution { component: "match:starts_with".to_string(), value: 6.0 });
+        assert!(parts.iter().any(|p| p.component == "boost:jvara"));
+        assert!(!parts.iter().any(|p| p.component == "short_code" || p.component == "boost:fever"));
+
+        // The shipped file matches the built-in ICD profile
+        let shipped = parse(include_str!("../../ranking.json")).unwrap();
+        assert_eq!(shipped["icd"].explain("fev", "1A00 Fever", MatchType::StartsWith), icd.explain("fev", "1A00 Fever", MatchType::StartsWith));
+
+        let (score, parts) = icd.explain("x", "X unspecified sequela with mention", MatchType::Fuzzy(2));
+        assert_eq!(score, icd.min_score);
+        assert_eq!(parts.last().unwrap().component, "min_score");
+    }
+}
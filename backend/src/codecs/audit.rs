@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+use mongodb::bson::{doc, DateTime, Document};
+use futures::stream::TryStreamExt;
+
+use crate::dbcodes::mongo;
+
+const COLLECTION: &str = "audit_events";
+
+// One handled request, as stored; served as a FHIR AuditEvent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub event_id: String,
+    pub recorded: DateTime,
+    pub actor: Option<String>,
+    pub client_address: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub action: String,      // FHIR AuditEventAction: C, R, U, D, E
+    pub interaction: String, // restful-interaction: read, search-type, create, operation, ...
+    pub query: Vec<(String, String)>,
+    pub codes: Vec<String>,
+    // None when the response was too large to read for codes
+    pub codes_total: Option<i64>,
+    pub status: i32,
+    pub outcome: String,     // FHIR AuditEventOutcome: 0, 4, 8
+    pub latency_ms: i64,
+}
+
+pub struct AuditFilter {
+    pub actor: Option<String>,
+    pub action: Option<String>,
+    // Mongo comparisons on "recorded", e.g. { "$gte": .., "$lt": .. }
+    pub recorded: Option<Document>,
+}
+
+pub struct AuditCodec;
+
+impl AuditCodec {
+    pub fn new() -> Self {
+        Self
+    }
+
+    async fn collection() -> Result<mongodb::Collection<AuditRecord>, Box<dyn std::error::Error>> {
+        let client = mongo::MongoClient::get_instance().await?;
+        Ok(client.database().collection::<AuditRecord>(COLLECTION))
+    }
+
+    pub async fn insert(&self, record: &AuditRecord) -> Result<(), Box<dyn std::error::Error>> {
+        Self::collection().await?.insert_one(record, None).await?;
+        Ok(())
+    }
+
+    pub async fn count(&self) -> Result<u64, Box<dyn std::error::Error>> {
+        Ok(Self::collection().await?.count_documents(doc! {}, None).await?)
+    }
+
+    // Newest first, plus the total number of matches
+    pub async fn search(
+        &self,
+        filter: AuditFilter,
+        offset: usize,
+        count: usize,
+    ) -> Result<(Vec<AuditRecord>, u64), Box<dyn std::error::Error>> {
+        let mut query = doc! {};
+        if let Some(actor) = filter.actor {
+            query.insert("actor", actor);
+        }
+        if let Some(action) = filter.action {
+            query.insert("action", action);
+        }
+        if let Some(recorded) = filter.recorded {
+            query.insert("recorded", recorded);
+        }
+
+        let collection = Self::collection().await?;
+        let total = collection.count_documents(query.clone(), None).await?;
+        let find_options = mongodb::options::FindOptions::builder()
+            .sort(doc! { "recorded": -1 })
+            .skip(offset as u64)
+            .limit(count as i64)
+            .build();
+        let mut cursor = collection.find(query, find_options).await?;
+        let mut results = Vec::new();
+        while let Some(record) = cursor.try_next().await? {
+            results.push(record);
+        }
+        Ok((results, total))
+    }
+}
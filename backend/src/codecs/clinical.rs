@@ -0,0 +1,231 @@
+use futures::future::BoxFuture;
+use mongodb::bson::{self, doc, Document};
+use mongodb::ClientSession;
+use serde_json::{json, Value};
+
+use crate::dbcodes::mongo;
+
+type ClinicalResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+// Clinical resources (Conditions, problem Lists) accepted through POST /fhir
+const COLLECTION: &str = "fhir_resources";
+
+pub struct ClinicalCodec;
+
+// Version a resource as the one after the stored version, if any
+fn stamp(resource: &mut Value, stored: Option<&Document>) {
+    let previous = stored
+        .and_then(|document| document.get_document("meta").ok())
+        .and_then(|meta| meta.get_str("versionId").ok())
+        .and_then(|version| version.parse::<u64>().ok())
+        .unwrap_or(0);
+    resource["meta"]["versionId"] = json!((previous + 1).to_string());
+    resource["meta"]["lastUpdated"] = json!(chrono::Utc::now().to_rfc3339());
+}
+
+fn key_of(resource: &Value) -> Document {
+    doc! {
+        "resourceType": resource["resourceType"].as_str().unwrap_or_default(),
+        "id": resource["id"].as_str().unwrap_or_default(),
+    }
+}
+
+// The writes storing without a transaction needs, so that undoing them can be tested
+trait ResourceWrites: Sync {
+    fn find<'a>(&'a self, key: &'a Document) -> BoxFuture<'a, ClinicalResult<Option<Document>>>;
+    fn replace<'a>(&'a self, key: &'a Document, document: Document) -> BoxFuture<'a, ClinicalResult<()>>;
+    fn delete<'a>(&'a self, key: &'a Document) -> BoxFuture<'a, ClinicalResult<()>>;
+}
+
+impl ResourceWrites for mongodb::Collection<Document> {
+    fn find<'a>(&'a self, key: &'a Document) -> BoxFuture<'a, ClinicalResult<Option<Document>>> {
+        Box::pin(async move { Ok(self.find_one(key.clone(), None).await?) })
+    }
+
+    fn replace<'a>(&'a self, key: &'a Document, document: Document) -> BoxFuture<'a, ClinicalResult<()>> {
+        Box::pin(async move {
+            let options = mongodb::options::ReplaceOptions::builder().upsert(true).build();
+            self.replace_one(key.clone(), document, options).await?;
+            Ok(())
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a Document) -> BoxFuture<'a, ClinicalResult<()>> {
+        Box::pin(async move {
+            self.delete_one(key.clone(), None).await?;
+            Ok(())
+        })
+    }
+}
+
+// One write at a time, remembering what each replaced. When one fails, the earlier ones are
+// put back (or removed if they were new) before the error is returned
+async fn replace_compensating(writes: &dyn ResourceWrites, resources: &mut [Value]) -> ClinicalResult<()> {
+    let mut written: Vec<(Document, Option<Document>)> = Vec::with_capacity(resources.len());
+    let mut failure = None;
+    for resource in resources.iter_mut() {
+        let key = key_of(resource);
+        let result = async {
+            let stored = writes.find(&key).await?;
+            stamp(resource, stored.as_ref());
+            writes.replace(&key, bson::to_document(resource)?).await?;
+            Ok::<_, Box<dyn std::error::Error>>(stored)
+        }
+        .await;
+        match result {
+            Ok(stored) => written.push((key, stored)),
+            Err(e) => {
+                failure = Some(e);
+                break;
+            },
+        }
+    }
+    let Some(failure) = failure else {
+        return Ok(());
+    };
+
+    for (key, previous) in written.into_iter().rev() {
+        let undone = match previous {
+            Some(previous) => writes.replace(&key, previous).await,
+            None => writes.delete(&key).await,
+        };
+        if let Err(e) = undone {
+            println!("⚠️  Could not undo the write of {:?}: {}", key, e);
+        }
+    }
+    Err(failure)
+}
+
+impl ClinicalCodec {
+    pub fn new() -> Self {
+        Self
+    }
+
+    async fn collection() -> ClinicalResult<mongodb::Collection<Document>> {
+        let client = mongo::MongoClient::get_instance().await?;
+        Ok(client.database().collection::<Document>(COLLECTION))
+    }
+
+    async fn replace_all(
+        collection: &mongodb::Collection<Document>,
+        resources: &mut [Value],
+        session: &mut ClientSession,
+    ) -> ClinicalResult<()> {
+        let options = mongodb::options::ReplaceOptions::builder().upsert(true).build();
+        for resource in resources.iter_mut() {
+            let filter = key_of(resource);
+            let stored = collection.find_one_with_session(filter.clone(), None, session).await?;
+            stamp(resource, stored.as_ref());
+            let document = bson::to_document(resource)?;
+            collection.replace_one_with_session(filter, document, options.clone(), session).await?;
+        }
+        Ok(())
+    }
+
+    // Insert or replace resources keyed by resourceType and id, all or none of them. Each
+    // gets the version after its stored one; the stored resources are returned. A replica set
+    // commits them in one transaction; on a standalone server earlier writes are undone when
+    // a later one fails
+    pub async fn store(&self, resources: &[Value]) -> ClinicalResult<Vec<Value>> {
+        let client = mongo::MongoClient::get_instance().await?;
+        let collection = Self::collection().await?;
+        let mut resources = resources.to_vec();
+
+        if !client.supports_transactions().await? {
+            replace_compensating(&collection, &mut resources).await?;
+            return Ok(resources);
+        }
+
+        let mut session = client.start_session().await?;
+        session.start_transaction(None).await?;
+        if let Err(e) = Self::replace_all(&collection, &mut resources, &mut session).await {
+            session.abort_transaction().await?;
+            return Err(e);
+        }
+        session.commit_transaction().await?;
+        Ok(resources)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // Documents by id; the write numbered `fail_at` (from 1) fails
+    struct FlakyWrites {
+        documents: Mutex<Vec<(Document, Document)>>,
+        writes: Mutex<usize>,
+        fail_at: usize,
+    }
+
+    impl FlakyWrites {
+        fn position(&self, key: &Document) -> Option<usize> {
+            self.documents.lock().unwrap().iter().position(|(stored, _)| stored == key)
+        }
+
+        fn get(&self, id: &str) -> Option<Document> {
+            let key = doc! { "resourceType": "Condition", "id": id };
+            self.position(&key).map(|i| self.documents.lock().unwrap()[i].1.clone())
+        }
+    }
+
+    impl ResourceWrites for FlakyWrites {
+        fn find<'a>(&'a self, key: &'a Document) -> BoxFuture<'a, ClinicalResult<Option<Document>>> {
+            let found = self.position(key).map(|i| self.documents.lock().unwrap()[i].1.clone());
+            Box::pin(async move { Ok(found) })
+        }
+
+        fn replace<'a>(&'a self, key: &'a Document, document: Document) -> BoxFuture<'a, ClinicalResult<()>> {
+            let mut writes = self.writes.lock().unwrap();
+            *writes += 1;
+            let fail = *writes == self.fail_at;
+            if !fail {
+                match self.position(key) {
+                    Some(i) => self.documents.lock().unwrap()[i].1 = document,
+                    None => self.documents.lock().unwrap().push((key.clone(), document)),
+                }
+            }
+            Box::pin(async move { if fail { Err("write failed".into()) } else { Ok(()) } })
+        }
+
+        fn delete<'a>(&'a self, key: &'a Document) -> BoxFuture<'a, ClinicalResult<()>> {
+            self.documents.lock().unwrap().retain(|(stored, _)| stored != key);
+            Box::pin(async move { Ok(()) })
+        }
+    }
+
+    #[test]
+    fn test_versions_follow_the_stored_version() {
+        let mut resource = json!({ "resourceType": "Condition", "id": "c1", "meta": { "versionId": "7" } });
+        stamp(&mut resource, None);
+        assert_eq!(resource["meta"]["versionId"], "1");
+
+        let stored = doc! { "resourceType": "Condition", "id": "c1", "meta": { "versionId": "3" } };
+        stamp(&mut resource, Some(&stored));
+        assert_eq!(resource["meta"]["versionId"], "4");
+        assert!(resource["meta"]["lastUpdated"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_without_transactions_a_failed_write_undoes_the_earlier_ones() {
+        let existing = doc! { "resourceType": "Condition", "id": "c1", "meta": { "versionId": "2" } };
+        let writes = FlakyWrites {
+            documents: Mutex::new(vec![(doc! { "resourceType": "Condition", "id": "c1" }, existing.clone())]),
+            writes: Mutex::new(0),
+            fail_at: 3,
+        };
+        let mut resources: Vec<Value> = ["c1", "c2", "c3"].iter().map(|id| json!({ "resourceType": "Condition", "id": id })).collect();
+
+        assert!(replace_compensating(&writes, &mut resources).await.is_err());
+        assert_eq!(writes.get("c1"), Some(existing));
+        assert_eq!(writes.get("c2"), None);
+        assert_eq!(writes.get("c3"), None);
+
+        // Without failures every resource is stored with its next version
+        let writes = FlakyWrites { fail_at: 0, ..writes };
+        replace_compensating(&writes, &mut resources).await.unwrap();
+        assert_eq!(writes.get("c1").unwrap().get_document("meta").unwrap().get_str("versionId"), Ok("3"));
+        assert_eq!(writes.get("c3").unwrap().get_document("meta").unwrap().get_str("versionId"), Ok("1"));
+    }
+}
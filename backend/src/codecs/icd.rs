@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
-use mongodb::bson::{doc, Bson};
-use futures::stream::TryStreamExt;
-use crate::dbcodes::mongo;
+use mongodb::bson::Bson;
+use crate::fulltext;
+use crate::repository;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IcdCode {
@@ -22,6 +22,36 @@ pub struct IcdCode {
     pub is_leaf: Option<String>,
 }
 
+impl IcdCode {
+    // Code used in FHIR resources: the ICD code when assigned, otherwise the
+    // entity number (chapters and blocks often have no code of their own)
+    pub fn concept_code(&self) -> String {
+        if !self.code.trim().is_empty() {
+            return self.code.trim().to_string();
+        }
+        entity_number(&self.id)
+    }
+
+    // Release segment of the entity URI, e.g. "2025-01"
+    pub fn release(&self) -> Option<String> {
+        let (_, rest) = self.id.split_once("/release/11/")?;
+        rest.split('/').next().map(|s| s.to_string())
+    }
+
+    pub fn is_leaf_flag(&self) -> Option<bool> {
+        match self.is_leaf.as_deref().map(|s| s.trim().to_lowercase()) {
+            Some(v) if v == "true" => Some(true),
+            Some(v) if v == "false" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+// Last path segment of an ICD entity URI
+pub fn entity_number(uri: &str) -> String {
+    uri.trim_end_matches('/').rsplit('/').next().unwrap_or(uri).to_string()
+}
+
 // Custom deserializer to handle mixed code types (integer/string/empty)
 fn deserialize_code<'de, D>(deserializer: D) -> Result<String, D::Error>
 where
@@ -57,63 +87,89 @@ impl IcdCodec {
         Self
     }
 
+    // Whether a code passes the discipline and parent parts of the filter
+    fn matches(filter: &IcdFilter, code: &IcdCode) -> bool {
+        filter.discipline.as_ref().is_none_or(|d| code.id.to_lowercase().contains(d.id_pattern()))
+            && filter.parent_filter.as_ref().is_none_or(|p| code.parent.as_ref() == Some(p))
+    }
+
     pub async fn search_codes(
         &self,
         filter: IcdFilter,
         limit: Option<usize>,
     ) -> Result<Vec<IcdCode>, Box<dyn std::error::Error>> {
-        println!("🔍 Searching ICD codes with filter: {:?}", filter);
-
-        let client = mongo::MongoClient::get_instance().await?;
-        let icd_db = client.get_database_by_name("icd11_database");
-        let collection = icd_db.collection::<IcdCode>("icd11_entities");
+        let ranked = self.search_codes_ranked(filter, limit).await?;
+        Ok(ranked.into_iter().map(|(code, _)| code).collect())
+    }
 
-        let mut query = doc! {};
+    // Search terms are ranked by the BM25 index, with their score; without a term, or
+    // before the index has loaded, the storage answers and there is no score
+    pub async fn search_codes_ranked(
+        &self,
+        filter: IcdFilter,
+        limit: Option<usize>,
+    ) -> Result<Vec<(IcdCode, Option<f32>)>, Box<dyn std::error::Error>> {
+        println!("🔍 Searching ICD codes with filter: {:?}", filter);
 
-        // Discipline filtering based on URL patterns
-        if let Some(discipline) = &filter.discipline {
-            match discipline {
-                IcdDiscipline::Biomedicine => {
-                    query.insert("id", doc! { "$regex": "/mms/", "$options": "i" });
-                },
-                IcdDiscipline::TM2 => {
-                    query.insert("id", doc! { "$regex": "/tm/", "$options": "i" });
-                }
-            }
+        if let Some(search_term) = &filter.search_term
+            && let Some(hits) = fulltext::search_icd(search_term, limit, |code| Self::matches(&filter, code)) {
+            println!("✅ Found {} ICD codes in the full-text index", hits.len());
+            return Ok(hits.into_iter().map(|(code, score)| (code, Some(score))).collect());
         }
 
-        // Text search across multiple fields
-        if let Some(search_term) = &filter.search_term {
-            query.insert("$or", vec![
-                doc! { "title": { "$regex": search_term, "$options": "i" } },
-                doc! { "definition": { "$regex": search_term, "$options": "i" } },
-                doc! { "code": { "$regex": search_term, "$options": "i" } },
-            ]);
-        }
+        let results: Vec<_> = repository::get().icd_search(&filter, limit).await?.into_iter().map(|code| (code, None)).collect();
 
-        // Filter by parent code
-        if let Some(parent) = &filter.parent_filter {
-            query.insert("parent", parent);
-        }
-
-        println!("📊 MongoDB ICD query: {:?}", query);
+        println!("✅ Found {} ICD codes", results.len());
+        Ok(results)
+    }
 
-        let mut find_options = mongodb::options::FindOptions::default();
-        if let Some(limit) = limit {
-            find_options.limit = Some(limit as i64);
-        }
+    // One page of matches in stable order, plus the total number of matches
+    pub async fn search_codes_paged(
+        &self,
+        filter: IcdFilter,
+        offset: usize,
+        count: usize,
+    ) -> Result<(Vec<IcdCode>, u64), Box<dyn std::error::Error>> {
+        Ok(repository::get().icd_page(&filter, offset, count).await?)
+    }
 
-        let mut cursor = collection.find(query, find_options).await?;
+    // The entity with the given id and everything below it, breadth first
+    pub async fn get_descendants(&self, root_id: &str) -> Result<Vec<IcdCode>, Box<dyn std::error::Error>> {
         let mut results = Vec::new();
-
-        while let Some(doc) = cursor.try_next().await? {
-            results.push(doc);
+        let mut seen = std::collections::HashSet::new();
+        let mut level = vec![root_id.to_string()];
+        if let Some(root) = self.find_by_id(root_id).await? {
+            results.push(root);
+        }
+        seen.insert(root_id.to_string());
+
+        while !level.is_empty() {
+            let mut next_level = Vec::new();
+            for child in repository::get().icd_children(&level).await? {
+                if seen.insert(child.id.clone()) {
+                    next_level.push(child.id.clone());
+                    results.push(child);
+                }
+            }
+            level = next_level;
         }
 
-        println!("✅ Found {} ICD codes", results.len());
         Ok(results)
     }
 
+    // Exact lookup by ICD code, or by entity number for entities without a code
+    pub async fn find_by_code(
+        &self,
+        code: &str,
+        discipline: Option<IcdDiscipline>,
+    ) -> Result<Option<IcdCode>, Box<dyn std::error::Error>> {
+        Ok(repository::get().icd_by_code(code, discipline.as_ref()).await?)
+    }
+
+    pub async fn find_by_id(&self, id: &str) -> Result<Option<IcdCode>, Box<dyn std::error::Error>> {
+        Ok(repository::get().icd_by_id(id).await?)
+    }
+
     pub async fn get_biomedicine_codes(&self, limit: Option<usize>) -> Result<Vec<IcdCode>, Box<dyn std::error::Error>> {
         let filter = IcdFilter {
             discipline: Some(IcdDiscipline::Biomedicine),
@@ -160,6 +216,16 @@ impl IcdCodec {
     }
 }
 
+impl IcdDiscipline {
+    // Entity URI fragment that identifies the discipline
+    pub fn id_pattern(&self) -> &'static str {
+        match self {
+            IcdDiscipline::Biomedicine => "/mms/",
+            IcdDiscipline::TM2 => "/tm/",
+        }
+    }
+}
+
 impl std::fmt::Display for IcdDiscipline {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
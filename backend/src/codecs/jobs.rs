@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use mongodb::bson::{self, doc};
+use futures::stream::TryStreamExt;
+
+use crate::dbcodes::mongo;
+
+const COLLECTION: &str = "jobs";
+
+// A background job as stored, so progress survives restarts and interrupted jobs resume
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobRecord {
+    pub job_id: String,
+    pub kind: String,
+    pub params: Value,
+    // queued, running, cancelling, completed, failed, cancelled
+    pub state: String,
+    pub total: i64,
+    pub processed: i64,
+    // Kind-specific counters
+    pub detail: Value,
+    // Where a resumed run picks up, written by the job itself
+    pub checkpoint: Option<Value>,
+    pub result: Option<Value>,
+    pub error: Option<String>,
+    // Times the job was started, including resumptions after a restart
+    pub attempts: i32,
+    pub created_at: String,
+    pub started_at: Option<String>,
+    pub updated_at: String,
+    pub finished_at: Option<String>,
+}
+
+pub struct JobCodec;
+
+impl JobCodec {
+    pub fn new() -> Self {
+        Self
+    }
+
+    async fn collection() -> Result<mongodb::Collection<JobRecord>, Box<dyn std::error::Error>> {
+        let client = mongo::MongoClient::get_instance().await?;
+        Ok(client.database().collection::<JobRecord>(COLLECTION))
+    }
+
+    pub async fn insert(&self, record: &JobRecord) -> Result<(), Box<dyn std::error::Error>> {
+        Self::collection().await?.insert_one(record, None).await?;
+        Ok(())
+    }
+
+    pub async fn get(&self, job_id: &str) -> Result<Option<JobRecord>, Box<dyn std::error::Error>> {
+        Ok(Self::collection().await?.find_one(doc! { "job_id": job_id }, None).await?)
+    }
+
+    // Set fields of a job, stamping updated_at
+    pub async fn update(&self, job_id: &str, fields: bson::Document) -> Result<(), Box<dyn std::error::Error>> {
+        let mut fields = fields;
+        fields.insert("updated_at", chrono::Utc::now().to_rfc3339());
+        Self::collection()
+            .await?
+            .update_one(doc! { "job_id": job_id }, doc! { "$set": fields }, None)
+            .await?;
+        Ok(())
+    }
+
+    // Newest first
+    pub async fn list(&self, kind: Option<&str>, limit: i64) -> Result<Vec<JobRecord>, Box<dyn std::error::Error>> {
+        let query = match kind {
+            Some(kind) => doc! { "kind": kind },
+            None => doc! {},
+        };
+        let options = mongodb::options::FindOptions::builder()
+            .sort(doc! { "created_at": -1 })
+            .limit(limit)
+            .build();
+        let cursor = Self::collection().await?.find(query, options).await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    // Jobs a previous process left queued, running or being cancelled, oldest first
+    pub async fn unfinished(&self) -> Result<Vec<JobRecord>, Box<dyn std::error::Error>> {
+        let options = mongodb::options::FindOptions::builder()
+            .sort(doc! { "created_at": 1 })
+            .build();
+        let cursor = Self::collection()
+            .await?
+            .find(doc! { "state": { "$in": ["queued", "running", "cancelling"] } }, options)
+            .await?;
+        Ok(cursor.try_collect().await?)
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use mongodb::bson::{self, doc, Document};
+use futures::stream::TryStreamExt;
+use tokio::sync::Mutex;
+
+use crate::codecs::icd::IcdCodec;
+use crate::codecs::namaste::NamasteCodec;
+use crate::dbcodes::mongo;
+use crate::fhir::{ICD11_TM2_SYSTEM, NAMASTE_SYSTEM};
+
+// Elements derived from the "TM2 (NAMASTE)" pairs in the NAMASTE sheet
+pub const SHEET_ORIGIN: &str = "namaste-sheet";
+// Elements added by terminologists through /services/mapping
+pub const CURATED_ORIGIN: &str = "curated";
+
+const COLLECTION: &str = "concept_map";
+// Where a rebuild is written before it replaces COLLECTION
+const STAGING_COLLECTION: &str = "concept_map_staging";
+// Records when the sheet elements were last derived, so that a sheet without pairs still counts as seeded
+const STATE_COLLECTION: &str = "concept_map_state";
+
+// Serialises rebuilds and curated writes, so that concurrent first requests seed the map once
+// and no curated element is lost to a swap
+static REBUILDING: Mutex<()> = Mutex::const_new(());
+
+// FHIR R4 ConceptMapEquivalence, read as "the target is ... the source"
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Equivalence {
+    Equivalent,
+    Wider,
+    Narrower,
+}
+
+impl Equivalence {
+    // ICD-11 residual categories ("other specified" ..Y, "unspecified" ..Z) are wider than the NAMASTE concept
+    pub fn for_icd_target(code: &str) -> Self {
+        if code.ends_with('Y') || code.ends_with('Z') {
+            Equivalence::Wider
+        } else {
+            Equivalence::Equivalent
+        }
+    }
+
+    // Equivalence seen from the other side of the map
+    pub fn reversed(&self) -> Self {
+        match self {
+            Equivalence::Equivalent => Equivalence::Equivalent,
+            Equivalence::Wider => Equivalence::Narrower,
+            Equivalence::Narrower => Equivalence::Wider,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Equivalence::Equivalent => "equivalent",
+            Equivalence::Wider => "wider",
+            Equivalence::Narrower => "narrower",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MappingElement {
+    pub source_system: String,
+    pub source_code: String,
+    pub source_display: Option<String>,
+    pub target_system: String,
+    pub target_code: String,
+    pub target_display: Option<String>,
+    pub equivalence: Equivalence,
+    pub origin: String,
+    pub comment: Option<String>,
+}
+
+pub struct MappingCodec;
+
+impl MappingCodec {
+    pub fn new() -> Self {
+        Self
+    }
+
+    async fn collection() -> Result<mongodb::Collection<MappingElement>, Box<dyn std::error::Error>> {
+        let client = mongo::MongoClient::get_instance().await?;
+        Ok(client.database().collection::<MappingElement>(COLLECTION))
+    }
+
+    // Re-derive the NAMASTE -> ICD-11 TM2 elements from the NAMASTE sheet; curated elements are kept
+    pub async fn rebuild_from_namaste(&self) -> Result<usize, Box<dyn std::error::Error>> {
+        let _guard = REBUILDING.lock().await;
+        self.rebuild_sheet_elements().await
+    }
+
+    async fn rebuild_sheet_elements(&self) -> Result<usize, Box<dyn std::error::Error>> {
+        println!("🔄 Rebuilding NAMASTE -> ICD-11 TM2 concept map...");
+
+        let namaste_codes = NamasteCodec::new().get_all_codes(None).await?;
+        let icd_codec = IcdCodec::new();
+        let mut icd_titles: HashMap<String, Option<String>> = HashMap::new();
+        let mut elements = Vec::new();
+
+        for code in namaste_codes {
+            let (nam_code, Some(icd_code)) = code.parse_codes() else {
+                continue;
+            };
+
+            if !icd_titles.contains_key(&icd_code) {
+                let title = icd_codec.find_by_code(&icd_code, None).await?.map(|c| c.title);
+                icd_titles.insert(icd_code.clone(), title);
+            }
+
+            elements.push(MappingElement {
+                source_system: NAMASTE_SYSTEM.to_string(),
+                source_code: nam_code,
+                source_display: Some(code.namc_term.clone()),
+                target_system: ICD11_TM2_SYSTEM.to_string(),
+                target_display: icd_titles.get(&icd_code).cloned().flatten(),
+                equivalence: Equivalence::for_icd_target(&icd_code),
+                target_code: icd_code,
+                origin: SHEET_ORIGIN.to_string(),
+                comment: None,
+            });
+        }
+
+        // A pair a terminologist has curated is not re-derived from the sheet
+        let collection = Self::collection().await?;
+        let curated: HashSet<(String, String)> = collection
+            .find(doc! { "origin": { "$ne": SHEET_ORIGIN } }, None)
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?
+            .into_iter()
+            .map(|element| (element.source_code, element.target_code))
+            .collect();
+        elements.retain(|element| !curated.contains(&(element.source_code.clone(), element.target_code.clone())));
+
+        // Stage the sheet elements, add the curated ones and swap the lot in with a single $out,
+        // so that readers see either the old or the new map
+        let db = mongo::MongoClient::get_instance().await?.database();
+        let staging = db.collection::<MappingElement>(STAGING_COLLECTION);
+        staging.drop(None).await?;
+        if !elements.is_empty() {
+            staging.insert_many(&elements, None).await?;
+        }
+        staging
+            .aggregate(vec![
+                doc! { "$unionWith": { "coll": COLLECTION, "pipeline": [{ "$match": { "origin": { "$ne": SHEET_ORIGIN } } }] } },
+                doc! { "$out": COLLECTION },
+            ], None)
+            .await?;
+        staging.drop(None).await?;
+
+        db.collection::<Document>(STATE_COLLECTION)
+            .replace_one(
+                doc! { "_id": SHEET_ORIGIN },
+                doc! { "_id": SHEET_ORIGIN, "elements": elements.len() as i64, "seeded_at": bson::DateTime::now() },
+                mongodb::options::ReplaceOptions::builder().upsert(true).build(),
+            )
+            .await?;
+
+        println!("✅ Concept map rebuilt with {} NAMASTE -> TM2 elements", elements.len());
+        Ok(elements.len())
+    }
+
+    // Seed the map from the NAMASTE sheet the first time it is needed
+    pub async fn ensure_seeded(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let _guard = REBUILDING.lock().await;
+        let state = mongo::MongoClient::get_instance().await?.database().collection::<Document>(STATE_COLLECTION);
+        if state.find_one(doc! { "_id": SHEET_ORIGIN }, None).await?.is_none() {
+            self.rebuild_sheet_elements().await?;
+        }
+        Ok(())
+    }
+
+    // Add or replace a single curated element
+    pub async fn upsert(&self, element: MappingElement) -> Result<(), Box<dyn std::error::Error>> {
+        let _guard = REBUILDING.lock().await;
+        let collection = Self::collection().await?;
+        let filter = doc! {
+            "source_system": &element.source_system,
+            "source_code": &element.source_code,
+            "target_system": &element.target_system,
+            "target_code": &element.target_code,
+        };
+        let options = mongodb::options::ReplaceOptions::builder().upsert(true).build();
+        collection.replace_one(filter, &element, options).await?;
+        Ok(())
+    }
+
+    pub async fn all_elements(&self) -> Result<Vec<MappingElement>, Box<dyn std::error::Error>> {
+        let collection = Self::collection().await?;
+        let mut cursor = collection.find(doc! {}, None).await?;
+        let mut results = Vec::new();
+        while let Some(element) = cursor.try_next().await? {
+            results.push(element);
+        }
+        Ok(results)
+    }
+
+    // Elements whose source (or target, when reverse) is system|code, optionally restricted to the other side's system
+    pub async fn translate(
+        &self,
+        system: &str,
+        code: &str,
+        other_system: Option<&str>,
+        reverse: bool,
+    ) -> Result<Vec<MappingElement>, Box<dyn std::error::Error>> {
+        let (from, to) = if reverse { ("target", "source") } else { ("source", "target") };
+        let mut filter = doc! {
+            format!("{}_system", from): system,
+            format!("{}_code", from): code,
+        };
+        if let Some(other_system) = other_system {
+            filter.insert(format!("{}_system", to), other_system);
+        }
+
+        let collection = Self::collection().await?;
+        let mut cursor = collection.find(filter, None).await?;
+        let mut results = Vec::new();
+        while let Some(element) = cursor.try_next().await? {
+            results.push(element);
+        }
+        Ok(results)
+    }
+}
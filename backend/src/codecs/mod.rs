@@ -1,2 +1,19 @@
 pub mod namaste;
-pub mod icd;
\ No newline at end of file
+pub mod icd;
+pub mod mapping;
+pub mod clinical;
+pub mod audit;
+pub mod jobs;
+pub mod transliterate;
+
+// Escape a literal string for use inside a MongoDB $regex
+pub fn escape_regex(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if "\\^$.|?*+()[]{}/-".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
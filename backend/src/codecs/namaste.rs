@@ -1,58 +1,94 @@
 use serde::{Deserialize, Serialize};
-use mongodb::bson::{doc};
-use futures::stream::TryStreamExt;
-
-use crate::dbcodes::mongo;
+use crate::fulltext;
+use crate::repository;
 
+// Stored under these field names; collections mongoimport filled with the sheet's own
+// column names are renamed at startup (see LEGACY_FIELDS)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NamasteCode {
-    #[serde(rename = "field_1")]
     pub sr_no: i32,
-    #[serde(rename = "field_1_1")]
     pub namc_id: i32,
-    #[serde(rename = "AYU")]
     pub namc_code: String,
-    #[serde(rename = "vyAdhi-viniScayaH")]
     pub namc_term: String,
-    #[serde(rename = "vyādhi-viniścayaḥ")]
     pub namc_term_diacritical: String,
-    #[serde(rename = "व्याधि-विनिश्चयः")]
     pub namc_term_devanagari: String,
-    #[serde(rename = "Unnamed: 6")]
     pub short_definition: Option<String>,
-    #[serde(rename = "Unnamed: 7")]
     pub long_definition: Option<String>,
-    #[serde(rename = "Unnamed: 8")]
     pub ontology_branches: Option<String>,
 }
 
+// Column names mongoimport took from the pandas export of the sheet, and the fields they became
+pub const LEGACY_FIELDS: [(&str, &str); 9] = [
+    ("field_1", "sr_no"),
+    ("field_1_1", "namc_id"),
+    ("AYU", "namc_code"),
+    ("vyAdhi-viniScayaH", "namc_term"),
+    ("vyādhi-viniścayaḥ", "namc_term_diacritical"),
+    ("व्याधि-विनिश्चयः", "namc_term_devanagari"),
+    ("Unnamed: 6", "short_definition"),
+    ("Unnamed: 7", "long_definition"),
+    ("Unnamed: 8", "ontology_branches"),
+];
+
 // Add a helper method to parse codes
 impl NamasteCode {
     pub fn parse_codes(&self) -> (String, Option<String>) {
+        // The source sheet mixes non-breaking spaces into the code column
+        let raw = self.namc_code.replace('\u{a0}', " ");
+
         // Parse the namc_code field to separate NAMASTE and ICD codes
-        if self.namc_code.contains('(') && self.namc_code.contains(')') {
-            // Format: "AAA-1 (SR-11)" or "SR11 (AAA-1)"
-            let parts: Vec<&str> = self.namc_code.split('(').collect();
-            if parts.len() == 2 {
-                let nam_code = parts[0].trim().to_string();
-                let icd_code = parts[1].replace(')', "").trim().to_string();
-                return (nam_code, Some(icd_code));
-            }
-        } else if self.namc_code.contains(" - ") {
-            // Format: "AAA-1 - SR-11"
-            let parts: Vec<&str> = self.namc_code.split(" - ").collect();
-            if parts.len() == 2 {
-                let nam_code = parts[0].trim().to_string();
-                let icd_code = parts[1].trim().to_string();
-                return (nam_code, Some(icd_code));
-            }
+        let pair = if raw.contains('(') && raw.contains(')') {
+            // Format: "AAA-1 (SR11)" or "SR11 (AAA-1)", with or without the space
+            let parts: Vec<&str> = raw.split('(').collect();
+            (parts.len() == 2).then(|| (parts[0].trim().to_string(), parts[1].replace(')', "").trim().to_string()))
+        } else if raw.contains(" - ") {
+            // Format: "AAA-1 - SR11"
+            let parts: Vec<&str> = raw.split(" - ").collect();
+            (parts.len() == 2).then(|| (parts[0].trim().to_string(), parts[1].trim().to_string()))
+        } else {
+            None
+        };
+
+        match pair {
+            // The ICD-11 code can come first ("SR11 (AAA-1)"), so order the pair by shape
+            Some((first, second)) if is_icd11_code(&first) && !is_icd11_code(&second) => (second, Some(first)),
+            Some((first, second)) => (first, Some(second)),
+            // Default: only NAMASTE code, no ICD mapping
+            None => (raw.trim().to_string(), None),
         }
-        
-        // Default: only NAMASTE code, no ICD mapping
-        (self.namc_code.clone(), None)
     }
 }
 
+// ICD-11 stem codes look like "SR11", "SM1P" or "1A00": two characters, a digit, then a character
+pub fn is_icd11_code(code: &str) -> bool {
+    let chars: Vec<char> = code.chars().collect();
+    chars.len() >= 4
+        && chars[1].is_ascii_alphabetic()
+        && chars[2].is_ascii_digit()
+        && chars.iter().all(|c| c.is_ascii_alphanumeric() || *c == '.')
+}
+
+// Possible ancestors of a NAMASTE code, nearest first: "AAA-2.1" -> "AAA-2", "AAA", "AA", "A"
+pub fn ancestor_candidates(code: &str) -> Vec<String> {
+    let mut candidates = Vec::new();
+    let mut candidate = code.to_string();
+    loop {
+        if let Some(idx) = candidate.rfind(['.', '-']) {
+            candidate.truncate(idx);
+        } else if candidate.chars().count() > 1 {
+            candidate.pop();
+        } else {
+            return candidates;
+        }
+        candidates.push(candidate.clone());
+    }
+}
+
+// Nearest ancestor that is actually a code in the set
+pub fn parent_code(code: &str, known: &std::collections::HashSet<String>) -> Option<String> {
+    ancestor_candidates(code).into_iter().find(|candidate| known.contains(candidate))
+}
+
 
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
@@ -77,53 +113,52 @@ impl NamasteCodec {
     }
 
     pub async fn search_codes(
-    &self,
-    filter: NamasteFilter,
-    limit: Option<usize>,
-) -> Result<Vec<NamasteCode>, Box<dyn std::error::Error>> {
-    println!("🔍 Searching NAMASTE codes with filter: {:?}", filter);
-
-    let client = mongo::MongoClient::get_instance().await?;
-    let ayurveda_db = client.get_database_by_name("ayurveda_db");
-    let collection = ayurveda_db.collection::<NamasteCode>("namc_codes");
-
-    let mut query = doc! {};
-
-    // Use regex search for better partial matching instead of text search
-    if let Some(search_term) = &filter.search_term {
-        query.insert("$or", vec![
-            doc! { "vyAdhi-viniScayaH": { "$regex": search_term, "$options": "i" } },
-            doc! { "vyādhi-viniścayaḥ": { "$regex": search_term, "$options": "i" } },
-            doc! { "व्याधि-विनिश्चयः": { "$regex": search_term, "$options": "i" } },
-            doc! { "AYU": { "$regex": search_term, "$options": "i" } }
-        ]);
+        &self,
+        filter: NamasteFilter,
+        limit: Option<usize>,
+    ) -> Result<Vec<NamasteCode>, Box<dyn std::error::Error>> {
+        let ranked = self.search_codes_ranked(filter, limit).await?;
+        Ok(ranked.into_iter().map(|(code, _)| code).collect())
     }
 
-    if let Some(code) = &filter.code {
-        query.insert("AYU", mongodb::bson::Regex {
-            pattern: code.clone(),
-            options: "i".to_string(),
-        });
-    }
+    // Search terms are ranked by the BM25 index, with their score; without a term, or
+    // before the index has loaded, the storage answers and there is no score
+    pub async fn search_codes_ranked(
+        &self,
+        filter: NamasteFilter,
+        limit: Option<usize>,
+    ) -> Result<Vec<(NamasteCode, Option<f32>)>, Box<dyn std::error::Error>> {
+        println!("🔍 Searching NAMASTE codes with filter: {:?}", filter);
+
+        if let Some(search_term) = &filter.search_term {
+            let code_filter = filter.code.as_ref().map(|code| code.to_lowercase());
+            let keep = |code: &NamasteCode| code_filter.as_ref().is_none_or(|c| code.namc_code.to_lowercase().contains(c));
+            if let Some(hits) = fulltext::search_namaste(search_term, limit, keep) {
+                println!("✅ Found {} NAMASTE codes in the full-text index", hits.len());
+                return Ok(hits.into_iter().map(|(code, score)| (code, Some(score))).collect());
+            }
+        }
 
-    println!("📊 MongoDB NAMASTE query: {:?}", query);
+        let results: Vec<_> = repository::get().namaste_search(&filter, limit).await?.into_iter().map(|code| (code, None)).collect();
 
-    let mut find_options = mongodb::options::FindOptions::default();
-    if let Some(limit) = limit {
-        find_options.limit = Some(limit as i64);
+        println!("✅ Found {} NAMASTE codes", results.len());
+        Ok(results)
     }
 
-    let mut cursor = collection.find(query, find_options).await?;
-    let mut results = Vec::new();
-
-    while let Some(doc) = cursor.try_next().await? {
-        results.push(doc);
+    // One page of matches in sheet order, plus the total number of matches
+    pub async fn search_codes_paged(
+        &self,
+        filter: NamasteFilter,
+        offset: usize,
+        count: usize,
+    ) -> Result<(Vec<NamasteCode>, u64), Box<dyn std::error::Error>> {
+        Ok(repository::get().namaste_page(&filter, offset, count).await?)
     }
 
-    println!("✅ Found {} NAMASTE codes", results.len());
-    Ok(results)
-}
-
+    // Exact lookup by NAMASTE code, matching the parsed code rather than the raw AYU text
+    pub async fn find_by_code(&self, code: &str) -> Result<Option<NamasteCode>, Box<dyn std::error::Error>> {
+        Ok(repository::get().namaste_by_code(code).await?)
+    }
 
     pub async fn get_all_codes(&self, limit: Option<usize>) -> Result<Vec<NamasteCode>, Box<dyn std::error::Error>> {
         let filter = NamasteFilter {
@@ -160,6 +195,7 @@ impl NamasteCodec {
 }
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum NamasteDiscipline {
     Ayurveda,
@@ -0,0 +1,239 @@
+// Sanskrit script handling for NAMASTE terms. Devanagari, Harvard-Kyoto and ITRANS are
+// converted to IAST, then folded to a plain ASCII key (no vowel length, retroflexes or
+// visarga) so "vataprakopa", "vAtaprakopaH" and "वातप्रकोप" compare equal
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+const VIRAMA: char = '\u{094D}';
+const NUKTA: char = '\u{093C}';
+
+// Nukta letters (क़, ज़, ...) decompose under NFC and read as their base consonant
+fn devanagari_consonant(c: char) -> Option<&'static str> {
+    Some(match c {
+        'क' => "k", 'ख' => "kh", 'ग' => "g", 'घ' => "gh", 'ङ' => "ṅ",
+        'च' => "c", 'छ' => "ch", 'ज' => "j", 'झ' => "jh", 'ञ' => "ñ",
+        'ट' => "ṭ", 'ठ' => "ṭh", 'ड' => "ḍ", 'ढ' => "ḍh", 'ण' => "ṇ",
+        'त' => "t", 'थ' => "th", 'द' => "d", 'ध' => "dh", 'न' => "n",
+        'प' => "p", 'फ' => "ph", 'ब' => "b", 'भ' => "bh", 'म' => "m",
+        'य' => "y", 'र' => "r", 'ल' => "l", 'ळ' => "ḷ", 'व' => "v",
+        'श' => "ś", 'ष' => "ṣ", 'स' => "s", 'ह' => "h",
+        _ => return None,
+    })
+}
+
+// Independent vowels and vowel signs share their IAST value
+fn devanagari_vowel(c: char) -> Option<(&'static str, bool)> {
+    Some(match c {
+        'अ' => ("a", false),
+        'आ' => ("ā", false), 'ा' => ("ā", true),
+        'इ' => ("i", false), 'ि' => ("i", true),
+        'ई' => ("ī", false), 'ी' => ("ī", true),
+        'उ' => ("u", false), 'ु' => ("u", true),
+        'ऊ' => ("ū", false), 'ू' => ("ū", true),
+        'ऋ' => ("ṛ", false), 'ृ' => ("ṛ", true),
+        'ॠ' => ("ṝ", false), 'ॄ' => ("ṝ", true),
+        'ऌ' => ("ḷ", false), 'ॢ' => ("ḷ", true),
+        'ए' | 'ऍ' => ("e", false), 'े' | 'ॅ' => ("e", true),
+        'ऐ' => ("ai", false), 'ै' => ("ai", true),
+        'ओ' | 'ऑ' => ("o", false), 'ो' | 'ॉ' => ("o", true),
+        'औ' => ("au", false), 'ौ' => ("au", true),
+        _ => return None,
+    })
+}
+
+fn devanagari_other(c: char) -> Option<&'static str> {
+    Some(match c {
+        'ं' | 'ँ' => "ṃ",
+        'ः' => "ḥ",
+        'ऽ' => "",
+        '।' | '॥' => ".",
+        '०' => "0", '१' => "1", '२' => "2", '३' => "3", '४' => "4",
+        '५' => "5", '६' => "6", '७' => "7", '८' => "8", '९' => "9",
+        _ => return None,
+    })
+}
+
+// Devanagari to IAST with the inherent "a" after consonants; other text passes through
+pub fn devanagari_to_iast(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    // A consonant was written and still owes its inherent vowel
+    let mut pending_a = false;
+    for c in text.chars() {
+        if c == NUKTA {
+            continue;
+        }
+        if let Some(consonant) = devanagari_consonant(c) {
+            if pending_a {
+                out.push('a');
+            }
+            out.push_str(consonant);
+            pending_a = true;
+        } else if c == VIRAMA {
+            pending_a = false;
+        } else if let Some((vowel, is_sign)) = devanagari_vowel(c) {
+            if pending_a && !is_sign {
+                out.push('a');
+            }
+            out.push_str(vowel);
+            pending_a = false;
+        } else {
+            if pending_a {
+                out.push('a');
+            }
+            pending_a = false;
+            match devanagari_other(c) {
+                Some(value) => out.push_str(value),
+                None => out.push(c),
+            }
+        }
+    }
+    if pending_a {
+        out.push('a');
+    }
+    out
+}
+
+// Harvard-Kyoto and ITRANS sequences, longest first. "c"/"ch" differ between the two and
+// fold together anyway, so they are left alone. The NAMASTE sheet writes ṣ as "Sh" (as
+// ITRANS does) and ś as "S", where strict HK has "S" and "z"
+const ROMANISED: [(&str, &str); 38] = [
+    ("RRi", "ṛ"), ("RRI", "ṝ"), ("R^i", "ṛ"), ("R^I", "ṝ"),
+    ("LLi", "ḷ"), ("LLI", "ḹ"), ("L^i", "ḷ"), ("L^I", "ḹ"),
+    ("lRR", "ḹ"), ("lR", "ḷ"), ("RR", "ṝ"),
+    ("~N", "ṅ"), ("N^", "ṅ"), ("~n", "ñ"), ("JN", "ñ"), ("GY", "jñ"),
+    ("Sh", "ṣ"), ("sh", "ś"), (".n", "ṃ"), (".m", "ṃ"), (".h", ""), (".a", ""),
+    ("aa", "ā"), ("ii", "ī"), ("uu", "ū"),
+    ("A", "ā"), ("I", "ī"), ("U", "ū"), ("R", "ṛ"), ("M", "ṃ"), ("H", "ḥ"),
+    ("G", "ṅ"), ("J", "ñ"), ("T", "ṭ"), ("D", "ḍ"), ("N", "ṇ"), ("S", "ś"), ("z", "ś"),
+];
+
+// Case carries meaning in HK and ITRANS ("vAta"), so a word with capitals after its first
+// letter, or with ITRANS marks, is read as one of them. "Jvara" or "AYU" are plain text
+fn is_romanised_sanskrit(word: &str) -> bool {
+    let has_lower = word.chars().any(|c| c.is_ascii_lowercase());
+    let inner_upper = word.chars().skip(1).any(|c| c.is_ascii_uppercase());
+    word.is_ascii() && ((has_lower && inner_upper) || word.contains(['~', '^']) || word.contains(".n") || word.contains(".h"))
+}
+
+pub fn romanised_to_iast(word: &str) -> String {
+    let mut out = String::with_capacity(word.len());
+    let mut rest = word;
+    'outer: while !rest.is_empty() {
+        for (from, to) in ROMANISED {
+            if let Some(tail) = rest.strip_prefix(from) {
+                out.push_str(to);
+                rest = tail;
+                continue 'outer;
+            }
+        }
+        let c = rest.chars().next().unwrap();
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
+
+// Any of the four schemes to NFC IAST. Words are rejoined with single spaces
+pub fn to_iast(text: &str) -> String {
+    let nfc: String = text.nfc().collect();
+    devanagari_to_iast(&nfc)
+        .split_whitespace()
+        .map(|word| if is_romanised_sanskrit(word) { romanised_to_iast(word) } else { word.to_string() })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Spellings clinicians use interchangeably: "chh"/"ch"/"c", "sh"/"s", doubled long vowels,
+// "ee"/"oo", "w" for "v", "x" for "ks", and "m" before a consonant for the anusvara
+fn fold_spellings(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let is_consonant = |c: Option<&char>| c.is_some_and(|c| c.is_ascii_alphabetic() && !"aeiou".contains(*c));
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let next = chars.get(i + 1).copied();
+        let (replacement, consumed) = match (chars[i], next) {
+            ('c', Some('h')) if chars.get(i + 2) == Some(&'h') => ("c", 3),
+            ('c', Some('h')) => ("c", 2),
+            ('s', Some('h')) => ("s", 2),
+            ('a', Some('a')) => ("a", 2),
+            ('i', Some('i')) | ('e', Some('e')) => ("i", 2),
+            ('u', Some('u')) | ('o', Some('o')) => ("u", 2),
+            ('w', _) => ("v", 1),
+            ('x', _) => ("ks", 1),
+            ('m', _) if is_consonant(chars.get(i + 1)) => ("n", 1),
+            (c, _) => {
+                out.push(c);
+                i += 1;
+                continue;
+            },
+        };
+        out.push_str(replacement);
+        i += consumed;
+    }
+    out
+}
+
+// Script-independent search key: IAST with diacritics, vowel length and visarga removed
+pub fn fold(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    for c in to_iast(text).to_lowercase().chars() {
+        match c {
+            'ṛ' | 'ṝ' => plain.push_str("ri"),
+            'ḷ' | 'ḹ' => plain.push_str("li"),
+            'ḥ' => {},
+            'ṃ' | 'ṁ' => plain.push('n'),
+            _ => plain.extend(c.to_string().nfd().filter(|c| !is_combining_mark(*c))),
+        }
+    }
+    fold_spellings(&plain)
+}
+
+// Folded forms of the given texts for indexing or querying, without duplicates, plus each
+// compound written solid so "vata-prakopa" and "vataprakopa" meet
+pub fn search_forms(texts: &[&str]) -> String {
+    let mut forms: Vec<String> = Vec::new();
+    for text in texts {
+        let folded = fold(text);
+        let solid: String = folded.chars().filter(|c| *c != '-' && !c.is_whitespace()).collect();
+        for form in [folded, solid] {
+            if !form.is_empty() && !forms.contains(&form) {
+                forms.push(form);
+            }
+        }
+    }
+    forms.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_devanagari_and_romanised_schemes_to_iast() {
+        assert_eq!(devanagari_to_iast("व्याधि-विनिश्चयः"), "vyādhi-viniścayaḥ");
+        assert_eq!(devanagari_to_iast("वातप्रकोप"), "vātaprakopa");
+        assert_eq!(devanagari_to_iast("संज्ञा"), "saṃjñā");
+        assert_eq!(to_iast("vyAdhi-viniScayaH"), "vyādhi-viniścayaḥ");
+        assert_eq!(to_iast("doShavaiShamyam"), "doṣavaiṣamyam");
+        assert_eq!(to_iast("kRRiShNa"), "kṛṣṇa");
+        assert_eq!(to_iast("sa.nj~naa"), "saṃjñā");
+        // Ordinary capitalisation is not Harvard-Kyoto
+        assert_eq!(to_iast("Jvara AAA-1"), "Jvara AAA-1");
+    }
+
+    #[test]
+    fn test_every_scheme_folds_to_the_same_key() {
+        let key = fold("vataprakopa");
+        for form in ["vAtaprakopaH", "वातप्रकोप", "vātaprakopaḥ", "vaataprakopa", "va\u{304}taprakopa"] {
+            assert_eq!(fold(form), key, "{}", form);
+        }
+        let key = fold("vyādhi-viniścayaḥ");
+        for form in ["vyAdhi-viniScayaH", "व्याधि-विनिश्चयः", "vyadhi-vinishchaya"] {
+            assert_eq!(fold(form), key, "{}", form);
+        }
+        assert_eq!(fold("hṛdroga"), fold("hridroga"));
+        assert_eq!(fold("saṃjñā"), fold("sanjna"));
+        assert_eq!(search_forms(&["vāta-prakopaḥ", "वात-प्रकोपः"]), "vata-prakopa vataprakopa");
+    }
+}
@@ -0,0 +1,438 @@
+// Application configuration: an optional JSON file (CONFIG_FILE, default config.json) with
+// environment variables overriding it, validated once at startup. The same binary serves
+// deployments with different database layouts, origins and endpoints
+use std::env;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::embedding::EmbeddingConfig;
+
+pub const DEFAULT_CONFIG_FILE: &str = "config.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    // Exact origins allowed by CORS, e.g. "http://localhost:5173"
+    pub cors_origins: Vec<String>,
+    // Origins ending in one of these are allowed too, e.g. ".trycloudflare.com"
+    pub cors_origin_suffixes: Vec<String>,
+    // Any localhost or 127.0.0.1 origin, for development
+    pub cors_allow_localhost: bool,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            host: "0.0.0.0".to_string(),
+            port: 8080,
+            cors_origins: vec!["http://localhost:5173".to_string()],
+            cors_origin_suffixes: Vec::new(),
+            cors_allow_localhost: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MongoConfig {
+    pub uri: String,
+    // The server's own collections: jobs, audit events, mappings, clinical records
+    pub database: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub auth_db: String,
+}
+
+impl Default for MongoConfig {
+    fn default() -> Self {
+        MongoConfig {
+            uri: "mongodb://localhost:27017".to_string(),
+            database: "fhir_terminology".to_string(),
+            username: None,
+            password: None,
+            auth_db: "admin".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RedisConfig {
+    pub url: String,
+}
+
+impl Default for RedisConfig {
+    fn default() -> Self {
+        RedisConfig { url: "redis://127.0.0.1/".to_string() }
+    }
+}
+
+// Where a code system's concepts are stored
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CollectionLocation {
+    pub database: String,
+    pub collection: String,
+}
+
+// Where concepts are read from: MongoDB, or the bundled CSVs held in memory (no database
+// needed, embeddings are kept until the process exits)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Storage {
+    #[default]
+    Mongo,
+    Memory,
+}
+
+impl std::str::FromStr for Storage {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "mongo" | "mongodb" => Ok(Storage::Mongo),
+            "memory" => Ok(Storage::Memory),
+            other => Err(format!("TERMINOLOGY_STORAGE must be mongo or memory, got {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TerminologyConfig {
+    pub storage: Storage,
+    pub icd: CollectionLocation,
+    pub namaste: CollectionLocation,
+}
+
+impl Default for TerminologyConfig {
+    fn default() -> Self {
+        TerminologyConfig {
+            storage: Storage::default(),
+            icd: CollectionLocation { database: "icd11_database".to_string(), collection: "icd11_entities".to_string() },
+            namaste: CollectionLocation { database: "ayurveda_db".to_string(), collection: "namc_codes".to_string() },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GeminiConfig {
+    // API base the embedding endpoints are appended to
+    pub url: String,
+}
+
+impl Default for GeminiConfig {
+    fn default() -> Self {
+        GeminiConfig { url: "https://generativelanguage.googleapis.com/v1beta".to_string() }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilesConfig {
+    pub ranking: String,
+    pub embedding_templates: String,
+    // Read by the memory storage
+    pub icd_csv: String,
+    pub namaste_csv: String,
+    // Sheets posted for import wait here until their job ends
+    pub uploads: String,
+}
+
+impl Default for FilesConfig {
+    fn default() -> Self {
+        FilesConfig {
+            ranking: crate::autocomplete::ranking::DEFAULT_RANKING_FILE.to_string(),
+            embedding_templates: crate::embedding::template::DEFAULT_TEMPLATES_FILE.to_string(),
+            icd_csv: crate::repository::memory::DEFAULT_ICD_CSV.to_string(),
+            namaste_csv: crate::importer::DEFAULT_NAMASTE_CSV.to_string(),
+            uploads: crate::importer::DEFAULT_UPLOADS_DIR.to_string(),
+        }
+    }
+}
+
+// Bearer token validation (ABHA / ABDM); without a JWKS the server is open
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OAuthConfig {
+    // Signing keys, from a file or fetched from the issuer; the file wins when both are set
+    pub jwks_file: Option<String>,
+    pub jwks_url: Option<String>,
+    // Required with a JWKS
+    pub issuer: Option<String>,
+    pub audience: Option<String>,
+    pub read_scope: String,
+    pub write_scope: String,
+    // GET /fhir/AuditEvent
+    pub audit_scope: String,
+}
+
+impl Default for OAuthConfig {
+    fn default() -> Self {
+        OAuthConfig {
+            jwks_file: None,
+            jwks_url: None,
+            issuer: None,
+            audience: None,
+            read_scope: crate::auth::DEFAULT_READ_SCOPE.to_string(),
+            write_scope: crate::auth::DEFAULT_WRITE_SCOPE.to_string(),
+            audit_scope: crate::auth::DEFAULT_AUDIT_SCOPE.to_string(),
+        }
+    }
+}
+
+impl OAuthConfig {
+    pub fn has_jwks(&self) -> bool {
+        self.jwks_file.is_some() || self.jwks_url.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppConfig {
+    pub server: ServerConfig,
+    pub mongodb: MongoConfig,
+    pub redis: RedisConfig,
+    pub terminology: TerminologyConfig,
+    pub gemini: GeminiConfig,
+    pub files: FilesConfig,
+    pub oauth: OAuthConfig,
+    // From the EMBEDDING_* variables only, as it holds API keys
+    #[serde(skip)]
+    pub embedding: Option<EmbeddingConfig>,
+}
+
+// "a, b" -> ["a", "b"]
+fn list(value: String) -> Vec<String> {
+    value.split(',').map(str::trim).filter(|item| !item.is_empty()).map(str::to_string).collect()
+}
+
+impl AppConfig {
+    // The file's values, each overridden by its environment variable when set
+    pub fn from_sources(file: Option<&str>, var: impl Fn(&str) -> Option<String>) -> Result<Self, String> {
+        let mut config: AppConfig = match file {
+            Some(json) => serde_json::from_str(json).map_err(|e| e.to_string())?,
+            None => AppConfig::default(),
+        };
+
+        let strings: [(&str, &mut String); 18] = [
+            ("SERVER_HOST", &mut config.server.host),
+            ("MONGODB_URI", &mut config.mongodb.uri),
+            ("MONGODB_DATABASE", &mut config.mongodb.database),
+            ("MONGODB_AUTH_DB", &mut config.mongodb.auth_db),
+            ("REDIS_URL", &mut config.redis.url),
+            ("ICD_DATABASE", &mut config.terminology.icd.database),
+            ("ICD_COLLECTION", &mut config.terminology.icd.collection),
+            ("NAMASTE_DATABASE", &mut config.terminology.namaste.database),
+            ("NAMASTE_COLLECTION", &mut config.terminology.namaste.collection),
+            ("GEMINI_URL", &mut config.gemini.url),
+            ("AUTOCOMPLETE_RANKING_FILE", &mut config.files.ranking),
+            ("EMBEDDING_TEMPLATES_FILE", &mut config.files.embedding_templates),
+            ("ICD_CSV", &mut config.files.icd_csv),
+            ("NAMASTE_CSV", &mut config.files.namaste_csv),
+            ("UPLOADS_DIR", &mut config.files.uploads),
+            ("OAUTH_READ_SCOPE", &mut config.oauth.read_scope),
+            ("OAUTH_WRITE_SCOPE", &mut config.oauth.write_scope),
+            ("OAUTH_AUDIT_SCOPE", &mut config.oauth.audit_scope),
+        ];
+        for (name, field) in strings {
+            if let Some(value) = var(name) {
+                *field = value;
+            }
+        }
+        if let Some(storage) = var("TERMINOLOGY_STORAGE") {
+            config.terminology.storage = storage.parse()?;
+        }
+        if let Some(port) = var("SERVER_PORT") {
+            config.server.port = port.parse().map_err(|_| format!("SERVER_PORT must be a port number, got {}", port))?;
+        }
+        if let Some(origins) = var("CORS_ORIGINS") {
+            config.server.cors_origins = list(origins);
+        }
+        if let Some(suffixes) = var("CORS_ORIGIN_SUFFIXES") {
+            config.server.cors_origin_suffixes = list(suffixes);
+        }
+        if let Some(allow) = var("CORS_ALLOW_LOCALHOST") {
+            config.server.cors_allow_localhost = allow.parse().map_err(|_| format!("CORS_ALLOW_LOCALHOST must be true or false, got {}", allow))?;
+        }
+        if let Some(username) = var("MONGODB_USERNAME") {
+            config.mongodb.username = Some(username);
+        }
+        if let Some(password) = var("MONGODB_PASSWORD") {
+            config.mongodb.password = Some(password);
+        }
+        let optionals: [(&str, &mut Option<String>); 4] = [
+            ("OAUTH_JWKS_FILE", &mut config.oauth.jwks_file),
+            ("OAUTH_JWKS_URL", &mut config.oauth.jwks_url),
+            ("OAUTH_ISSUER", &mut config.oauth.issuer),
+            ("OAUTH_AUDIENCE", &mut config.oauth.audience),
+        ];
+        for (name, field) in optionals {
+            if let Some(value) = var(name) {
+                *field = Some(value);
+            }
+        }
+        config.embedding = EmbeddingConfig::from_vars(&var)?;
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    // Every problem at once, so a deployment is fixed in one go
+    fn validate(&self) -> Result<(), String> {
+        let mut problems = Vec::new();
+        let mut check = |ok: bool, problem: String| {
+            if !ok {
+                problems.push(problem);
+            }
+        };
+        let is_url = |value: &str, schemes: &[&str]| schemes.iter().any(|scheme| value.starts_with(scheme)) && !value.contains(char::is_whitespace);
+
+        check(!self.server.host.trim().is_empty(), "server.host is empty".to_string());
+        check(self.server.port != 0, "server.port must not be 0".to_string());
+        for origin in &self.server.cors_origins {
+            check(
+                is_url(origin, &["http://", "https://"]) && !origin.ends_with('/'),
+                format!("CORS origin {} must be scheme://host[:port] without a path", origin),
+            );
+        }
+        for suffix in &self.server.cors_origin_suffixes {
+            check(suffix.starts_with('.'), format!("CORS origin suffix {} must start with a dot", suffix));
+        }
+        check(is_url(&self.mongodb.uri, &["mongodb://", "mongodb+srv://"]), format!("mongodb.uri {} is not a MongoDB connection string", self.mongodb.uri));
+        check(is_url(&self.redis.url, &["redis://", "rediss://", "unix://"]), format!("redis.url {} is not a Redis URL", self.redis.url));
+        check(is_url(&self.gemini.url, &["http://", "https://"]), format!("gemini.url {} is not an http(s) URL", self.gemini.url));
+
+        // MongoDB refuses these characters in database names
+        let database_ok = |name: &str| !name.is_empty() && !name.contains(['/', '\\', '.', ' ', '"', '$']);
+        check(database_ok(&self.mongodb.database), format!("mongodb.database {:?} is not a valid database name", self.mongodb.database));
+        for (system, location) in [("icd", &self.terminology.icd), ("namaste", &self.terminology.namaste)] {
+            check(database_ok(&location.database), format!("terminology.{}.database {:?} is not a valid database name", system, location.database));
+            check(
+                !location.collection.is_empty() && !location.collection.contains('$') && !location.collection.starts_with("system."),
+                format!("terminology.{}.collection {:?} is not a valid collection name", system, location.collection),
+            );
+        }
+        check(!self.files.ranking.is_empty(), "files.ranking is empty".to_string());
+        check(!self.files.embedding_templates.is_empty(), "files.embedding_templates is empty".to_string());
+        check(!self.files.uploads.is_empty(), "files.uploads is empty".to_string());
+        let oauth = &self.oauth;
+        if oauth.has_jwks() {
+            check(oauth.issuer.as_deref().is_some_and(|issuer| !issuer.is_empty()), "oauth.issuer must be set when a JWKS is configured".to_string());
+            check(oauth.audience.as_deref().is_some_and(|audience| !audience.is_empty()), "oauth.audience must be set when a JWKS is configured".to_string());
+        }
+        if let Some(url) = &oauth.jwks_url {
+            check(is_url(url, &["http://", "https://"]), format!("oauth.jwks_url {} is not an http(s) URL", url));
+        }
+        check([&oauth.read_scope, &oauth.write_scope, &oauth.audit_scope].iter().all(|scope| !scope.is_empty()), "oauth scopes must not be empty".to_string());
+        if self.terminology.storage == Storage::Memory {
+            check(!self.files.icd_csv.is_empty(), "files.icd_csv is empty but terminology.storage is memory".to_string());
+            check(!self.files.namaste_csv.is_empty(), "files.namaste_csv is empty but terminology.storage is memory".to_string());
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join("; "))
+        }
+    }
+
+    pub fn bind_address(&self) -> (String, u16) {
+        (self.server.host.clone(), self.server.port)
+    }
+
+    // Whether CORS lets a browser origin call the API
+    pub fn allows_origin(&self, origin: &str) -> bool {
+        let server = &self.server;
+        server.cors_origins.iter().any(|allowed| allowed == origin)
+            || server.cors_origin_suffixes.iter().any(|suffix| origin.ends_with(suffix.as_str()))
+            || (server.cors_allow_localhost && (origin.contains("://localhost") || origin.contains("://127.0.0.1")))
+    }
+}
+
+static CONFIG: OnceLock<AppConfig> = OnceLock::new();
+
+fn load() -> Result<AppConfig, String> {
+    dotenv::dotenv().ok();
+    let var = |name: &str| env::var(name).ok().filter(|value| !value.is_empty());
+    // A missing default file means defaults and environment; a named file must exist
+    let named = var("CONFIG_FILE");
+    let path = named.clone().unwrap_or_else(|| DEFAULT_CONFIG_FILE.to_string());
+    let file = match std::fs::read_to_string(&path) {
+        Ok(json) => Some(json),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && named.is_none() => None,
+        Err(e) => return Err(format!("{} could not be read: {}", path, e)),
+    };
+    AppConfig::from_sources(file.as_deref(), var).map_err(|e| match file {
+        Some(_) => format!("{}: {}", path, e),
+        None => e,
+    })
+}
+
+// Load and validate the configuration; called first thing so that a bad one stops the
+// process before it serves anything
+pub fn init() -> Result<&'static AppConfig, String> {
+    if let Some(config) = CONFIG.get() {
+        return Ok(config);
+    }
+    let config = load()?;
+    Ok(CONFIG.get_or_init(|| config))
+}
+
+// The configuration every module reads
+pub fn get() -> &'static AppConfig {
+    CONFIG.get_or_init(|| load().unwrap_or_else(|e| panic!("Invalid configuration: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config(file: Option<&str>, vars: &[(&str, &str)]) -> Result<AppConfig, String> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        AppConfig::from_sources(file, |name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_file_values_are_overridden_by_env_and_validated() {
+        assert_eq!(config(None, &[]), Ok(AppConfig::default()));
+
+        let file = r#"{
+            "server": { "port": 9000, "cors_origins": ["https://emr.hospital-a.in"], "cors_allow_localhost": false },
+            "terminology": { "namaste": { "database": "terminology", "collection": "namaste_codes" } }
+        }"#;
+        let loaded = config(Some(file), &[("SERVER_PORT", "9100"), ("ICD_DATABASE", "terminology"), ("CORS_ORIGIN_SUFFIXES", ".hospital-a.in")]).unwrap();
+        assert_eq!(loaded.bind_address(), ("0.0.0.0".to_string(), 9100));
+        assert_eq!(loaded.terminology.namaste.collection, "namaste_codes");
+        assert_eq!(loaded.terminology.icd, CollectionLocation { database: "terminology".to_string(), collection: "icd11_entities".to_string() });
+        assert!(loaded.allows_origin("https://emr.hospital-a.in"));
+        assert!(loaded.allows_origin("https://lab.hospital-a.in"));
+        assert!(!loaded.allows_origin("http://localhost:5173"));
+        assert!(!loaded.allows_origin("https://abc.trycloudflare.com"));
+        assert!(AppConfig::default().allows_origin("http://127.0.0.1:3000"));
+
+        // Typos in the file and bad values are reported, all of them at once
+        assert!(config(Some(r#"{"server": {"prot": 1}}"#), &[]).is_err());
+        let error = config(None, &[("MONGODB_URI", "localhost:27017"), ("NAMASTE_DATABASE", "ayurveda.db"), ("CORS_ORIGINS", "http://a.in/")]).unwrap_err();
+        assert_eq!(error.matches("; ").count(), 2);
+        assert!(config(None, &[("SERVER_PORT", "http")]).is_err());
+        assert!(config(None, &[("EMBEDDING_PROVIDER", "word2vec")]).is_err());
+        assert_eq!(config(None, &[("TERMINOLOGY_STORAGE", "Memory")]).unwrap().terminology.storage, Storage::Memory);
+        assert!(config(None, &[("TERMINOLOGY_STORAGE", "sqlite")]).is_err());
+        assert!(config(Some(include_str!("../../config.example.json")), &[]).is_ok());
+
+        // A JWKS needs the issuer and audience tokens are checked against
+        assert!(config(None, &[("OAUTH_JWKS_URL", "https://abdm.example/certs")]).is_err());
+        let oauth = config(Some(r#"{"oauth": {"issuer": "https://abdm.example", "audience": "terminology"}}"#), &[("OAUTH_JWKS_FILE", "jwks.json"), ("OAUTH_READ_SCOPE", "read"), ("OAUTH_AUDIT_SCOPE", "audit")])
+            .unwrap()
+            .oauth;
+        assert_eq!(oauth.jwks_file.as_deref(), Some("jwks.json"));
+        assert_eq!((oauth.read_scope.as_str(), oauth.write_scope.as_str()), ("read", "terminology.write"));
+        assert_eq!(oauth.audit_scope, "audit");
+    }
+}
@@ -1,15 +1,17 @@
 use mongodb::{
     bson::{doc},
     options::{ClientOptions, ServerApi, ServerApiVersion},
-    Client, Database,
+    Client, ClientSession, Database,
 };
 use serde::{Deserialize, Serialize};
-use std::env;
 use tokio::sync::OnceCell;
-use dotenv::dotenv;
+
+use crate::config::{self, CollectionLocation};
 
 // Global MongoDB client instance
 static MONGO_CLIENT: OnceCell<MongoClient> = OnceCell::const_new();
+// Whether the server is a replica set or mongos, asked once
+static TRANSACTIONS: OnceCell<bool> = OnceCell::const_new();
 
 #[derive(Clone)]
 pub struct MongoClient {
@@ -28,27 +30,15 @@ pub struct ConnectionStatus {
 impl MongoClient {
     // Initialize MongoDB connection
     pub async fn new() -> Result<Self, mongodb::error::Error> {
-        // Load .env file variables
-        dotenv().ok();
-        
-        // Get connection string from environment or use default
-        let uri = env::var("MONGODB_URI")
-            .unwrap_or_else(|_| {
-                println!("⚠️  MONGODB_URI not found in .env, using default");
-                "mongodb://localhost:27017".to_string()
-            });
-        
-        let database_name = env::var("MONGODB_DATABASE")
-            .unwrap_or_else(|_| {
-                println!("⚠️  MONGODB_DATABASE not found in .env, using default");
-                "fhir_terminology".to_string()
-            });
+        let settings = &config::get().mongodb;
+        let uri = &settings.uri;
+        let database_name = &settings.database;
 
         println!("🔗 Connecting to MongoDB: {}", &uri);
         println!("📁 Using database: {}", &database_name);
 
         // Parse connection options
-        let mut client_options = ClientOptions::parse(&uri).await?;
+        let mut client_options = ClientOptions::parse(uri).await?;
         
         // Set the server API version
         let server_api = ServerApi::builder()
@@ -60,24 +50,22 @@ impl MongoClient {
         client_options.app_name = Some("FHIR Terminology Server".to_string());
         
         // Add authentication if provided
-        if let (Ok(username), Ok(password)) = (env::var("MONGODB_USERNAME"), env::var("MONGODB_PASSWORD")) {
-            if !username.is_empty() && !password.is_empty() {
-                let auth_db = env::var("MONGODB_AUTH_DB").unwrap_or_else(|_| "admin".to_string());
-                println!("🔐 Using authentication for user: {}", username);
+        if let (Some(username), Some(password)) = (&settings.username, &settings.password)
+            && !username.is_empty() && !password.is_empty() {
+            println!("🔐 Using authentication for user: {}", username);
                 
-                client_options.credential = Some(
-                    mongodb::options::Credential::builder()
-                        .username(username)
-                        .password(password)
-                        .source(auth_db)
-                        .build()
-                );
-            }
+            client_options.credential = Some(
+                mongodb::options::Credential::builder()
+                    .username(username.clone())
+                    .password(password.clone())
+                    .source(settings.auth_db.clone())
+                    .build()
+            );
         }
         
         // Create client
         let client = Client::with_options(client_options)?;
-        let database = client.database(&database_name);
+        let database = client.database(database_name);
         
         println!("✅ MongoDB client initialized for database: {}", database_name);
         
@@ -98,6 +86,33 @@ impl MongoClient {
         self.client.database(db_name)
     }
     
+    // A code system's concepts, wherever the configuration puts them
+    pub fn collection_at<T>(&self, location: &CollectionLocation) -> mongodb::Collection<T> {
+        self.client.database(&location.database).collection::<T>(&location.collection)
+    }
+
+    // Default database (MONGODB_DATABASE) holding the server's own collections
+    pub fn database(&self) -> &Database {
+        &self.database
+    }
+
+    // Session for writes that must commit together; transactions need a replica set
+    pub async fn start_session(&self) -> Result<ClientSession, mongodb::error::Error> {
+        self.client.start_session(None).await
+    }
+
+    // Transactions need a replica set (even a single-node one) or a sharded cluster; a
+    // standalone mongod, as InstallMongo.sh sets up by default, refuses them
+    pub async fn supports_transactions(&self) -> Result<bool, mongodb::error::Error> {
+        TRANSACTIONS
+            .get_or_try_init(|| async {
+                let hello = self.client.database("admin").run_command(doc! { "hello": 1 }, None).await?;
+                Ok(hello.contains_key("setName") || hello.get_str("msg") == Ok("isdbgrid"))
+            })
+            .await
+            .copied()
+    }
+
     // Health check - ping the database
     pub async fn health_check(&self) -> ConnectionStatus {
         match self.client
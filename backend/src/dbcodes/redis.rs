@@ -1,8 +1,49 @@
 use redis::{Client, aio::ConnectionManager, AsyncCommands};
 use tokio::sync::OnceCell;
 use serde::{Deserialize, Serialize};
-use std::env;
-use dotenv::dotenv;
+use std::collections::{HashMap, HashSet};
+
+use crate::autocomplete::ranking::{self, Contribution, MatchType};
+use crate::embedding::hashing::fnv1a;
+use crate::fulltext::fuzzy;
+
+// Words the final query word may expand to
+const MAX_PREFIX_WORDS: isize = 100;
+// Concepts written per pipeline round-trip
+pub const PIPELINE_BATCH: usize = 500;
+// Keys deleted per DEL when dropping a namespace
+const DROP_BATCH: usize = 1000;
+
+// Remove one stale concept from a namespace. ARGV: namespace, concept id, entry, then per
+// word of the entry: the word, its trigram count and the trigrams. The entry itself goes only
+// once no other concept indexes the same text, and a word (with its trigrams) once no entry
+// contains it
+const REMOVE_STALE: &str = r#"
+local ns, id, entry = ARGV[1], ARGV[2], ARGV[3]
+redis.call('HDEL', ns .. ':concepts', id)
+redis.call('HDEL', ns .. ':fingerprints', id)
+local owners = ns .. ':owners:' .. entry
+redis.call('SREM', owners, id)
+if redis.call('SCARD', owners) > 0 then
+    return 0
+end
+redis.call('ZREM', ns, entry)
+redis.call('HDEL', ns .. ':payloads', entry)
+local i = 4
+while i <= #ARGV do
+    local word, grams = ARGV[i], tonumber(ARGV[i + 1])
+    local postings = ns .. ':postings:' .. word
+    redis.call('SREM', postings, entry)
+    if redis.call('SCARD', postings) == 0 then
+        redis.call('ZREM', ns .. ':words', word)
+        for g = 1, grams do
+            redis.call('SREM', ns .. ':grams:' .. ARGV[i + 1 + g], word)
+        end
+    end
+    i = i + 2 + grams
+end
+return 1
+"#;
 
 // Global Redis client instance
 static REDIS_CLIENT: OnceCell<ConnectionManager> = OnceCell::const_new();
@@ -21,17 +62,10 @@ pub struct RedisClient {
 
 impl RedisClient {
     pub async fn new() -> Result<RedisClient, redis::RedisError> {
-        dotenv().ok();
+        let redis_url = &crate::config::get().redis.url;
+        println!("🔗 Connecting to Redis: {}", redis_url);
         
-        let redis_url = env::var("REDIS_URL")
-            .unwrap_or_else(|_| {
-                println!("⚠️ REDIS_URL not found in .env, using default");
-                "redis://127.0.0.1/".to_string()
-            });
-        
-        println!("🔗 Connecting to Redis: {}", &redis_url);
-        
-        let client = Client::open(redis_url)?;
+        let client = Client::open(redis_url.as_str())?;
         let manager = ConnectionManager::new(client).await?;
         
         println!("✅ Redis client initialized");
@@ -68,243 +102,345 @@ impl RedisClient {
         }
     }
 
-    // Calculate relevance score based on multiple factors
-    fn calculate_relevance_score(&self, query: &str, entry: &str, match_type: MatchType) -> f64 {
-        let query_lower = query.to_lowercase();
-        let entry_lower = entry.to_lowercase();
-        
-        let mut score = 1.0;
-        
-        // 1. Match type scoring
-        match match_type {
-            MatchType::ExactTitle => score += 10.0,      // "Fever" matches "Fever"
-            MatchType::StartsWith => score += 8.0,       // "Fev" matches "Fever" 
-            MatchType::WordStart => score += 5.0,        // "Acute" matches "Acute kidney injury"
-            MatchType::Contains => score += 2.0,         // "fever" matches "rheumatic fever"
-            MatchType::Related => score += 1.0,          // Related terms
-        }
-        
-        // 2. Query length vs match length (prefer shorter, more specific matches)
-        let query_len = query_lower.len() as f64;
-        let entry_len = entry_lower.len() as f64;
-        if entry_len > 0.0 {
-            score += (query_len / entry_len) * 2.0;
-        }
-        
-        // 3. Common medical terms boost
-        let common_terms = [
-            "fever", "pain", "infection", "acute", "chronic", "syndrome", 
-            "disease", "disorder", "injury", "fracture", "diabetes", 
-            "hypertension", "pneumonia", "cancer", "tumor", "inflammation"
-        ];
-        
-        for term in &common_terms {
-            if entry_lower.contains(term) {
-                score += 1.5;
-            }
-        }
-        
-        // 4. Penalty for very specific/rare conditions
-        let complexity_indicators = [
-            "unspecified", "not elsewhere classified", "other specified", 
-            "without mention", "with mention", "sequela"
-        ];
-        
-        for indicator in &complexity_indicators {
-            if entry_lower.contains(indicator) {
-                score -= 2.0;
+    // Namespace a category's autocomplete keys live under. Indexing builds into a fresh
+    // namespace and swaps this pointer; before the first build it is the legacy fixed name
+    pub async fn namespace(&self, category: &str) -> Result<String, redis::RedisError> {
+        let mut conn = self.manager.clone();
+        let current: Option<String> = conn.get(current_key(category)).await?;
+        Ok(current.unwrap_or_else(|| legacy_namespace(category)))
+    }
+
+    // The swapped-in namespace, None while the category still uses the legacy keys
+    pub async fn active_namespace(&self, category: &str) -> Result<Option<String>, redis::RedisError> {
+        let mut conn = self.manager.clone();
+        conn.get(current_key(category)).await
+    }
+
+    // Point the category at `namespace`, returning the one it replaces
+    pub async fn swap_namespace(&self, category: &str, namespace: &str) -> Result<String, redis::RedisError> {
+        let mut conn = self.manager.clone();
+        let previous: Option<String> = conn.getset(current_key(category), namespace).await?;
+        Ok(previous.unwrap_or_else(|| legacy_namespace(category)))
+    }
+
+    // Delete every key of a namespace that is no longer being read
+    pub async fn drop_namespace(&self, namespace: &str) -> Result<usize, redis::RedisError> {
+        let mut conn = self.manager.clone();
+        let mut keys: Vec<String> = {
+            let mut scan = conn.scan_match::<_, String>(format!("{}:*", namespace)).await?;
+            let mut keys = Vec::new();
+            while let Some(key) = scan.next_item().await {
+                keys.push(key);
             }
+            keys
+        };
+        keys.push(namespace.to_string());
+        let mut dropped = 0;
+        for chunk in keys.chunks(DROP_BATCH) {
+            let deleted: usize = conn.del(chunk).await?;
+            dropped += deleted;
         }
-        
-        // 5. Boost for primary conditions (shorter codes typically)
-        if let Some(code_part) = entry.split_whitespace().next() {
-            if code_part.len() <= 4 {  // Short codes like "A00", "I10" are usually primary
-                score += 3.0;
-            }
+        Ok(dropped)
+    }
+
+    // Concept id -> fingerprint and concept id -> entry of everything indexed in a namespace
+    pub async fn indexed_concepts(
+        &self,
+        namespace: &str,
+    ) -> Result<(HashMap<String, String>, HashMap<String, String>), redis::RedisError> {
+        let mut conn = self.manager.clone();
+        let fingerprints: HashMap<String, String> = conn.hgetall(format!("{}:fingerprints", namespace)).await?;
+        let entries: HashMap<String, String> = conn.hgetall(format!("{}:concepts", namespace)).await?;
+        Ok((fingerprints, entries))
+    }
+
+    // Entries containing `word`, from its posting list
+    async fn postings(
+        &self,
+        conn: &mut ConnectionManager,
+        namespace: &str,
+        word: &str,
+    ) -> Result<HashSet<String>, redis::RedisError> {
+        conn.smembers(format!("{}:postings:{}", namespace, word)).await
+    }
+
+    // Union of the posting lists of `words`, restricted to `within` when earlier query words
+    // already narrowed the candidates
+    async fn entries_for_words(
+        &self,
+        conn: &mut ConnectionManager,
+        namespace: &str,
+        words: impl IntoIterator<Item = &str>,
+        within: Option<&HashSet<String>>,
+    ) -> Result<HashSet<String>, redis::RedisError> {
+        let mut entries = HashSet::new();
+        for word in words {
+            let postings = self.postings(conn, namespace, word).await?;
+            entries.extend(postings.into_iter().filter(|entry| within.is_none_or(|within| within.contains(entry))));
         }
-        
-        score.max(0.1) // Ensure minimum score
+        Ok(entries)
     }
-    
-    // Determine match type
-    fn get_match_type(&self, query: &str, entry: &str) -> MatchType {
-        let query_lower = query.to_lowercase();
-        let entry_lower = entry.to_lowercase();
-        
-        // Extract title from entry (after the code)
-        let title = if let Some(space_idx) = entry.find(' ') {
-            &entry[space_idx + 1..]
-        } else {
-            entry
-        }.to_lowercase();
-        
-        if title == query_lower {
-            MatchType::ExactTitle
-        } else if title.starts_with(&query_lower) {
-            MatchType::StartsWith
-        } else if entry_lower.split_whitespace().any(|word| word.starts_with(&query_lower)) {
-            MatchType::WordStart
-        } else if entry_lower.contains(&query_lower) {
-            MatchType::Contains
-        } else {
-            MatchType::Related
+
+    // Indexed words whose beginning is within the typo budget of the query, fewest typos
+    // first. Candidates come from the trigram sets, then are checked by edit distance
+    async fn fuzzy_words(
+        &self,
+        conn: &mut ConnectionManager,
+        namespace: &str,
+        search_term: &str,
+        limit: usize,
+    ) -> Result<Vec<(String, usize)>, redis::RedisError> {
+        let max = fuzzy::max_typos(search_term.chars().count());
+        if max == 0 {
+            return Ok(Vec::new());
+        }
+
+        let grams = fuzzy::trigrams(search_term);
+        let mut shared: HashMap<String, usize> = HashMap::new();
+        for gram in &grams {
+            let words: Vec<String> = conn.smembers(format!("{}:grams:{}", namespace, gram)).await?;
+            for word in words {
+                *shared.entry(word).or_default() += 1;
+            }
         }
+
+        let needed = fuzzy::min_shared_trigrams(grams.len(), max);
+        let mut candidates: Vec<(String, usize)> = shared
+            .into_iter()
+            .filter(|(_, count)| *count >= needed)
+            .filter_map(|(word, _)| fuzzy::prefix_distance(search_term, &word, max).map(|typos| (word, typos)))
+            .collect();
+        candidates.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+        candidates.truncate(limit);
+        Ok(candidates)
     }
 
-    // Enhanced autocomplete with relevance scoring
+    // Autocomplete ranked by the category's ranking profile. Every query word but the last must appear
+    // in the entry; the last may be unfinished and matches as a prefix ("acute kid" finds
+    // "acute kidney injury"), or within the typo budget when nothing starts with it
     pub async fn get_autocomplete_suggestions(
         &self,
         key: &str,
         prefix: &str,
         limit: usize,
+        explain: bool,
     ) -> Result<Vec<AutocompleteSuggestion>, redis::RedisError> {
         let mut conn = self.manager.clone();
         let search_term = prefix.trim().to_lowercase();
+        let Some((last, complete)) = query_words(&search_term) else {
+            return Ok(Vec::new());
+        };
+        let namespace = self.namespace(key).await?;
+        let profile = ranking::profile(key);
         
-        println!("🔍 Searching for '{}' in autocomplete:{}", search_term, key);
+        println!("🔍 Searching for '{}' in {}", search_term, namespace);
         
-        // Search in word index
-        let start_range = format!("[{}", search_term);
-        let end_range = format!("[{}~", search_term);
+        // Intersect the posting lists of the finished words
+        let mut required: Option<HashSet<String>> = None;
+        for word in &complete {
+            let postings = self.postings(&mut conn, &namespace, word).await?;
+            let narrowed = match required {
+                Some(previous) => previous.intersection(&postings).cloned().collect(),
+                None => postings,
+            };
+            if narrowed.is_empty() {
+                println!("🔍 No entry contains every word of '{}'", search_term);
+                return Ok(Vec::new());
+            }
+            required = Some(narrowed);
+        }
         
+        // Expand the last word over the word index
+        let start_range = format!("[{}", last);
+        let end_range = format!("[{}~", last);
         let matching_words: Vec<String> = conn
             .zrangebylex_limit(
-                format!("autocomplete:{}:words", key),
+                format!("{}:words", namespace),
                 &start_range,
                 &end_range,
                 0,
-                (limit * 3) as isize, // Get more candidates for better relevance filtering
+                MAX_PREFIX_WORDS,
             )
             .await?;
         
         println!("🔍 Found {} matching words: {:?}", matching_words.len(), matching_words);
         
-        let mut scored_results = Vec::new();
-        let mut seen_entries = std::collections::HashSet::new();
-        
-        for word in matching_words {
-            let full_entry: Option<String> = conn
-                .hget(format!("autocomplete:{}:word_to_entry", key), &word)
-                .await
-                .unwrap_or(None);
-                
-            if let Some(entry) = full_entry {
-                if seen_entries.insert(entry.clone()) {
-                    let payload: Option<String> = conn
-                        .hget(format!("autocomplete:{}:payloads", key), &entry)
-                        .await
-                        .unwrap_or(None);
-                    
-                    // Calculate intelligent relevance score
-                    let match_type = self.get_match_type(&search_term, &entry);
-                    let relevance_score = self.calculate_relevance_score(&search_term, &entry, match_type);
-                    
-                    scored_results.push(ScoredSuggestion {
-                        suggestion: AutocompleteSuggestion {
-                            text: entry,
-                            payload,
-                            score: relevance_score,
-                        },
-                        relevance_score,
-                    });
+        let entries = self
+            .entries_for_words(&mut conn, &namespace, matching_words.iter().map(String::as_str), required.as_ref())
+            .await?;
+        let mut scored: Vec<(String, f64, MatchType)> = entries
+            .into_iter()
+            .map(|entry| {
+                let match_type = MatchType::of(&search_term, &entry);
+                let score = profile.score(&search_term, &entry, match_type);
+                (entry, score, match_type)
+            })
+            .collect();
+
+        // Too few prefix matches: try words one or two typos away
+        if scored.len() < limit {
+            let fuzzy_words = self.fuzzy_words(&mut conn, &namespace, last, limit * 3).await?;
+            println!("🔍 Found {} words within the typo budget: {:?}", fuzzy_words.len(), fuzzy_words);
+            let mut seen: HashSet<String> = scored.iter().map(|(entry, _, _)| entry.clone()).collect();
+            for (word, typos) in fuzzy_words {
+                for entry in self.entries_for_words(&mut conn, &namespace, [word.as_str()], required.as_ref()).await? {
+                    if seen.insert(entry.clone()) {
+                        let match_type = MatchType::Fuzzy(typos);
+                        let score = profile.score(&search_term, &entry, match_type);
+                        scored.push((entry, score, match_type));
+                    }
                 }
             }
         }
         
-        // Sort by relevance score (highest first)
-        scored_results.sort_by(|a, b| b.relevance_score.partial_cmp(&a.relevance_score).unwrap());
+        // Sort by relevance score (highest first), ties alphabetically so results are stable
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        scored.truncate(limit);
         
-        // Take top results
-        let results: Vec<AutocompleteSuggestion> = scored_results
-            .into_iter()
-            .take(limit)
-            .map(|scored| scored.suggestion)
-            .collect();
+        // Payloads only for the entries we return
+        let mut results = Vec::with_capacity(scored.len());
+        for (entry, relevance_score, match_type) in scored {
+            let payload: Option<String> = conn
+                .hget(format!("{}:payloads", namespace), &entry)
+                .await
+                .unwrap_or(None);
+            let explanation = explain.then(|| profile.explain(&search_term, &entry, match_type).1);
+            results.push(AutocompleteSuggestion {
+                text: entry,
+                payload,
+                score: relevance_score,
+                explanation,
+            });
+        }
         
         println!("🔍 Returning {} results with improved relevance scoring", results.len());
         
         Ok(results)
     }
 
-    pub async fn bulk_add_suggestions(
+    // Index a batch of concepts into `namespace` in one atomic pipeline, first removing the
+    // entries of `stale` concepts (id, entry) that are being replaced or deleted. Readers
+    // never see a concept half-indexed. Concepts sharing an entry text keep it until the
+    // last of them goes (see REMOVE_STALE)
+    pub async fn index_batch(
         &self,
-        suggestions: Vec<BulkSuggestion>,
+        namespace: &str,
+        batch: &[&BulkSuggestion],
+        stale: &[(String, String)],
     ) -> Result<(), redis::RedisError> {
         let mut conn = self.manager.clone();
+        let mut pipe = redis::pipe();
+        pipe.atomic();
         
-        for suggestion in suggestions {
+        for (id, entry) in stale {
+            pipe.cmd("EVAL").arg(REMOVE_STALE).arg(0).arg(remove_stale_args(namespace, id, entry)).ignore();
+        }
+        
+        for suggestion in batch {
             let clean_text = suggestion.text.trim().to_lowercase();
             
-            // Store the full entry
-            let _: () = conn.zadd(
-                format!("autocomplete:{}", suggestion.category),
-                &clean_text,
-                suggestion.score,
-            ).await?;
+            // Store the full entry, and which concepts index it
+            pipe.zadd(namespace, &clean_text, suggestion.score).ignore()
+                .sadd(format!("{}:owners:{}", namespace, clean_text), &suggestion.id).ignore();
             
             // Store payload
             if let Some(payload) = &suggestion.payload {
-                let _: () = conn.hset(
-                    format!("autocomplete:{}:payloads", suggestion.category),
-                    &clean_text,
-                    payload,
-                ).await?;
+                pipe.hset(format!("{}:payloads", namespace), &clean_text, payload).ignore();
             }
             
-            // Store individual words for better searchability
-            let words: Vec<&str> = clean_text.split_whitespace().collect();
-            
-            for word in words {
-                if word.len() >= 2 {
-                    let word_clean = word.trim_end_matches(&[',', '.', ';', ':', '!', '?'][..]);
-                    
-                    let word_key = format!("autocomplete:{}:words", suggestion.category);
-                    let _: () = conn.zadd(&word_key, word_clean, suggestion.score).await?;
-                    
-                    // Link word back to full entry
-                    let _: () = conn.hset(
-                        format!("autocomplete:{}:word_to_entry", suggestion.category),
-                        word_clean,
-                        &clean_text,
-                    ).await?;
+            // Posting list per word, so every entry containing it stays reachable
+            for word in index_words(&clean_text) {
+                pipe.zadd(format!("{}:words", namespace), &word, suggestion.score).ignore()
+                    .sadd(format!("{}:postings:{}", namespace, word), &clean_text).ignore();
+
+                // Trigrams for typo-tolerant lookup
+                for gram in fuzzy::trigrams(&word) {
+                    pipe.sadd(format!("{}:grams:{}", namespace, gram), &word).ignore();
                 }
             }
+            
+            // What was indexed for the concept, so re-indexing can skip or replace it
+            pipe.hset(format!("{}:concepts", namespace), &suggestion.id, &clean_text).ignore()
+                .hset(format!("{}:fingerprints", namespace), &suggestion.id, suggestion.fingerprint()).ignore();
         }
         
-        Ok(())
+        pipe.query_async(&mut conn).await
     }
 }
 
-#[derive(Debug, Clone)]
-enum MatchType {
-    ExactTitle,    // Query exactly matches the title
-    StartsWith,    // Title starts with query
-    WordStart,     // A word in the title starts with query
-    Contains,      // Title contains query
-    Related,       // Related/partial match
-}
-
-#[derive(Debug)]
-struct ScoredSuggestion {
-    suggestion: AutocompleteSuggestion,
-    relevance_score: f64,
-}
-
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct AutocompleteSuggestion {
     pub text: String,
     pub payload: Option<String>,
     pub score: f64,
+    // Score components, when asked to explain the ranking
+    pub explanation: Option<Vec<Contribution>>,
 }
 
 #[derive(Clone, Debug)]
 pub struct BulkSuggestion {
-    pub category: String,
+    // Concept the entry was built from, e.g. the ICD entity id or NAMC_ID
+    pub id: String,
     pub text: String,
     pub score: f64,
     pub payload: Option<String>,
 }
 
+impl BulkSuggestion {
+    // Changes whenever the indexed text or payload does
+    pub fn fingerprint(&self) -> String {
+        let payload = self.payload.as_deref().unwrap_or_default();
+        format!("{:016x}", fnv1a(format!("{}\u{1f}{}", self.text, payload).as_bytes()))
+    }
+}
+
+fn current_key(category: &str) -> String {
+    format!("autocomplete:current:{}", category)
+}
+
+// Where categories kept their keys before indexing moved to swapped namespaces
+fn legacy_namespace(category: &str) -> String {
+    format!("autocomplete:{}", category)
+}
+
+// REMOVE_STALE's arguments for one concept
+fn remove_stale_args(namespace: &str, id: &str, entry: &str) -> Vec<String> {
+    let mut args = vec![namespace.to_string(), id.to_string(), entry.to_string()];
+    for word in index_words(entry) {
+        let grams = fuzzy::trigrams(&word);
+        args.push(word);
+        args.push(grams.len().to_string());
+        args.extend(grams);
+    }
+    args
+}
+
+// Distinct words of an entry as indexed: surrounding punctuation trimmed, single letters skipped
+fn index_words(text: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        let word = word.trim_matches(|c: char| c.is_ascii_punctuation());
+        if word.chars().count() >= 2 && !words.iter().any(|w| w == word) {
+            words.push(word.to_string());
+        }
+    }
+    words
+}
+
+// The query's last, possibly unfinished, word and the finished words before it
+fn query_words(query: &str) -> Option<(&str, Vec<&str>)> {
+    let mut words: Vec<&str> = query
+        .split_whitespace()
+        .map(|word| word.trim_matches(|c: char| c.is_ascii_punctuation()))
+        .filter(|word| !word.is_empty())
+        .collect();
+    let last = words.pop()?;
+    words.retain(|word| word.chars().count() >= 2);
+    words.sort_unstable();
+    words.dedup();
+    Some((last, words))
+}
+
 // Initialize Redis connection
 pub async fn init_redis() -> Result<(), redis::RedisError> {
     RedisClient::get_instance().await?;
@@ -342,4 +478,24 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_entries_and_queries_split_into_the_same_words() {
+        assert_eq!(index_words("n17 acute kidney injury, acute"), vec!["n17", "acute", "kidney", "injury"]);
+        assert_eq!(index_words("(fever) of a child"), vec!["fever", "of", "child"]);
+        assert_eq!(query_words("acute kid"), Some(("kid", vec!["acute"])));
+        assert_eq!(query_words("kidney a acute, kidney inj"), Some(("inj", vec!["acute", "kidney"])));
+        assert_eq!(query_words("  ,, "), None);
+    }
+
+    #[test]
+    fn test_stale_entries_carry_each_word_with_its_trigrams() {
+        let args = remove_stale_args("autocomplete:icd:2", "1A00", "cholera, ab");
+        assert_eq!(&args[..3], ["autocomplete:icd:2", "1A00", "cholera, ab"]);
+        let grams = fuzzy::trigrams("cholera");
+        assert_eq!(args[3], "cholera");
+        assert_eq!(args[4], grams.len().to_string());
+        assert_eq!(args[5..5 + grams.len()], grams[..]);
+        assert_eq!(args[5 + grams.len()..], ["ab", "1", "^ab"]);
+    }
 }
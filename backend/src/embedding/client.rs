@@ -0,0 +1,287 @@
+// HTTP plumbing shared by the remote providers: one pooled client, a token bucket that
+// spaces requests out, and retries with exponential backoff and jitter on 429s, 5xx and
+// connection errors
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use reqwest::StatusCode;
+use serde_json::Value;
+
+pub const DEFAULT_BATCH_SIZE: usize = 100;
+pub const DEFAULT_MAX_RETRIES: u32 = 5;
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientSettings {
+    // Texts sent per request
+    pub batch_size: usize,
+    // Requests per second, None for no limit
+    pub rate_limit: Option<f64>,
+    // Retries after the first attempt
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    // A request that has not been answered in `timeout` is retried like a connection error
+    pub timeout: Duration,
+    pub connect_timeout: Duration,
+}
+
+impl Default for ClientSettings {
+    fn default() -> Self {
+        ClientSettings {
+            batch_size: DEFAULT_BATCH_SIZE,
+            rate_limit: None,
+            max_retries: DEFAULT_MAX_RETRIES,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            timeout: DEFAULT_TIMEOUT,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+        }
+    }
+}
+
+impl ClientSettings {
+    // Delay before retry number `retry` (0 for the first): doubling from base_delay up to
+    // max_delay, half of it fixed and half scaled by `jitter` in [0, 1] so that concurrent
+    // callers do not retry in lockstep
+    pub fn backoff(&self, retry: u32, jitter: f64) -> Duration {
+        let delay = (self.base_delay.as_secs_f64() * 2f64.powi(retry.min(30) as i32)).min(self.max_delay.as_secs_f64());
+        Duration::from_secs_f64(delay / 2.0 + delay / 2.0 * jitter.clamp(0.0, 1.0))
+    }
+
+    // The server's Retry-After when it sent one, but never longer than max_delay
+    pub fn retry_delay(&self, retry_after: Option<Duration>, retry: u32) -> Duration {
+        match retry_after {
+            Some(wait) => wait.min(self.max_delay),
+            None => self.backoff(retry, jitter()),
+        }
+    }
+}
+
+// A random number in [0, 1]; every RandomState is seeded differently
+fn jitter() -> f64 {
+    RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64
+}
+
+// Holds up to `rate` tokens (at least one) and refills `rate` per second; each request takes one
+pub struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    // Tokens left and when they were counted
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    pub fn new(rate: f64) -> Self {
+        let capacity = rate.max(1.0);
+        TokenBucket { rate, capacity, state: Mutex::new((capacity, Instant::now())) }
+    }
+
+    // Wait until a request may go out
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                let now = Instant::now();
+                let tokens = (state.0 + now.duration_since(state.1).as_secs_f64() * self.rate).min(self.capacity);
+                if tokens >= 1.0 {
+                    *state = (tokens - 1.0, now);
+                    return;
+                }
+                *state = (tokens, now);
+                Duration::from_secs_f64((1.0 - tokens) / self.rate)
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RequestError {
+    // A 4xx other than 429: sending the same request again will not help
+    Rejected { status: u16, body: String },
+    // Still failing after every retry
+    Exhausted { attempts: u32, last: String },
+}
+
+impl std::fmt::Display for RequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestError::Rejected { status, body } => write!(f, "request rejected with {}: {}", status, body),
+            RequestError::Exhausted { attempts, last } => write!(f, "gave up after {} attempts: {}", attempts, last),
+        }
+    }
+}
+
+impl std::error::Error for RequestError {}
+
+// True when the endpoint refused the request itself, e.g. one text in a batch is invalid
+pub fn is_rejected(error: &anyhow::Error) -> bool {
+    matches!(error.downcast_ref::<RequestError>(), Some(RequestError::Rejected { .. }))
+}
+
+fn retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+// Retry-After in seconds, as rate-limited APIs send with 429 and 503
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    let seconds: f64 = response.headers().get("retry-after")?.to_str().ok()?.trim().parse().ok()?;
+    Some(Duration::from_secs_f64(seconds.max(0.0)))
+}
+
+pub struct HttpClient {
+    client: reqwest::Client,
+    limiter: Option<TokenBucket>,
+    settings: ClientSettings,
+}
+
+impl HttpClient {
+    pub fn new(settings: ClientSettings) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(settings.timeout)
+            .connect_timeout(settings.connect_timeout)
+            .build()
+            .expect("Failed to build the embedding HTTP client");
+        HttpClient {
+            client,
+            limiter: settings.rate_limit.filter(|rate| *rate > 0.0).map(TokenBucket::new),
+            settings,
+        }
+    }
+
+    pub fn batch_size(&self) -> usize {
+        self.settings.batch_size
+    }
+
+    // POST a JSON body and return the JSON answer, waiting for the rate limit before every
+    // attempt and backing off between them
+    pub async fn post_json(&self, url: &str, headers: &[(&str, &str)], body: &Value) -> anyhow::Result<Value> {
+        let mut retry = 0;
+        loop {
+            if let Some(limiter) = &self.limiter {
+                limiter.acquire().await;
+            }
+            let mut request = self.client.post(url).json(body);
+            for (name, value) in headers {
+                request = request.header(*name, *value);
+            }
+
+            let (wait, last) = match request.send().await {
+                Ok(response) if response.status().is_success() => return Ok(response.json().await?),
+                Ok(response) if retryable(response.status()) => {
+                    (retry_after(&response), format!("{} from {}", response.status(), url))
+                },
+                Ok(response) => {
+                    let status = response.status().as_u16();
+                    let body = response.text().await.unwrap_or_default();
+                    return Err(RequestError::Rejected { status, body }.into());
+                },
+                Err(e) if e.is_connect() || e.is_timeout() || e.is_request() => (None, e.to_string()),
+                Err(e) => return Err(e.into()),
+            };
+
+            if retry >= self.settings.max_retries {
+                return Err(RequestError::Exhausted { attempts: retry + 1, last }.into());
+            }
+            let delay = self.settings.retry_delay(wait, retry);
+            println!("⏳ Embedding request failed ({}), retrying in {:.1}s", last, delay.as_secs_f64());
+            tokio::time::sleep(delay).await;
+            retry += 1;
+        }
+    }
+}
+
+// A local HTTP server for provider tests: answers every request with the next canned
+// response and keeps the JSON bodies it received
+#[cfg(test)]
+pub mod mock {
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+
+    use actix_web::{http::StatusCode, web, App, HttpResponse, HttpServer};
+    use serde_json::{json, Value};
+
+    pub struct MockServer {
+        pub url: String,
+        pub requests: Arc<Mutex<Vec<Value>>>,
+    }
+
+    // 429s are sent with Retry-After: 0 so tests do not wait
+    pub fn serve(responses: Vec<(u16, Value)>) -> MockServer {
+        let responses = Arc::new(Mutex::new(VecDeque::from(responses)));
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let server = HttpServer::new(move || {
+            let responses = responses.clone();
+            let recorded = recorded.clone();
+            App::new().default_service(web::to(move |body: web::Json<Value>| {
+                recorded.lock().unwrap().push(body.into_inner());
+                let (status, answer) = responses.lock().unwrap().pop_front().unwrap_or((500, json!({})));
+                let mut response = HttpResponse::build(StatusCode::from_u16(status).unwrap());
+                if status == 429 {
+                    response.insert_header(("Retry-After", "0"));
+                }
+                async move { response.json(answer) }
+            }))
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let url = format!("http://{}", server.addrs()[0]);
+        actix_web::rt::spawn(server.run());
+        MockServer { url, requests }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn quick() -> ClientSettings {
+        ClientSettings { max_retries: 2, base_delay: Duration::from_millis(1), ..ClientSettings::default() }
+    }
+
+    #[actix_web::test]
+    async fn test_retries_transient_failures_and_stops_on_rejections() {
+        let settings = ClientSettings::default();
+        assert_eq!(settings.backoff(0, 0.0), Duration::from_millis(250));
+        assert_eq!(settings.backoff(2, 1.0), Duration::from_secs(2));
+        assert_eq!(settings.backoff(20, 1.0), settings.max_delay);
+        assert_eq!(settings.retry_delay(Some(Duration::from_secs(3600)), 0), settings.max_delay);
+
+        let server = mock::serve(vec![(429, json!({})), (503, json!({})), (200, json!({ "ok": true }))]);
+        let client = HttpClient::new(quick());
+        let answer = client.post_json(&server.url, &[], &json!({ "n": 1 })).await.unwrap();
+        assert_eq!(answer, json!({ "ok": true }));
+        assert_eq!(server.requests.lock().unwrap().len(), 3);
+
+        let server = mock::serve(vec![(500, json!({})), (500, json!({})), (500, json!({}))]);
+        let error = client.post_json(&server.url, &[], &json!({})).await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<RequestError>(), Some(RequestError::Exhausted { attempts: 3, .. })));
+
+        let server = mock::serve(vec![(400, json!({ "error": "bad input" }))]);
+        let error = client.post_json(&server.url, &[], &json!({})).await.unwrap_err();
+        assert!(is_rejected(&error));
+        assert_eq!(server.requests.lock().unwrap().len(), 1);
+
+        // A server that accepts the connection but never answers times out and is retried
+        let silent = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", silent.local_addr().unwrap());
+        let client = HttpClient::new(ClientSettings { timeout: Duration::from_millis(100), ..quick() });
+        let error = client.post_json(&url, &[], &json!({})).await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<RequestError>(), Some(RequestError::Exhausted { attempts: 3, .. })));
+
+        // Two requests a second with a burst of two: the third waits about half a second
+        let bucket = TokenBucket::new(2.0);
+        let started = Instant::now();
+        for _ in 0..3 {
+            bucket.acquire().await;
+        }
+        assert!(started.elapsed() >= Duration::from_millis(400));
+    }
+}
@@ -0,0 +1,134 @@
+// Google Gemini embedContent and batchEmbedContents APIs
+use futures::future::BoxFuture;
+use serde_json::{json, Value};
+
+use super::client::{ClientSettings, HttpClient};
+use super::EmbeddingProvider;
+
+// Requests batchEmbedContents accepts at once
+const MAX_BATCH: usize = 100;
+
+pub struct GeminiProvider {
+    client: HttpClient,
+    base_url: String,
+    api_key: String,
+    model: String,
+}
+
+impl GeminiProvider {
+    pub fn new(api_key: String, model: String, settings: ClientSettings) -> Self {
+        let base_url = crate::config::GeminiConfig::default().url;
+        GeminiProvider { client: HttpClient::new(settings), base_url, api_key, model }
+    }
+
+    // The API base, e.g. a proxy or a local mock
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    fn content(&self, text: &str) -> Value {
+        json!({
+            "model": format!("models/{}", self.model),
+            "content": {
+                "parts": [{
+                    "text": text
+                }]
+            }
+        })
+    }
+
+    async fn call(&self, input_text: &str) -> anyhow::Result<Vec<f32>> {
+        let url = format!("{}/models/{}:embedContent", self.base_url, self.model);
+        let json_resp = self.client
+            .post_json(&url, &[("x-goog-api-key", &self.api_key)], &self.content(input_text))
+            .await?;
+        values(&json_resp["embedding"])
+    }
+
+    async fn call_batch(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> {
+        let url = format!("{}/models/{}:batchEmbedContents", self.base_url, self.model);
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for chunk in texts.chunks(self.batch_size()) {
+            let requests: Vec<Value> = chunk.iter().map(|text| self.content(text)).collect();
+            let json_resp = self.client
+                .post_json(&url, &[("x-goog-api-key", &self.api_key)], &json!({ "requests": requests }))
+                .await?;
+            let returned = json_resp["embeddings"]
+                .as_array()
+                .ok_or_else(|| anyhow::anyhow!("Invalid batch embedding response from Gemini"))?;
+            if returned.len() != chunk.len() {
+                anyhow::bail!("Gemini returned {} embeddings for {} texts", returned.len(), chunk.len());
+            }
+            for embedding in returned {
+                embeddings.push(values(embedding)?);
+            }
+        }
+        Ok(embeddings)
+    }
+}
+
+// { "values": [...] }
+fn values(embedding: &Value) -> anyhow::Result<Vec<f32>> {
+    let embedding_array = embedding["values"]
+        .as_array()
+        .ok_or_else(|| anyhow::anyhow!("Invalid embedding response from Gemini"))?;
+
+    Ok(embedding_array
+        .iter()
+        .map(|v| v.as_f64().unwrap_or(0.0) as f32)
+        .collect())
+}
+
+impl EmbeddingProvider for GeminiProvider {
+    fn name(&self) -> &'static str {
+        "gemini"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn embed<'a>(&'a self, text: &'a str) -> BoxFuture<'a, anyhow::Result<Vec<f32>>> {
+        Box::pin(self.call(text))
+    }
+
+    fn batch_size(&self) -> usize {
+        self.client.batch_size().clamp(1, MAX_BATCH)
+    }
+
+    fn embed_batch<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, anyhow::Result<Vec<Vec<f32>>>> {
+        Box::pin(self.call_batch(texts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embedding::client::mock;
+    use std::time::Duration;
+
+    #[actix_web::test]
+    async fn test_batches_against_a_local_server_retrying_a_429() {
+        let server = mock::serve(vec![
+            (429, json!({ "error": { "status": "RESOURCE_EXHAUSTED" } })),
+            (200, json!({ "embeddings": [{ "values": [0.1, 0.2] }, { "values": [0.3, 0.4] }] })),
+            (200, json!({ "embeddings": [{ "values": [0.5, 0.6] }] })),
+        ]);
+        let settings = ClientSettings { batch_size: 2, base_delay: Duration::from_millis(1), ..ClientSettings::default() };
+        let provider = GeminiProvider::new("key".to_string(), "text-embedding-004".to_string(), settings)
+            .with_base_url(&server.url);
+
+        let texts: Vec<String> = ["fever", "cough", "jvara"].iter().map(|t| t.to_string()).collect();
+        let embeddings = provider.embed_batch(&texts).await.unwrap();
+        assert_eq!(embeddings, vec![vec![0.1, 0.2], vec![0.3, 0.4], vec![0.5, 0.6]]);
+
+        // The throttled batch was sent again, then the rest in a second request
+        let requests = server.requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0], requests[1]);
+        assert_eq!(requests[1]["requests"].as_array().unwrap().len(), 2);
+        assert_eq!(requests[2]["requests"][0]["content"]["parts"][0]["text"], "jvara");
+        assert_eq!(requests[2]["requests"][0]["model"], "models/text-embedding-004");
+    }
+}
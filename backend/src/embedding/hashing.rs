@@ -0,0 +1,94 @@
+// Offline embedder: signed feature hashing of words and character trigrams into a fixed
+// number of buckets. Deterministic across runs and machines, so stored vectors stay valid;
+// it captures spelling overlap rather than meaning
+use futures::future::BoxFuture;
+
+use super::EmbeddingProvider;
+
+const WORD_WEIGHT: f32 = 1.0;
+const TRIGRAM_WEIGHT: f32 = 0.5;
+
+pub struct HashingProvider {
+    dimension: usize,
+    model: String,
+}
+
+// FNV-1a; std's hasher is not guaranteed stable between releases
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+impl HashingProvider {
+    pub fn new(dimension: usize) -> Self {
+        HashingProvider { dimension, model: format!("hashing-{}", dimension) }
+    }
+
+    fn add(&self, vector: &mut [f32], feature: &str, weight: f32) {
+        let hash = fnv1a(feature.as_bytes());
+        let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+        vector[(hash % self.dimension as u64) as usize] += sign * weight;
+    }
+
+    pub fn embed_text(&self, text: &str) -> anyhow::Result<Vec<f32>> {
+        let mut vector = vec![0.0; self.dimension];
+        let lowered = text.to_lowercase();
+        let words = lowered
+            .split(|c: char| c.is_whitespace() || c.is_ascii_punctuation())
+            .filter(|word| !word.is_empty());
+        for word in words {
+            self.add(&mut vector, word, WORD_WEIGHT);
+            let padded: Vec<char> = format!("#{}#", word).chars().collect();
+            for trigram in padded.windows(3) {
+                self.add(&mut vector, &trigram.iter().collect::<String>(), TRIGRAM_WEIGHT);
+            }
+        }
+
+        let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm == 0.0 {
+            return Err(anyhow::anyhow!("No words to embed"));
+        }
+        vector.iter_mut().for_each(|x| *x /= norm);
+        Ok(vector)
+    }
+}
+
+impl EmbeddingProvider for HashingProvider {
+    fn name(&self) -> &'static str {
+        "hashing"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn embed<'a>(&'a self, text: &'a str) -> BoxFuture<'a, anyhow::Result<Vec<f32>>> {
+        Box::pin(async move { self.embed_text(text) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cosine(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(x, y)| x * y).sum()
+    }
+
+    #[test]
+    fn test_hashing_embeddings_are_stable_and_reflect_overlap() {
+        let provider = HashingProvider::new(256);
+        let fever = provider.embed_text("Jvara (fever)").unwrap();
+        assert_eq!(fever.len(), 256);
+        assert_eq!(fever, provider.embed_text("jvara fever").unwrap());
+        assert!((cosine(&fever, &fever) - 1.0).abs() < 1e-5);
+
+        let related = provider.embed_text("Vataja jvara").unwrap();
+        let unrelated = provider.embed_text("Diabetes mellitus type 2").unwrap();
+        assert!(cosine(&fever, &related) > cosine(&fever, &unrelated));
+
+        // Devanagari words keep their vowel signs
+        let devanagari = provider.embed_text("ज्वर").unwrap();
+        assert!(cosine(&devanagari, &provider.embed_text("ज्वरः").unwrap()) > 0.3);
+        assert!(provider.embed_text("  ,. ").is_err());
+    }
+}
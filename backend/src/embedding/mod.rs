@@ -0,0 +1,194 @@
+// Embedding providers behind one trait: Gemini, any OpenAI-compatible /embeddings endpoint
+// (a local server works), and an offline hashing embedder for air-gapped deployments
+pub mod client;
+pub mod gemini;
+pub mod hashing;
+pub mod openai;
+pub mod stored;
+pub mod template;
+
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use futures::future::BoxFuture;
+
+use client::ClientSettings;
+use crate::config;
+
+pub const DEFAULT_GEMINI_MODEL: &str = "text-embedding-004";
+pub const DEFAULT_HASHING_DIMENSION: usize = 384;
+
+pub trait EmbeddingProvider: Send + Sync {
+    // "gemini", "openai" or "hashing"
+    fn name(&self) -> &'static str;
+    fn model(&self) -> &str;
+    fn embed<'a>(&'a self, text: &'a str) -> BoxFuture<'a, anyhow::Result<Vec<f32>>>;
+
+    // Texts worth handing to embed_batch at once
+    fn batch_size(&self) -> usize {
+        1
+    }
+
+    // One embedding per text, in order. Providers with a batch endpoint send many texts
+    // per request
+    fn embed_batch<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, anyhow::Result<Vec<Vec<f32>>>> {
+        Box::pin(async move {
+            let mut embeddings = Vec::with_capacity(texts.len());
+            for text in texts {
+                embeddings.push(self.embed(text).await?);
+            }
+            Ok(embeddings)
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EmbeddingConfig {
+    Gemini { api_key: String, model: String, client: ClientSettings },
+    OpenAi { url: String, model: String, api_key: Option<String>, client: ClientSettings },
+    Hashing { dimension: usize },
+}
+
+impl EmbeddingConfig {
+    // EMBEDDING_PROVIDER picks the provider; without it GEMINI_KEY selects Gemini.
+    // None when nothing is configured, which leaves semantic search unavailable. Read as
+    // part of the application configuration
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Option<Self>, String> {
+        let provider = match var("EMBEDDING_PROVIDER") {
+            Some(provider) => provider.to_lowercase(),
+            None if var("GEMINI_KEY").is_some() => "gemini".to_string(),
+            None => return Ok(None),
+        };
+        // Batching, rate limit and retries of the remote providers
+        let client = ClientSettings {
+            batch_size: parse_var(&var, "EMBEDDING_BATCH_SIZE", |size: &usize| *size > 0)?.unwrap_or(client::DEFAULT_BATCH_SIZE),
+            rate_limit: parse_var(&var, "EMBEDDING_RATE_LIMIT", |rate: &f64| *rate > 0.0)?,
+            max_retries: parse_var(&var, "EMBEDDING_MAX_RETRIES", |_: &u32| true)?.unwrap_or(client::DEFAULT_MAX_RETRIES),
+            timeout: seconds_var(&var, "EMBEDDING_TIMEOUT")?.unwrap_or(client::DEFAULT_TIMEOUT),
+            connect_timeout: seconds_var(&var, "EMBEDDING_CONNECT_TIMEOUT")?.unwrap_or(client::DEFAULT_CONNECT_TIMEOUT),
+            ..ClientSettings::default()
+        };
+        let config = match provider.as_str() {
+            "gemini" => EmbeddingConfig::Gemini {
+                api_key: var("GEMINI_KEY").ok_or("GEMINI_KEY must be set for the gemini embedding provider")?,
+                model: var("EMBEDDING_MODEL").unwrap_or_else(|| DEFAULT_GEMINI_MODEL.to_string()),
+                client,
+            },
+            "openai" => EmbeddingConfig::OpenAi {
+                url: var("EMBEDDING_URL").ok_or("EMBEDDING_URL must be set for the openai embedding provider")?,
+                model: var("EMBEDDING_MODEL").ok_or("EMBEDDING_MODEL must be set for the openai embedding provider")?,
+                api_key: var("EMBEDDING_API_KEY"),
+                client,
+            },
+            "hashing" => EmbeddingConfig::Hashing {
+                dimension: match var("EMBEDDING_DIMENSION") {
+                    Some(value) => value
+                        .parse()
+                        .ok()
+                        .filter(|dimension| *dimension > 0)
+                        .ok_or_else(|| format!("EMBEDDING_DIMENSION must be a positive integer, got {}", value))?,
+                    None => DEFAULT_HASHING_DIMENSION,
+                },
+            },
+            other => return Err(format!("unknown EMBEDDING_PROVIDER {} (expected gemini, openai or hashing)", other)),
+        };
+        Ok(Some(config))
+    }
+
+    pub fn build(self) -> Arc<dyn EmbeddingProvider> {
+        match self {
+            EmbeddingConfig::Gemini { api_key, model, client } => {
+                Arc::new(gemini::GeminiProvider::new(api_key, model, client).with_base_url(&config::get().gemini.url))
+            },
+            EmbeddingConfig::OpenAi { url, model, api_key, client } => Arc::new(openai::OpenAiProvider::new(url, model, api_key, client)),
+            EmbeddingConfig::Hashing { dimension } => Arc::new(hashing::HashingProvider::new(dimension)),
+        }
+    }
+}
+
+// An optional variable that must parse and pass `valid` when set
+fn parse_var<T: std::str::FromStr>(
+    var: &impl Fn(&str) -> Option<String>,
+    name: &str,
+    valid: impl Fn(&T) -> bool,
+) -> Result<Option<T>, String> {
+    match var(name) {
+        Some(value) => value
+            .parse()
+            .ok()
+            .filter(|parsed| valid(parsed))
+            .map(Some)
+            .ok_or_else(|| format!("{} has an invalid value: {}", name, value)),
+        None => Ok(None),
+    }
+}
+
+// A positive number of seconds, fractions allowed
+fn seconds_var(var: &impl Fn(&str) -> Option<String>, name: &str) -> Result<Option<Duration>, String> {
+    Ok(parse_var(var, name, |seconds: &f64| seconds.is_finite() && *seconds > 0.0)?.map(Duration::from_secs_f64))
+}
+
+static PROVIDER: OnceLock<Option<Arc<dyn EmbeddingProvider>>> = OnceLock::new();
+
+// The configured provider, built on first use
+pub fn provider() -> Option<Arc<dyn EmbeddingProvider>> {
+    PROVIDER
+        .get_or_init(|| config::get().embedding.clone().map(EmbeddingConfig::build))
+        .clone()
+}
+
+// Key under which the configured provider's vectors are stored, None without a provider
+pub fn active_model_key() -> Option<String> {
+    provider().map(|provider| stored::key_of(provider.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config(vars: &[(&str, &str)]) -> Result<Option<EmbeddingConfig>, String> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        EmbeddingConfig::from_vars(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_provider_selection_from_env() {
+        assert_eq!(config(&[]), Ok(None));
+        assert_eq!(
+            config(&[("GEMINI_KEY", "k")]),
+            Ok(Some(EmbeddingConfig::Gemini {
+                api_key: "k".to_string(),
+                model: DEFAULT_GEMINI_MODEL.to_string(),
+                client: ClientSettings::default(),
+            }))
+        );
+        assert_eq!(
+            config(&[("GEMINI_KEY", "k"), ("EMBEDDING_PROVIDER", "Hashing")]),
+            Ok(Some(EmbeddingConfig::Hashing { dimension: DEFAULT_HASHING_DIMENSION }))
+        );
+        assert_eq!(
+            config(&[("EMBEDDING_PROVIDER", "openai"), ("EMBEDDING_URL", "http://localhost:11434/v1"), ("EMBEDDING_MODEL", "nomic-embed-text")]),
+            Ok(Some(EmbeddingConfig::OpenAi {
+                url: "http://localhost:11434/v1".to_string(),
+                model: "nomic-embed-text".to_string(),
+                api_key: None,
+                client: ClientSettings::default(),
+            }))
+        );
+        assert_eq!(
+            config(&[("GEMINI_KEY", "k"), ("EMBEDDING_BATCH_SIZE", "50"), ("EMBEDDING_RATE_LIMIT", "2.5"), ("EMBEDDING_TIMEOUT", "1.5")]),
+            Ok(Some(EmbeddingConfig::Gemini {
+                api_key: "k".to_string(),
+                model: DEFAULT_GEMINI_MODEL.to_string(),
+                client: ClientSettings { batch_size: 50, rate_limit: Some(2.5), timeout: Duration::from_millis(1500), ..ClientSettings::default() },
+            }))
+        );
+        assert!(config(&[("GEMINI_KEY", "k"), ("EMBEDDING_BATCH_SIZE", "0")]).is_err());
+        assert!(config(&[("GEMINI_KEY", "k"), ("EMBEDDING_RATE_LIMIT", "fast")]).is_err());
+        assert!(config(&[("GEMINI_KEY", "k"), ("EMBEDDING_CONNECT_TIMEOUT", "0")]).is_err());
+        assert!(config(&[("EMBEDDING_PROVIDER", "openai"), ("EMBEDDING_MODEL", "m")]).is_err());
+        assert!(config(&[("EMBEDDING_PROVIDER", "gemini")]).is_err());
+        assert!(config(&[("EMBEDDING_PROVIDER", "hashing"), ("EMBEDDING_DIMENSION", "0")]).is_err());
+        assert!(config(&[("EMBEDDING_PROVIDER", "word2vec")]).is_err());
+    }
+}
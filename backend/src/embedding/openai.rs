@@ -0,0 +1,95 @@
+// OpenAI-compatible POST {url}/embeddings, as served by OpenAI, Ollama, llama.cpp, vLLM or TEI
+use futures::future::BoxFuture;
+
+use super::client::{ClientSettings, HttpClient};
+use super::EmbeddingProvider;
+
+pub struct OpenAiProvider {
+    client: HttpClient,
+    url: String,
+    model: String,
+    api_key: Option<String>,
+}
+
+impl OpenAiProvider {
+    // `url` is the API base, e.g. http://localhost:11434/v1
+    pub fn new(url: String, model: String, api_key: Option<String>, settings: ClientSettings) -> Self {
+        let url = format!("{}/embeddings", url.trim_end_matches('/'));
+        OpenAiProvider { client: HttpClient::new(settings), url, model, api_key }
+    }
+
+    // One request per batch_size texts, each with an array input
+    async fn call(&self, texts: &[String]) -> anyhow::Result<Vec<Vec<f32>>> {
+        let authorization = self.api_key.as_ref().map(|api_key| format!("Bearer {}", api_key));
+        let headers: Vec<(&str, &str)> = authorization.iter().map(|value| ("Authorization", value.as_str())).collect();
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for chunk in texts.chunks(self.batch_size()) {
+            let body = serde_json::json!({ "model": self.model, "input": chunk });
+            let json_resp = self.client.post_json(&self.url, &headers, &body).await?;
+            embeddings.extend(parse_embeddings(&json_resp, chunk.len())?);
+        }
+        Ok(embeddings)
+    }
+}
+
+// { "data": [ { "index": 0, "embedding": [...] }, ... ] }, put back in input order
+fn parse_embeddings(response: &serde_json::Value, expected: usize) -> anyhow::Result<Vec<Vec<f32>>> {
+    let data = response["data"]
+        .as_array()
+        .ok_or_else(|| anyhow::anyhow!("Invalid embedding response from OpenAI-compatible endpoint"))?;
+    if data.len() != expected {
+        anyhow::bail!("OpenAI-compatible endpoint returned {} embeddings for {} texts", data.len(), expected);
+    }
+    let mut indexed = Vec::with_capacity(data.len());
+    for (position, item) in data.iter().enumerate() {
+        let values = item["embedding"]
+            .as_array()
+            .ok_or_else(|| anyhow::anyhow!("Invalid embedding response from OpenAI-compatible endpoint"))?;
+        let index = item["index"].as_u64().map(|i| i as usize).unwrap_or(position);
+        indexed.push((index, values.iter().map(|v| v.as_f64().unwrap_or(0.0) as f32).collect()));
+    }
+    indexed.sort_by_key(|(index, _)| *index);
+    Ok(indexed.into_iter().map(|(_, embedding)| embedding).collect())
+}
+
+impl EmbeddingProvider for OpenAiProvider {
+    fn name(&self) -> &'static str {
+        "openai"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn embed<'a>(&'a self, text: &'a str) -> BoxFuture<'a, anyhow::Result<Vec<f32>>> {
+        Box::pin(async move {
+            let mut embeddings = self.call(&[text.to_string()]).await?;
+            Ok(embeddings.remove(0))
+        })
+    }
+
+    fn batch_size(&self) -> usize {
+        self.client.batch_size().max(1)
+    }
+
+    fn embed_batch<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, anyhow::Result<Vec<Vec<f32>>>> {
+        Box::pin(self.call(texts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_embedding_response() {
+        let response = serde_json::json!({ "object": "list", "data": [{ "index": 0, "embedding": [0.5, -1.0] }] });
+        assert_eq!(parse_embeddings(&response, 1).unwrap(), vec![vec![0.5, -1.0]]);
+        assert!(parse_embeddings(&serde_json::json!({ "error": "model not found" }), 1).is_err());
+
+        // Batched answers may come back in any order
+        let response = serde_json::json!({ "data": [{ "index": 1, "embedding": [2.0] }, { "index": 0, "embedding": [1.0] }] });
+        assert_eq!(parse_embeddings(&response, 2).unwrap(), vec![vec![1.0], vec![2.0]]);
+        assert!(parse_embeddings(&response, 3).is_err());
+    }
+}
@@ -0,0 +1,120 @@
+// Embeddings as stored on concept documents: one entry per provider and model under
+// "embeddings", each with the metadata needed to tell whether it is still current
+//
+//   embeddings: { "gemini_text-embedding-004": { vector, provider, model, dimension, text_hash, created_at } }
+//
+// A model that failed to embed a concept leaves error: { message, at, attempts } in its entry
+// instead, next to the vector of an earlier text if there is one
+use mongodb::bson::{self, Document};
+use serde::{Deserialize, Serialize};
+
+use super::hashing::fnv1a;
+use super::EmbeddingProvider;
+
+pub const FIELD: &str = "embeddings";
+// The failure of a model's last attempt, within its entry
+pub const ERROR: &str = "error";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredEmbedding {
+    pub vector: Vec<f32>,
+    pub provider: String,
+    pub model: String,
+    pub dimension: usize,
+    // Hash of the text the vector was computed from
+    pub text_hash: String,
+    pub created_at: String,
+}
+
+impl StoredEmbedding {
+    pub fn new(provider: &dyn EmbeddingProvider, text: &str, vector: Vec<f32>) -> Self {
+        StoredEmbedding {
+            dimension: vector.len(),
+            vector,
+            provider: provider.name().to_string(),
+            model: provider.model().to_string(),
+            text_hash: text_hash(text),
+            created_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+// Key of a provider's model under "embeddings"; field names cannot hold dots
+pub fn model_key(provider: &str, model: &str) -> String {
+    format!("{}_{}", provider, model)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+pub fn key_of(provider: &dyn EmbeddingProvider) -> String {
+    model_key(provider.name(), provider.model())
+}
+
+// Dotted path of a model's entry, e.g. "embeddings.gemini_text-embedding-004"
+pub fn path(model_key: &str) -> String {
+    format!("{}.{}", FIELD, model_key)
+}
+
+pub fn text_hash(text: &str) -> String {
+    format!("{:016x}", fnv1a(text.as_bytes()))
+}
+
+// A model's entry on a document, when it has a usable vector
+pub fn of(document: &Document, model_key: &str) -> Option<StoredEmbedding> {
+    let entry = document.get_document(FIELD).ok()?.get_document(model_key).ok()?;
+    let stored: StoredEmbedding = bson::from_document(entry.clone()).ok()?;
+    (!stored.vector.is_empty() && stored.vector.len() == stored.dimension).then_some(stored)
+}
+
+// Hash of the text behind a model's entry, without reading the vector
+pub fn text_hash_of<'a>(document: &'a Document, model_key: &str) -> Option<&'a str> {
+    document.get_document(FIELD).ok()?.get_document(model_key).ok()?.get_str("text_hash").ok()
+}
+
+// The model's last attempt to embed the concept failed
+pub fn failed(document: &Document, model_key: &str) -> bool {
+    document
+        .get_document(FIELD)
+        .and_then(|entries| entries.get_document(model_key))
+        .is_ok_and(|entry| entry.contains_key(ERROR))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embedding::hashing::HashingProvider;
+    use mongodb::bson::doc;
+
+    #[test]
+    fn test_entries_are_kept_per_model_with_their_text_hash() {
+        assert_eq!(model_key("openai", "nomic-embed-text:v1.5"), "openai_nomic-embed-text_v1_5");
+
+        let provider = HashingProvider::new(4);
+        let key = key_of(&provider);
+        let stored = StoredEmbedding::new(&provider, "fever", vec![0.1, 0.2, 0.3, 0.4]);
+        assert_eq!(stored.text_hash, text_hash("fever"));
+        assert_ne!(stored.text_hash, text_hash("fever "));
+
+        let document = doc! {
+            FIELD: {
+                &key: bson::to_bson(&stored).unwrap(),
+                "gemini_text-embedding-004": { "vector": [1.0], "dimension": 768 },
+            },
+            "embedding": [0.5],
+        };
+        assert_eq!(text_hash_of(&document, &key), Some(stored.text_hash.as_str()));
+        assert_eq!(of(&document, &key), Some(stored));
+        // Another model's entry, a malformed one and the legacy bare array are not this model's
+        assert_eq!(of(&document, "gemini_text-embedding-004"), None);
+        assert_eq!(of(&document, "openai_other"), None);
+        assert_eq!(of(&doc! { "embedding": [0.5] }, &key), None);
+
+        // Failures are kept per model
+        let failing = doc! { FIELD: { "gemini_text-embedding-004": { ERROR: { "message": "timed out", "attempts": 1 } } } };
+        assert!(failed(&failing, "gemini_text-embedding-004"));
+        assert!(!failed(&failing, &key));
+        assert!(!failed(&document, &key));
+        assert_eq!(of(&failing, "gemini_text-embedding-004"), None);
+    }
+}
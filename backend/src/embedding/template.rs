@@ -0,0 +1,208 @@
+// The text sent to the embedder for a concept, from a template per code system such as
+// "{title} | {definition} | {parent_titles} | {synonyms}". Segments between "|" whose
+// fields are all empty are left out. Templates are read from the configured file
+// (EMBEDDING_TEMPLATES_FILE, default embedding_templates.json); systems the file does not
+// list use the defaults
+use std::collections::HashMap;
+
+use mongodb::bson::Document;
+use serde::{Deserialize, Serialize};
+
+use crate::codecs::icd::IcdCode;
+
+pub const DEFAULT_TEMPLATES_FILE: &str = "embedding_templates.json";
+
+pub const ICD_FIELDS: [&str; 8] = [
+    "code", "title", "definition", "parent_titles", "synonyms", "inclusions", "exclusions", "coding_note",
+];
+pub const NAMASTE_FIELDS: [&str; 7] = [
+    "code", "term", "term_diacritical", "term_devanagari", "short_definition", "long_definition", "ontology_branches",
+];
+
+// Ancestors followed at most, in case the parent links loop
+const MAX_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Templates {
+    pub icd: String,
+    pub namaste: String,
+}
+
+impl Default for Templates {
+    fn default() -> Self {
+        Templates {
+            icd: "{title} | {definition} | {parent_titles} | {synonyms} | {inclusions} | {code}".to_string(),
+            namaste: "{term} | {term_diacritical} | {term_devanagari} | {short_definition} | {long_definition} | {ontology_branches} | {code}".to_string(),
+        }
+    }
+}
+
+impl Templates {
+    // Read the templates file; a missing file means the defaults
+    pub fn load() -> Result<Self, String> {
+        let path = &crate::config::get().files.embedding_templates;
+        match std::fs::read_to_string(path) {
+            Ok(json) => Self::parse(&json).map_err(|e| format!("{}: {}", path, e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Templates::default()),
+            Err(e) => Err(format!("{} could not be read: {}", path, e)),
+        }
+    }
+
+    // Templates may only name the fields their code system has
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let templates: Templates = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        for (system, template, known) in [("icd", &templates.icd, &ICD_FIELDS[..]), ("namaste", &templates.namaste, &NAMASTE_FIELDS[..])] {
+            if let Some(unknown) = placeholders(template).into_iter().find(|name| !known.contains(name)) {
+                return Err(format!("unknown field {{{}}} in the {} template (expected one of {})", unknown, system, known.join(", ")));
+            }
+        }
+        Ok(templates)
+    }
+}
+
+fn placeholders(template: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else { break };
+        names.push(&rest[start + 1..start + end]);
+        rest = &rest[start + end + 1..];
+    }
+    names
+}
+
+// Fill a template. A segment with fields is kept when at least one of them has a value
+pub fn render(template: &str, fields: &HashMap<&str, String>) -> String {
+    let segments: Vec<String> = template
+        .split('|')
+        .filter_map(|segment| {
+            let mut text = String::new();
+            let (mut named, mut filled) = (0, 0);
+            let mut rest = segment;
+            while let Some(start) = rest.find('{') {
+                let Some(end) = rest[start..].find('}') else { break };
+                text.push_str(&rest[..start]);
+                let value = fields.get(&rest[start + 1..start + end]).map(|v| v.trim()).unwrap_or_default();
+                named += 1;
+                if !value.is_empty() {
+                    filled += 1;
+                    text.push_str(value);
+                }
+                rest = &rest[start + end + 1..];
+            }
+            text.push_str(rest);
+            let text = text.trim();
+            (!text.is_empty() && (named == 0 || filled > 0)).then(|| text.to_string())
+        })
+        .collect();
+    segments.join(" | ")
+}
+
+// Titles from the top of the hierarchy down to the code's parent
+pub fn parent_titles(code: &IcdCode, by_id: &HashMap<&str, &IcdCode>) -> Vec<String> {
+    let mut titles = Vec::new();
+    let mut current = code.parent.as_deref();
+    while let Some(parent) = current.and_then(|id| by_id.get(id)) {
+        if titles.len() == MAX_DEPTH {
+            break;
+        }
+        titles.push(parent.title.clone());
+        current = parent.parent.as_deref();
+    }
+    titles.reverse();
+    titles
+}
+
+// Lists in the ICD export are separated by ";"
+fn list(value: &Option<String>) -> String {
+    value
+        .as_deref()
+        .unwrap_or_default()
+        .split(';')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub fn icd_fields(code: &IcdCode, parent_titles: &[String]) -> HashMap<&'static str, String> {
+    HashMap::from([
+        ("code", code.code.clone()),
+        ("title", code.title.clone()),
+        ("definition", code.definition.clone().unwrap_or_default()),
+        ("parent_titles", parent_titles.join(" > ")),
+        ("synonyms", list(&code.synonyms)),
+        ("inclusions", list(&code.inclusions)),
+        ("exclusions", list(&code.exclusions)),
+        ("coding_note", code.coding_note.clone().unwrap_or_default()),
+    ])
+}
+
+// From the stored document (see NamasteCode)
+pub fn namaste_fields(document: &Document) -> HashMap<&'static str, String> {
+    let text = |column: &str| match document.get(column) {
+        Some(mongodb::bson::Bson::String(value)) => value.clone(),
+        Some(mongodb::bson::Bson::Null) | None => String::new(),
+        Some(other) => other.to_string(),
+    };
+    HashMap::from([
+        ("code", text("namc_code")),
+        ("term", text("namc_term")),
+        ("term_diacritical", text("namc_term_diacritical")),
+        ("term_devanagari", text("namc_term_devanagari")),
+        ("short_definition", text("short_definition")),
+        ("long_definition", text("long_definition")),
+        ("ontology_branches", text("ontology_branches")),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::doc;
+
+    fn icd(id: &str, title: &str, parent: Option<&str>) -> IcdCode {
+        IcdCode {
+            id: id.to_string(),
+            code: String::new(),
+            title: title.to_string(),
+            definition: None,
+            parent: parent.map(str::to_string),
+            browser_url: None,
+            coding_note: None,
+            synonyms: None,
+            exclusions: None,
+            inclusions: None,
+            is_leaf: None,
+        }
+    }
+
+    #[test]
+    fn test_templates_fill_fields_and_drop_empty_segments() {
+        let chapter = icd("1", "Certain infectious or parasitic diseases", None);
+        let block = icd("2", "Intestinal infectious diseases", Some("1"));
+        let mut cholera = icd("3", "Cholera", Some("2"));
+        cholera.code = "1A00".to_string();
+        cholera.synonyms = Some("Asiatic cholera; ;epidemic cholera".to_string());
+        let by_id: HashMap<&str, &IcdCode> = [&chapter, &block, &cholera].into_iter().map(|c| (c.id.as_str(), c)).collect();
+
+        let parents = parent_titles(&cholera, &by_id);
+        assert_eq!(parents, vec!["Certain infectious or parasitic diseases", "Intestinal infectious diseases"]);
+        let text = render(&Templates::default().icd, &icd_fields(&cholera, &parents));
+        assert_eq!(
+            text,
+            "Cholera | Certain infectious or parasitic diseases > Intestinal infectious diseases | Asiatic cholera, epidemic cholera | 1A00"
+        );
+        assert_eq!(render("Code {code} | Defined as: {definition} | ICD-11", &icd_fields(&cholera, &[])), "Code 1A00 | ICD-11");
+
+        // A Sanskrit-named concept carries its English definitions into the text
+        let jvara = doc! { "namc_code": "EC-3", "namc_term": "jvaraH", "short_definition": "Fever", "long_definition": null };
+        assert_eq!(render(&Templates::default().namaste, &namaste_fields(&jvara)), "jvaraH | Fever | EC-3");
+
+        assert!(Templates::parse(r#"{"icd": "{title} | {parents}"}"#).is_err());
+        let custom = Templates::parse(r#"{"namaste": "{term}: {short_definition}"}"#).unwrap();
+        assert_eq!(custom.icd, Templates::default().icd);
+        assert_eq!(Templates::parse(include_str!("../../embedding_templates.json")), Ok(Templates::default()));
+    }
+}
@@ -0,0 +1,84 @@
+use serde_json::{json, Value};
+
+use crate::codecs::audit::AuditRecord;
+use super::PUBLISHER;
+
+pub fn audit_event(record: &AuditRecord) -> Value {
+    let who = match &record.actor {
+        Some(actor) => json!({ "identifier": { "value": actor } }),
+        None => json!({ "display": "anonymous" }),
+    };
+    let mut agent = json!({ "who": who, "requestor": true });
+    if let Some(address) = &record.client_address {
+        agent["network"] = json!({ "address": address, "type": "2" });
+    }
+
+    let query_details: Vec<Value> = record
+        .query
+        .iter()
+        .map(|(name, value)| json!({ "type": name, "valueString": value }))
+        .collect();
+    let code_details: Vec<Value> = record
+        .codes
+        .iter()
+        .map(|code| json!({ "type": "code", "valueString": code }))
+        .collect();
+
+    json!({
+        "resourceType": "AuditEvent",
+        "id": record.event_id,
+        "type": {
+            "system": "http://terminology.hl7.org/CodeSystem/audit-event-type",
+            "code": "rest",
+            "display": "RESTful Operation"
+        },
+        "subtype": [{ "system": "http://hl7.org/fhir/restful-interaction", "code": record.interaction }],
+        "action": record.action,
+        "recorded": record.recorded.try_to_rfc3339_string().unwrap_or_default(),
+        "outcome": record.outcome,
+        "outcomeDesc": format!("HTTP {} in {} ms", record.status, record.latency_ms),
+        "agent": [agent],
+        "source": {
+            "observer": { "display": PUBLISHER },
+            "type": [{
+                "system": "http://terminology.hl7.org/CodeSystem/security-source-type",
+                "code": "4",
+                "display": "Application Server"
+            }]
+        },
+        "entity": [
+            {
+                "name": format!("{} {}", record.method, record.path),
+                "description": "Request",
+                "detail": query_details
+            },
+            {
+                "name": "codes",
+                "description": match record.codes_total {
+                    Some(total) => format!("{} codes returned", total),
+                    None => "Codes not recorded (response too large)".to_string(),
+                },
+                "detail": code_details
+            }
+        ]
+    })
+}
+
+// searchset Bundle of AuditEvents
+pub fn audit_bundle(records: &[AuditRecord], total: u64) -> Value {
+    let entries: Vec<Value> = records
+        .iter()
+        .map(|record| json!({
+            "fullUrl": format!("AuditEvent/{}", record.event_id),
+            "resource": audit_event(record),
+            "search": { "mode": "match" }
+        }))
+        .collect();
+    json!({
+        "resourceType": "Bundle",
+        "type": "searchset",
+        "total": total,
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+        "entry": entries
+    })
+}
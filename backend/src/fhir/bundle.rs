@@ -0,0 +1,99 @@
+use serde_json::{json, Value};
+
+// Resource types accepted by POST /fhir. Problem-list items are Conditions
+// with category "problem-list-item"; a List can group them
+pub const INGESTIBLE_TYPES: [&str; 2] = ["Condition", "List"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BundleKind {
+    // All entries are stored or none are
+    Transaction,
+    // Each entry succeeds or fails on its own
+    Batch,
+}
+
+impl BundleKind {
+    pub fn from_type(bundle_type: &str) -> Option<Self> {
+        match bundle_type {
+            "transaction" => Some(BundleKind::Transaction),
+            "batch" => Some(BundleKind::Batch),
+            _ => None,
+        }
+    }
+
+    pub fn response_type(&self) -> &'static str {
+        match self {
+            BundleKind::Transaction => "transaction-response",
+            BundleKind::Batch => "batch-response",
+        }
+    }
+}
+
+pub fn issue(severity: &str, code: &str, diagnostics: &str) -> Value {
+    json!({
+        "severity": severity,
+        "code": code,
+        "diagnostics": diagnostics
+    })
+}
+
+pub fn has_errors(issues: &[Value]) -> bool {
+    issues.iter().any(|issue| matches!(issue["severity"].as_str(), Some("error") | Some("fatal")))
+}
+
+// OperationOutcome for one entry; an entry with nothing to report gets an informational issue
+pub fn entry_outcome(mut issues: Vec<Value>) -> Value {
+    if issues.is_empty() {
+        issues.push(issue("information", "informational", "All codings are valid"));
+    }
+    json!({ "resourceType": "OperationOutcome", "issue": issues })
+}
+
+pub fn entry_response(status: &str, location: Option<&str>, outcome: Value, resource: Option<Value>) -> Value {
+    let mut response = json!({ "status": status, "outcome": outcome });
+    if let Some(location) = location {
+        response["location"] = json!(location);
+    }
+    let mut entry = json!({ "response": response });
+    if let Some(resource) = resource {
+        entry["resource"] = resource;
+    }
+    entry
+}
+
+pub fn response_bundle(kind: BundleKind, entries: Vec<Value>) -> Value {
+    json!({
+        "resourceType": "Bundle",
+        "id": uuid::Uuid::new_v4().to_string(),
+        "type": kind.response_type(),
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+        "entry": entries
+    })
+}
+
+// Whether a coding with this system and code is already present
+pub fn has_coding(codings: &[Value], system: &str, code: &str) -> bool {
+    codings.iter().any(|c| c["system"] == system && c["code"] == code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_outcomes_and_response_bundle() {
+        assert_eq!(BundleKind::from_type("batch"), Some(BundleKind::Batch));
+        assert_eq!(BundleKind::from_type("collection"), None);
+
+        let warning = vec![issue("warning", "code-invalid", "Display does not match")];
+        assert!(!has_errors(&warning));
+        assert!(has_errors(&[issue("error", "code-invalid", "Unknown code")]));
+        assert_eq!(entry_outcome(Vec::new())["issue"][0]["severity"], "information");
+
+        let entry = entry_response("201 Created", Some("Condition/1/_history/1"), entry_outcome(warning), None);
+        let bundle = response_bundle(BundleKind::Transaction, vec![entry]);
+        assert_eq!(bundle["type"], "transaction-response");
+        assert_eq!(bundle["entry"][0]["response"]["location"], "Condition/1/_history/1");
+        assert_eq!(bundle["entry"][0]["response"]["outcome"]["issue"][0]["severity"], "warning");
+    }
+}
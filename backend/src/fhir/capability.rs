@@ -0,0 +1,154 @@
+use std::collections::{BTreeMap, BTreeSet};
+use actix_web::http::Method;
+use serde_json::{json, Value};
+
+use super::{CodeSystemKind, CANONICAL_BASE, FHIR_JSON, PUBLISHER};
+
+// Routes registered under /fhir, recorded as they are added so /metadata describes what is really served
+#[derive(Debug, Clone, Default)]
+pub struct FhirRoutes(pub Vec<(Method, String)>);
+
+// A code system as currently loaded in Mongo
+pub struct CodeSystemSummary {
+    pub kind: CodeSystemKind,
+    pub version: Option<String>,
+    pub count: u64,
+}
+
+#[derive(Default)]
+struct ResourceCapability {
+    interactions: BTreeSet<&'static str>,
+    operations: BTreeSet<String>,
+}
+
+// Group routes by resource type; returns the per-resource capabilities and the whole-system interactions
+fn describe(routes: &FhirRoutes) -> (BTreeMap<String, ResourceCapability>, BTreeSet<&'static str>) {
+    let mut resources: BTreeMap<String, ResourceCapability> = BTreeMap::new();
+    let mut system = BTreeSet::new();
+
+    for (method, path) in &routes.0 {
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        let Some(resource_type) = segments.first().filter(|s| s.starts_with(char::is_uppercase)) else {
+            // POST to the base is the transaction / batch endpoint
+            if segments.is_empty() && method == Method::POST {
+                system.extend(["transaction", "batch"]);
+            }
+            continue;
+        };
+
+        let capability = resources.entry(resource_type.to_string()).or_default();
+        if let Some(operation) = segments.iter().find_map(|s| s.strip_prefix('$')) {
+            capability.operations.insert(operation.to_string());
+        } else if segments.len() == 2 && method == Method::GET {
+            capability.interactions.insert("read");
+        } else if segments.len() == 1 && method == Method::GET {
+            capability.interactions.insert("search-type");
+        } else if segments.len() == 1 && method == Method::POST {
+            capability.interactions.insert("create");
+        }
+    }
+
+    (resources, system)
+}
+
+pub fn capability_statement(routes: &FhirRoutes) -> Value {
+    let (resources, system) = describe(routes);
+
+    let resources: Vec<Value> = resources
+        .into_iter()
+        .map(|(resource_type, capability)| {
+            let interactions: Vec<Value> = capability.interactions.iter().map(|code| json!({ "code": code })).collect();
+            let operations: Vec<Value> = capability
+                .operations
+                .iter()
+                .map(|name| json!({
+                    "name": name,
+                    "definition": format!("http://hl7.org/fhir/OperationDefinition/{}-{}", resource_type, name)
+                }))
+                .collect();
+            json!({ "type": resource_type, "interaction": interactions, "operation": operations })
+        })
+        .collect();
+    let system: Vec<Value> = system.iter().map(|code| json!({ "code": code })).collect();
+
+    json!({
+        "resourceType": "CapabilityStatement",
+        "url": format!("{}/metadata", CANONICAL_BASE),
+        "name": "CodeVedasTerminologyServer",
+        "title": format!("{} capabilities", PUBLISHER),
+        "status": "active",
+        "date": chrono::Utc::now().to_rfc3339(),
+        "publisher": PUBLISHER,
+        "kind": "instance",
+        "software": { "name": PUBLISHER, "version": env!("CARGO_PKG_VERSION") },
+        "implementation": { "description": PUBLISHER },
+        "fhirVersion": "4.0.1",
+        "format": [FHIR_JSON, "json"],
+        "instantiates": ["http://hl7.org/fhir/CapabilityStatement/terminology-server"],
+        "rest": [{
+            "mode": "server",
+            "resource": resources,
+            "interaction": system
+        }]
+    })
+}
+
+pub fn terminology_capabilities(systems: &[CodeSystemSummary]) -> Value {
+    let code_systems: Vec<Value> = systems
+        .iter()
+        .filter(|summary| summary.count > 0)
+        .map(|summary| {
+            let mut version = json!({
+                "isDefault": true,
+                "extension": [{
+                    "url": format!("{}/StructureDefinition/concept-count", CANONICAL_BASE),
+                    "valueInteger": summary.count
+                }]
+            });
+            if let Some(code) = &summary.version {
+                version["code"] = json!(code);
+            }
+            json!({ "uri": summary.kind.url(), "version": [version] })
+        })
+        .collect();
+
+    json!({
+        "resourceType": "TerminologyCapabilities",
+        "url": format!("{}/metadata?mode=terminology", CANONICAL_BASE),
+        "name": "CodeVedasTerminologyCapabilities",
+        "status": "active",
+        "date": chrono::Utc::now().to_rfc3339(),
+        "publisher": PUBLISHER,
+        "kind": "instance",
+        "software": { "name": PUBLISHER, "version": env!("CARGO_PKG_VERSION") },
+        "codeSystem": code_systems,
+        "expansion": { "hierarchical": false, "paging": true, "incomplete": false },
+        "validateCode": { "translations": false },
+        "translation": { "needsMap": false }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capability_statement_from_routes() {
+        let routes = FhirRoutes(vec![
+            (Method::POST, "".to_string()),
+            (Method::GET, "/metadata".to_string()),
+            (Method::GET, "/CodeSystem/$lookup".to_string()),
+            (Method::GET, "/CodeSystem/{id}".to_string()),
+            (Method::GET, "/ValueSet/{id}/$expand".to_string()),
+        ]);
+        let statement = capability_statement(&routes);
+        let rest = &statement["rest"][0];
+
+        assert_eq!(rest["interaction"].as_array().unwrap().len(), 2);
+        assert_eq!(rest["resource"][0]["type"], "CodeSystem");
+        assert_eq!(rest["resource"][0]["interaction"][0]["code"], "read");
+        assert_eq!(rest["resource"][0]["operation"][0]["definition"], "http://hl7.org/fhir/OperationDefinition/CodeSystem-lookup");
+        assert_eq!(rest["resource"][1]["operation"][0]["name"], "expand");
+        assert_eq!(rest["resource"].as_array().unwrap().len(), 2);
+    }
+}
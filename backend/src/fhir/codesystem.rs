@@ -0,0 +1,349 @@
+use std::collections::{HashMap, HashSet};
+use serde_json::{json, Map, Value};
+
+use crate::codecs::icd::{IcdCode, IcdDiscipline};
+use crate::codecs::namaste::{self, NamasteCode};
+use super::{ICD11_BIOMEDICINE_SYSTEM, ICD11_TM2_SYSTEM, NAMASTE_SYSTEM, PUBLISHER};
+
+// Code systems exposed under /fhir/CodeSystem/{id}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodeSystemKind {
+    Namaste,
+    Icd11Tm2,
+    Icd11Biomedicine,
+}
+
+impl CodeSystemKind {
+    pub const ALL: [CodeSystemKind; 3] = [
+        CodeSystemKind::Namaste,
+        CodeSystemKind::Icd11Tm2,
+        CodeSystemKind::Icd11Biomedicine,
+    ];
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.id() == id)
+    }
+
+    // Accepts a canonical URL, optionally suffixed with "|version"
+    pub fn from_url(url: &str) -> Option<Self> {
+        let url = url.split('|').next().unwrap_or(url).trim_end_matches('/');
+        Self::ALL.into_iter().find(|kind| kind.url() == url)
+    }
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            CodeSystemKind::Namaste => "namaste",
+            CodeSystemKind::Icd11Tm2 => "icd11-tm2",
+            CodeSystemKind::Icd11Biomedicine => "icd11-biomedicine",
+        }
+    }
+
+    // ICD discipline backing the code system, None for NAMASTE
+    pub fn icd_discipline(&self) -> Option<IcdDiscipline> {
+        match self {
+            CodeSystemKind::Namaste => None,
+            CodeSystemKind::Icd11Tm2 => Some(IcdDiscipline::TM2),
+            CodeSystemKind::Icd11Biomedicine => Some(IcdDiscipline::Biomedicine),
+        }
+    }
+
+    pub fn url(&self) -> &'static str {
+        match self {
+            CodeSystemKind::Namaste => NAMASTE_SYSTEM,
+            CodeSystemKind::Icd11Tm2 => ICD11_TM2_SYSTEM,
+            CodeSystemKind::Icd11Biomedicine => ICD11_BIOMEDICINE_SYSTEM,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CodeSystemKind::Namaste => "NAMASTE",
+            CodeSystemKind::Icd11Tm2 => "ICD11TM2",
+            CodeSystemKind::Icd11Biomedicine => "ICD11Biomedicine",
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            CodeSystemKind::Namaste => "National AYUSH Morbidity and Standardized Terminologies Electronic (Ayurveda)",
+            CodeSystemKind::Icd11Tm2 => "ICD-11 Traditional Medicine Module 2",
+            CodeSystemKind::Icd11Biomedicine => "ICD-11 Mortality and Morbidity Statistics (Biomedicine)",
+        }
+    }
+}
+
+// A flat concept waiting to be placed in the hierarchy
+struct ConceptNode {
+    code: String,
+    parent: Option<String>,
+    is_leaf: Option<bool>,
+    concept: Map<String, Value>,
+}
+
+// Property definitions shared by every code system we publish
+fn property_definitions() -> Value {
+    json!([
+        {
+            "code": "parent",
+            "uri": "http://hl7.org/fhir/concept-properties#parent",
+            "description": "The concept this concept is a child of",
+            "type": "code"
+        },
+        {
+            "code": "isLeaf",
+            "description": "True when the concept has no children",
+            "type": "boolean"
+        }
+    ])
+}
+
+// Nest flat concepts under their parents and attach parent/isLeaf properties
+fn build_hierarchy(nodes: Vec<ConceptNode>) -> Vec<Value> {
+    let known: HashSet<String> = nodes.iter().map(|n| n.code.clone()).collect();
+    let mut children: HashMap<String, Vec<String>> = HashMap::new();
+    let mut roots = Vec::new();
+    let mut order = Vec::new();
+    let mut by_code = HashMap::new();
+
+    for node in nodes {
+        match &node.parent {
+            Some(parent) if parent != &node.code && known.contains(parent) => {
+                children.entry(parent.clone()).or_default().push(node.code.clone());
+            },
+            _ => roots.push(node.code.clone()),
+        }
+        order.push(node.code.clone());
+        by_code.insert(node.code.clone(), node);
+    }
+
+    fn place(
+        code: &str,
+        by_code: &mut HashMap<String, ConceptNode>,
+        children: &HashMap<String, Vec<String>>,
+    ) -> Option<Value> {
+        let node = by_code.remove(code)?;
+        let child_codes = children.get(code).cloned().unwrap_or_default();
+        let nested: Vec<Value> = child_codes
+            .iter()
+            .filter_map(|child| place(child, by_code, children))
+            .collect();
+
+        let mut concept = node.concept;
+        let mut properties = Vec::new();
+        if let Some(parent) = node.parent.filter(|p| children.get(p).is_some_and(|c| c.contains(&node.code))) {
+            properties.push(json!({ "code": "parent", "valueCode": parent }));
+        }
+        properties.push(json!({ "code": "isLeaf", "valueBoolean": node.is_leaf.unwrap_or(nested.is_empty()) }));
+        concept.insert("property".to_string(), Value::Array(properties));
+        if !nested.is_empty() {
+            concept.insert("concept".to_string(), Value::Array(nested));
+        }
+        Some(Value::Object(concept))
+    }
+
+    let mut result: Vec<Value> = roots
+        .iter()
+        .filter_map(|code| place(code, &mut by_code, &children))
+        .collect();
+
+    // Anything left over sits on a parent cycle; surface it at the top level rather than drop it
+    for code in order {
+        if let Some(concept) = place(&code, &mut by_code, &children) {
+            result.push(concept);
+        }
+    }
+
+    result
+}
+
+fn code_system_shell(kind: CodeSystemKind, version: Option<String>, count: usize, concepts: Vec<Value>) -> Value {
+    let mut resource = json!({
+        "resourceType": "CodeSystem",
+        "id": kind.id(),
+        "url": kind.url(),
+        "name": kind.name(),
+        "title": kind.title(),
+        "status": "active",
+        "experimental": false,
+        "date": chrono::Utc::now().to_rfc3339(),
+        "publisher": PUBLISHER,
+        "caseSensitive": true,
+        "hierarchyMeaning": "is-a",
+        "content": "complete",
+        "count": count,
+        "property": property_definitions(),
+        "concept": concepts
+    });
+    if let Some(version) = version {
+        resource["version"] = Value::String(version);
+    }
+    resource
+}
+
+// Build the NAMASTE CodeSystem, with the IAST and Devanagari spellings as designations
+pub fn namaste_code_system(codes: &[NamasteCode]) -> Value {
+    let mut seen = HashSet::new();
+    let entries: Vec<(String, &NamasteCode)> = codes
+        .iter()
+        .map(|code| (code.parse_codes().0, code))
+        .filter(|(nam_code, _)| !nam_code.is_empty() && seen.insert(nam_code.clone()))
+        .collect();
+
+    let known: HashSet<String> = entries.iter().map(|(c, _)| c.clone()).collect();
+    let nodes: Vec<ConceptNode> = entries
+        .into_iter()
+        .map(|(nam_code, code)| ConceptNode {
+            parent: namaste::parent_code(&nam_code, &known),
+            is_leaf: None,
+            concept: namaste_concept(&nam_code, code),
+            code: nam_code,
+        })
+        .collect();
+
+    let count = nodes.len();
+    code_system_shell(CodeSystemKind::Namaste, None, count, build_hierarchy(nodes))
+}
+
+fn namaste_concept(nam_code: &str, code: &NamasteCode) -> Map<String, Value> {
+    let mut concept = Map::new();
+    concept.insert("code".to_string(), json!(nam_code));
+    concept.insert("display".to_string(), json!(code.namc_term));
+
+    let definition = code.long_definition.as_ref()
+        .or(code.short_definition.as_ref())
+        .filter(|d| !d.trim().is_empty());
+    if let Some(definition) = definition {
+        concept.insert("definition".to_string(), json!(definition));
+    }
+
+    let designations = namaste_designations(code);
+    if !designations.is_empty() {
+        concept.insert("designation".to_string(), Value::Array(designations));
+    }
+
+    concept
+}
+
+// IAST and Devanagari spellings of a NAMASTE term as FHIR designations
+pub fn namaste_designations(code: &NamasteCode) -> Vec<Value> {
+    [
+        ("sa-Latn", "IAST (diacritical)", &code.namc_term_diacritical),
+        ("sa-Deva", "Devanagari", &code.namc_term_devanagari),
+    ]
+    .into_iter()
+    .filter(|(_, _, value)| !value.trim().is_empty())
+    .map(|(language, script, value)| json!({
+        "language": language,
+        "use": {
+            "system": "http://terminology.hl7.org/CodeSystem/designation-usage",
+            "code": "display",
+            "display": script
+        },
+        "value": value
+    }))
+    .collect()
+}
+
+// Build an ICD-11 CodeSystem (TM2 or Biomedicine) from the entities of that discipline
+pub fn icd_code_system(kind: CodeSystemKind, codes: &[IcdCode]) -> Value {
+    let code_by_id: HashMap<&str, String> = codes
+        .iter()
+        .map(|code| (code.id.as_str(), code.concept_code()))
+        .collect();
+
+    let mut seen = HashSet::new();
+    let nodes: Vec<ConceptNode> = codes
+        .iter()
+        .filter(|code| seen.insert(code.concept_code()))
+        .map(|code| ConceptNode {
+            code: code.concept_code(),
+            parent: code.parent.as_deref().and_then(|p| code_by_id.get(p).cloned()),
+            is_leaf: code.is_leaf_flag(),
+            concept: icd_concept(code),
+        })
+        .collect();
+
+    let version = codes.iter().find_map(|code| code.release());
+    let count = nodes.len();
+    code_system_shell(kind, version, count, build_hierarchy(nodes))
+}
+
+fn icd_concept(code: &IcdCode) -> Map<String, Value> {
+    let mut concept = Map::new();
+    concept.insert("code".to_string(), json!(code.concept_code()));
+    concept.insert("display".to_string(), json!(code.title));
+    if let Some(definition) = code.definition.as_ref().filter(|d| !d.trim().is_empty()) {
+        concept.insert("definition".to_string(), json!(definition));
+    }
+    concept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn namaste(code: &str, term: &str) -> NamasteCode {
+        NamasteCode {
+            sr_no: 1,
+            namc_id: 1,
+            namc_code: code.to_string(),
+            namc_term: term.to_string(),
+            namc_term_diacritical: format!("{}-iast", term),
+            namc_term_devanagari: format!("{}-deva", term),
+            short_definition: None,
+            long_definition: None,
+            ontology_branches: None,
+        }
+    }
+
+    #[test]
+    fn test_namaste_hierarchy_and_designations() {
+        let codes = vec![
+            namaste("AA", "vAtavyAdhiH"),
+            namaste("AAA", "doShAvasthA"),
+            namaste("SR11 (AAA-1)", "vAtasa~jcayaH"),
+            namaste("SR10\u{a0} (AAA-2.1)", "vAtaprakopaH"),
+        ];
+        let cs = namaste_code_system(&codes);
+
+        assert_eq!(cs["resourceType"], "CodeSystem");
+        assert_eq!(cs["count"], 4);
+        let root = &cs["concept"][0];
+        assert_eq!(root["code"], "AA");
+        let aaa = &root["concept"][0];
+        assert_eq!(aaa["code"], "AAA");
+        assert_eq!(aaa["concept"][0]["code"], "AAA-1");
+        // AAA-2 is not in the set, so AAA-2.1 hangs off AAA
+        assert_eq!(aaa["concept"][1]["code"], "AAA-2.1");
+        assert_eq!(aaa["concept"][1]["property"][0], json!({ "code": "parent", "valueCode": "AAA" }));
+        assert_eq!(aaa["concept"][1]["property"][1], json!({ "code": "isLeaf", "valueBoolean": true }));
+        assert_eq!(aaa["concept"][1]["designation"][1]["value"], "vAtaprakopaH-deva");
+    }
+
+    #[test]
+    fn test_icd_hierarchy_uses_entity_numbers_for_uncoded_entities() {
+        let entity = |id: &str, code: &str, parent: Option<&str>, leaf: &str| IcdCode {
+            id: format!("http://id.who.int/icd/release/11/2025-01/mms/{}", id),
+            code: code.to_string(),
+            title: format!("title {}", id),
+            definition: None,
+            parent: parent.map(|p| format!("http://id.who.int/icd/release/11/2025-01/mms/{}", p)),
+            browser_url: None,
+            coding_note: None,
+            synonyms: None,
+            exclusions: None,
+            inclusions: None,
+            is_leaf: Some(leaf.to_string()),
+        };
+        let codes = vec![
+            entity("100", "", None, "FALSE"),
+            entity("200", "1A00", Some("100"), "TRUE"),
+        ];
+        let cs = icd_code_system(CodeSystemKind::Icd11Biomedicine, &codes);
+
+        assert_eq!(cs["version"], "2025-01");
+        assert_eq!(cs["concept"][0]["code"], "100");
+        assert_eq!(cs["concept"][0]["concept"][0]["code"], "1A00");
+        assert_eq!(cs["concept"][0]["concept"][0]["property"][0]["valueCode"], "100");
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::BTreeMap;
+use serde_json::{json, Value};
+
+use crate::codecs::mapping::MappingElement;
+use super::{CANONICAL_BASE, PUBLISHER};
+
+pub const CONCEPT_MAP_ID: &str = "namaste-icd11";
+
+pub fn concept_map_url() -> String {
+    format!("{}/ConceptMap/{}", CANONICAL_BASE, CONCEPT_MAP_ID)
+}
+
+// Source code -> (source display, targets) within one group
+type GroupElements<'a> = BTreeMap<&'a str, (Option<&'a str>, Vec<Value>)>;
+
+// ConceptMap resource with one group per source/target system pair
+pub fn concept_map(elements: &[MappingElement]) -> Value {
+    let mut groups: BTreeMap<(&str, &str), GroupElements> = BTreeMap::new();
+
+    for element in elements {
+        let entry = groups
+            .entry((element.source_system.as_str(), element.target_system.as_str()))
+            .or_default()
+            .entry(element.source_code.as_str())
+            .or_insert((element.source_display.as_deref(), Vec::new()));
+
+        let mut target = json!({
+            "code": element.target_code,
+            "equivalence": element.equivalence.as_str()
+        });
+        if let Some(display) = &element.target_display {
+            target["display"] = json!(display);
+        }
+        if let Some(comment) = &element.comment {
+            target["comment"] = json!(comment);
+        }
+        entry.1.push(target);
+    }
+
+    let groups: Vec<Value> = groups
+        .into_iter()
+        .map(|((source, target), concepts)| {
+            let elements: Vec<Value> = concepts
+                .into_iter()
+                .map(|(code, (display, targets))| {
+                    let mut element = json!({ "code": code, "target": targets });
+                    if let Some(display) = display {
+                        element["display"] = json!(display);
+                    }
+                    element
+                })
+                .collect();
+            json!({ "source": source, "target": target, "element": elements })
+        })
+        .collect();
+
+    json!({
+        "resourceType": "ConceptMap",
+        "id": CONCEPT_MAP_ID,
+        "url": concept_map_url(),
+        "name": "NAMASTEToICD11",
+        "title": "NAMASTE to ICD-11 (TM2 and Biomedicine) dual coding map",
+        "status": "active",
+        "experimental": false,
+        "date": chrono::Utc::now().to_rfc3339(),
+        "publisher": PUBLISHER,
+        "group": groups
+    })
+}
+
+// $translate result. Matches carry the equivalence as seen from the requested code
+pub fn translate_result(matches: &[(Value, &str)], message: &str) -> Value {
+    let mut parameters = vec![
+        json!({ "name": "result", "valueBoolean": !matches.is_empty() }),
+        json!({ "name": "message", "valueString": message }),
+    ];
+    for (coding, equivalence) in matches {
+        parameters.push(json!({
+            "name": "match",
+            "part": [
+                { "name": "equivalence", "valueCode": equivalence },
+                { "name": "concept", "valueCoding": coding },
+                { "name": "source", "valueUri": concept_map_url() }
+            ]
+        }));
+    }
+    json!({ "resourceType": "Parameters", "parameter": parameters })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codecs::mapping::{Equivalence, SHEET_ORIGIN};
+    use crate::fhir::{ICD11_TM2_SYSTEM, NAMASTE_SYSTEM};
+
+    fn element(source: &str, target: &str) -> MappingElement {
+        MappingElement {
+            source_system: NAMASTE_SYSTEM.to_string(),
+            source_code: source.to_string(),
+            source_display: Some(format!("{} display", source)),
+            target_system: ICD11_TM2_SYSTEM.to_string(),
+            target_code: target.to_string(),
+            target_display: None,
+            equivalence: Equivalence::for_icd_target(target),
+            origin: SHEET_ORIGIN.to_string(),
+            comment: None,
+        }
+    }
+
+    #[test]
+    fn test_concept_map_groups_targets_by_source_code() {
+        let map = concept_map(&[element("AAA-1", "SR11"), element("AAB-3", "SP9Y"), element("AAA-1", "SR12")]);
+
+        let group = &map["group"][0];
+        assert_eq!(group["source"], NAMASTE_SYSTEM);
+        assert_eq!(group["target"], ICD11_TM2_SYSTEM);
+        assert_eq!(group["element"][0]["code"], "AAA-1");
+        assert_eq!(group["element"][0]["target"].as_array().unwrap().len(), 2);
+        assert_eq!(group["element"][1]["target"][0]["equivalence"], "wider");
+    }
+
+    #[test]
+    fn test_equivalence_reverses() {
+        assert_eq!(Equivalence::Wider.reversed(), Equivalence::Narrower);
+        assert_eq!(Equivalence::Equivalent.reversed(), Equivalence::Equivalent);
+    }
+}
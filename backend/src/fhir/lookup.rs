@@ -0,0 +1,217 @@
+use serde_json::{json, Value};
+
+use crate::codecs::icd::IcdCode;
+use crate::codecs::namaste::NamasteCode;
+use super::codesystem::{namaste_designations, CodeSystemKind};
+use super::operation_outcome;
+
+// Turn a CodeSystem designation into the "designation" part list used by $lookup
+fn designation_parameter(designation: &Value) -> Value {
+    let mut parts = Vec::new();
+    if let Some(language) = designation["language"].as_str() {
+        parts.push(json!({ "name": "language", "valueCode": language }));
+    }
+    if designation["use"].is_object() {
+        parts.push(json!({ "name": "use", "valueCoding": designation["use"] }));
+    }
+    parts.push(json!({ "name": "value", "valueString": designation["value"] }));
+    json!({ "name": "designation", "part": parts })
+}
+
+fn property_parameter(code: &str, value: Value) -> Value {
+    json!({
+        "name": "property",
+        "part": [
+            { "name": "code", "valueCode": code },
+            value_part(value)
+        ]
+    })
+}
+
+fn value_part(value: Value) -> Value {
+    match value {
+        Value::Bool(b) => json!({ "name": "value", "valueBoolean": b }),
+        other => json!({ "name": "value", "valueString": other }),
+    }
+}
+
+fn lookup_header(kind: CodeSystemKind, version: Option<String>, display: &str) -> Vec<Value> {
+    let mut parameters = vec![json!({ "name": "name", "valueString": kind.name() })];
+    if let Some(version) = version {
+        parameters.push(json!({ "name": "version", "valueString": version }));
+    }
+    parameters.push(json!({ "name": "display", "valueString": display }));
+    parameters
+}
+
+// OperationOutcome for a code the system does not have
+pub fn not_found(kind: CodeSystemKind, code: &str) -> Value {
+    operation_outcome("error", "not-found", &format!("Code {} was not found in {}", code, kind.url()))
+}
+
+// $lookup result for a NAMASTE concept
+pub fn namaste_lookup(code: &NamasteCode, parent: Option<String>) -> Value {
+    let mut parameters = lookup_header(CodeSystemKind::Namaste, None, &code.namc_term);
+
+    let definition = code.long_definition.as_ref()
+        .or(code.short_definition.as_ref())
+        .filter(|d| !d.trim().is_empty());
+    if let Some(definition) = definition {
+        parameters.push(json!({ "name": "definition", "valueString": definition }));
+    }
+
+    parameters.extend(namaste_designations(code).iter().map(designation_parameter));
+
+    if let Some(parent) = parent {
+        parameters.push(json!({
+            "name": "property",
+            "part": [
+                { "name": "code", "valueCode": "parent" },
+                { "name": "value", "valueCode": parent }
+            ]
+        }));
+    }
+    if let (_, Some(icd_code)) = code.parse_codes() {
+        parameters.push(property_parameter("icd11Code", json!(icd_code)));
+    }
+    if let Some(branches) = code.ontology_branches.as_ref().filter(|b| !b.trim().is_empty()) {
+        parameters.push(property_parameter("ontologyBranches", json!(branches)));
+    }
+
+    json!({ "resourceType": "Parameters", "parameter": parameters })
+}
+
+// $lookup result for an ICD-11 entity
+pub fn icd_lookup(kind: CodeSystemKind, code: &IcdCode, parent: Option<String>) -> Value {
+    let mut parameters = lookup_header(kind, code.release(), &code.title);
+
+    if let Some(definition) = code.definition.as_ref().filter(|d| !d.trim().is_empty()) {
+        parameters.push(json!({ "name": "definition", "valueString": definition }));
+    }
+
+    // Synonyms are stored "; "-separated by the scraper
+    let synonyms = code.synonyms.as_deref().unwrap_or_default();
+    for synonym in synonyms.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+        parameters.push(designation_parameter(&json!({
+            "language": "en",
+            "use": {
+                "system": "http://snomed.info/sct",
+                "code": "900000000000013009",
+                "display": "Synonym"
+            },
+            "value": synonym
+        })));
+    }
+
+    if let Some(parent) = parent {
+        parameters.push(json!({
+            "name": "property",
+            "part": [
+                { "name": "code", "valueCode": "parent" },
+                { "name": "value", "valueCode": parent }
+            ]
+        }));
+    }
+    let text_properties = [
+        ("codingNote", &code.coding_note),
+        ("inclusions", &code.inclusions),
+        ("exclusions", &code.exclusions),
+        ("browserUrl", &code.browser_url),
+    ];
+    for (name, value) in text_properties {
+        if let Some(value) = value.as_ref().filter(|v| !v.trim().is_empty()) {
+            parameters.push(property_parameter(name, json!(value)));
+        }
+    }
+    if let Some(is_leaf) = code.is_leaf_flag() {
+        parameters.push(property_parameter("isLeaf", json!(is_leaf)));
+    }
+
+    json!({ "resourceType": "Parameters", "parameter": parameters })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The parts of a "property" parameter with the given code
+    fn property<'a>(parameters: &'a Value, code: &str) -> Option<&'a Value> {
+        parameters["parameter"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|p| p["name"] == "property" && p["part"][0]["valueCode"] == code)
+            .map(|p| &p["part"][1])
+    }
+
+    #[test]
+    fn test_namaste_lookup_has_script_designations_and_parent() {
+        let code = NamasteCode {
+            sr_no: 9,
+            namc_id: 9,
+            namc_code: "SR11 (AAA-2.2)".to_string(),
+            namc_term: "prANavAtakopaH".to_string(),
+            namc_term_diacritical: "prāṇavātakōpaḥ".to_string(),
+            namc_term_devanagari: "प्राणवातकोपः".to_string(),
+            short_definition: Some(" ".to_string()),
+            long_definition: Some("the disorder is characterized by hikkā [hiccup]".to_string()),
+            ontology_branches: None,
+        };
+        let parameters = namaste_lookup(&code, Some("AAA-2".to_string()));
+        assert_eq!(parameters["resourceType"], "Parameters");
+        assert_eq!(parameters["parameter"][0], json!({ "name": "name", "valueString": "NAMASTE" }));
+        // The Harvard-Kyoto term is the display, IAST and Devanagari are designations
+        assert_eq!(parameters["parameter"][1], json!({ "name": "display", "valueString": "prANavAtakopaH" }));
+        assert_eq!(parameters["parameter"][2]["valueString"], "the disorder is characterized by hikkā [hiccup]");
+        let designations: Vec<&Value> = parameters["parameter"].as_array().unwrap().iter().filter(|p| p["name"] == "designation").collect();
+        assert_eq!(designations.len(), 2);
+        assert_eq!(designations[0]["part"][0], json!({ "name": "language", "valueCode": "sa-Latn" }));
+        assert_eq!(designations[0]["part"][2], json!({ "name": "value", "valueString": "prāṇavātakōpaḥ" }));
+        assert_eq!(designations[1]["part"][0], json!({ "name": "language", "valueCode": "sa-Deva" }));
+        assert_eq!(designations[1]["part"][2], json!({ "name": "value", "valueString": "प्राणवातकोपः" }));
+
+        assert_eq!(property(&parameters, "parent"), Some(&json!({ "name": "value", "valueCode": "AAA-2" })));
+        assert_eq!(property(&parameters, "icd11Code"), Some(&json!({ "name": "value", "valueString": "SR11" })));
+        assert_eq!(property(&parameters, "ontologyBranches"), None);
+        assert_eq!(property(&namaste_lookup(&code, None), "parent"), None);
+    }
+
+    #[test]
+    fn test_icd_lookup_has_text_properties_and_synonyms() {
+        let code = IcdCode {
+            id: "http://id.who.int/icd/release/11/2025-01/mms/257068234".to_string(),
+            code: "1A00".to_string(),
+            title: "Cholera".to_string(),
+            definition: None,
+            parent: Some("http://id.who.int/icd/release/11/2025-01/mms/135352227".to_string()),
+            browser_url: None,
+            coding_note: Some("Use additional code for dehydration".to_string()),
+            synonyms: Some("Asiatic cholera; ;epidemic cholera".to_string()),
+            exclusions: Some("Vibrio parahaemolyticus infection".to_string()),
+            inclusions: Some("classical cholera".to_string()),
+            is_leaf: Some("TRUE".to_string()),
+        };
+        let parameters = icd_lookup(CodeSystemKind::Icd11Biomedicine, &code, Some("135352227".to_string()));
+        assert_eq!(parameters["parameter"][1], json!({ "name": "version", "valueString": "2025-01" }));
+        assert_eq!(parameters["parameter"][2], json!({ "name": "display", "valueString": "Cholera" }));
+        let synonyms: Vec<&Value> = parameters["parameter"].as_array().unwrap().iter().filter(|p| p["name"] == "designation").collect();
+        assert_eq!(synonyms.len(), 2);
+        assert_eq!(synonyms[1]["part"][2]["valueString"], "epidemic cholera");
+
+        assert_eq!(property(&parameters, "parent"), Some(&json!({ "name": "value", "valueCode": "135352227" })));
+        assert_eq!(property(&parameters, "codingNote").unwrap()["valueString"], "Use additional code for dehydration");
+        assert_eq!(property(&parameters, "inclusions").unwrap()["valueString"], "classical cholera");
+        assert_eq!(property(&parameters, "exclusions").unwrap()["valueString"], "Vibrio parahaemolyticus infection");
+        assert_eq!(property(&parameters, "browserUrl"), None);
+        assert_eq!(property(&parameters, "isLeaf"), Some(&json!({ "name": "value", "valueBoolean": true })));
+    }
+
+    #[test]
+    fn test_unknown_code_is_a_not_found_outcome() {
+        let outcome = not_found(CodeSystemKind::Namaste, "ZZZ-9");
+        assert_eq!(outcome["resourceType"], "OperationOutcome");
+        assert_eq!(outcome["issue"][0]["severity"], "error");
+        assert_eq!(outcome["issue"][0]["code"], "not-found");
+        assert_eq!(outcome["issue"][0]["diagnostics"], "Code ZZZ-9 was not found in https://namaste.ayush.gov.in/ayurveda");
+    }
+}
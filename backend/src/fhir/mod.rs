@@ -0,0 +1,38 @@
+// FHIR R4 resource builders shared by the /fhir routes
+pub mod codesystem;
+pub mod lookup;
+pub mod conceptmap;
+pub mod valueset;
+pub mod validate;
+pub mod bundle;
+pub mod capability;
+pub mod audit;
+
+use serde_json::{json, Value};
+
+pub use codesystem::CodeSystemKind;
+
+// Media type for FHIR JSON responses
+pub const FHIR_JSON: &str = "application/fhir+json";
+
+// Canonical system URIs for the code systems we serve
+pub const NAMASTE_SYSTEM: &str = "https://namaste.ayush.gov.in/ayurveda";
+pub const ICD11_BIOMEDICINE_SYSTEM: &str = "http://id.who.int/icd/release/11/mms";
+pub const ICD11_TM2_SYSTEM: &str = "http://id.who.int/icd/release/11/tm2";
+
+// Base for canonical URLs of resources this server defines itself (ConceptMap, ValueSet)
+pub const CANONICAL_BASE: &str = "https://codevedas.in/fhir";
+
+pub const PUBLISHER: &str = "CodeVedas FHIR Terminology Server";
+
+// Build an OperationOutcome with a single issue
+pub fn operation_outcome(severity: &str, code: &str, diagnostics: &str) -> Value {
+    json!({
+        "resourceType": "OperationOutcome",
+        "issue": [{
+            "severity": severity,
+            "code": code,
+            "diagnostics": diagnostics
+        }]
+    })
+}
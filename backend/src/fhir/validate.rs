@@ -0,0 +1,65 @@
+use serde_json::{json, Value};
+use unicode_normalization::UnicodeNormalization;
+
+// Outcome of checking a code (and optional display) against a code system or value set
+#[derive(Debug, Clone)]
+pub struct CodeValidation {
+    pub result: bool,
+    pub message: String,
+    pub display: Option<String>,
+}
+
+impl CodeValidation {
+    pub fn invalid(message: String) -> Self {
+        CodeValidation { result: false, message, display: None }
+    }
+}
+
+// Displays are compared in NFC (an IAST "ā" may arrive as "a" plus a combining macron),
+// ignoring runs of whitespace, and case unless it is significant
+fn normalise_display(display: &str, case_sensitive: bool) -> String {
+    let collapsed = display.split_whitespace().collect::<Vec<_>>().join(" ").nfc().collect::<String>();
+    if case_sensitive { collapsed } else { collapsed.to_lowercase() }
+}
+
+// Harvard-Kyoto tells letters apart by case (a/A, s/S), so NAMASTE terms are compared
+// case-sensitively; English ICD-11 titles are not
+pub fn display_matches(candidates: &[&str], display: &str, case_sensitive: bool) -> bool {
+    let wanted = normalise_display(display, case_sensitive);
+    candidates.iter().any(|candidate| normalise_display(candidate, case_sensitive) == wanted)
+}
+
+pub fn validation_parameters(validation: &CodeValidation) -> Value {
+    let mut parameters = vec![
+        json!({ "name": "result", "valueBoolean": validation.result }),
+        json!({ "name": "message", "valueString": validation.message }),
+    ];
+    if let Some(display) = &validation.display {
+        parameters.push(json!({ "name": "display", "valueString": display }));
+    }
+    json!({ "resourceType": "Parameters", "parameter": parameters })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_matching_ignores_spacing_and_english_case() {
+        let candidates = ["vAtasa~jcayaH", "vātasañcayaḥ", "वातसञ्चयः"];
+        assert!(display_matches(&candidates, "vātasañcayaḥ", true));
+        // The IAST designation in decomposed form
+        assert!(display_matches(&candidates, "va\u{304}tasan\u{303}cayah\u{323}", true));
+        assert!(display_matches(&candidates, "  वातसञ्चयः ", true));
+        assert!(display_matches(&["Cholera due to Vibrio cholerae"], "cholera due to  vibrio cholerae", false));
+        assert!(!display_matches(&candidates, "vatasancayah", true));
+    }
+
+    #[test]
+    fn test_harvard_kyoto_display_is_case_sensitive() {
+        let candidates = ["vAtasa~jcayaH", "vātasañcayaḥ", "वातसञ्चयः"];
+        assert!(display_matches(&candidates, "vAtasa~jcayaH", true));
+        assert!(!display_matches(&candidates, "vatasa~jcayah", true));
+        assert!(!display_matches(&candidates, "VĀTASAÑCAYAḤ", true));
+    }
+}
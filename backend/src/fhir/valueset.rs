@@ -0,0 +1,138 @@
+use serde_json::{json, Value};
+
+use crate::codecs::icd::IcdCode;
+use crate::codecs::namaste::NamasteCode;
+use super::codesystem::{namaste_designations, CodeSystemKind};
+
+// Implicit value sets, addressed FHIR-style as "[system]?fhir_vs" or "[system]?fhir_vs=isa/[code]"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImplicitValueSet {
+    All(CodeSystemKind),
+    IsA(CodeSystemKind, String),
+}
+
+impl ImplicitValueSet {
+    pub fn from_url(url: &str) -> Option<Self> {
+        let (system, query) = url.split_once("?fhir_vs")?;
+        let kind = CodeSystemKind::from_url(system)?;
+        match query.strip_prefix('=') {
+            None if query.is_empty() => Some(ImplicitValueSet::All(kind)),
+            Some(rest) => {
+                let code = rest.strip_prefix("isa/")?;
+                (!code.is_empty()).then(|| ImplicitValueSet::IsA(kind, code.to_string()))
+            },
+            None => None,
+        }
+    }
+
+    // ValueSet ids mirror the CodeSystem ids
+    pub fn from_id(id: &str) -> Option<Self> {
+        CodeSystemKind::from_id(id).map(ImplicitValueSet::All)
+    }
+
+    pub fn kind(&self) -> CodeSystemKind {
+        match self {
+            ImplicitValueSet::All(kind) | ImplicitValueSet::IsA(kind, _) => *kind,
+        }
+    }
+
+    pub fn url(&self) -> String {
+        match self {
+            ImplicitValueSet::All(kind) => format!("{}?fhir_vs", kind.url()),
+            ImplicitValueSet::IsA(kind, code) => format!("{}?fhir_vs=isa/{}", kind.url(), code),
+        }
+    }
+}
+
+pub struct ExpansionPage {
+    pub contains: Vec<Value>,
+    pub total: u64,
+    pub offset: usize,
+    pub count: usize,
+    pub filter: Option<String>,
+}
+
+// ValueSet resource carrying one page of expansion
+pub fn expansion(value_set: &ImplicitValueSet, page: ExpansionPage) -> Value {
+    let mut parameters = vec![
+        json!({ "name": "offset", "valueInteger": page.offset }),
+        json!({ "name": "count", "valueInteger": page.count }),
+    ];
+    if let Some(filter) = &page.filter {
+        parameters.push(json!({ "name": "filter", "valueString": filter }));
+    }
+
+    let mut resource = json!({
+        "resourceType": "ValueSet",
+        "url": value_set.url(),
+        "status": "active",
+        "compose": {
+            "include": [include_for(value_set)]
+        },
+        "expansion": {
+            "identifier": format!("urn:uuid:{}", uuid::Uuid::new_v4()),
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+            "total": page.total,
+            "offset": page.offset,
+            "parameter": parameters,
+            "contains": page.contains
+        }
+    });
+    if let ImplicitValueSet::All(kind) = value_set {
+        resource["id"] = json!(kind.id());
+    }
+    resource
+}
+
+fn include_for(value_set: &ImplicitValueSet) -> Value {
+    match value_set {
+        ImplicitValueSet::All(kind) => json!({ "system": kind.url() }),
+        ImplicitValueSet::IsA(kind, code) => json!({
+            "system": kind.url(),
+            "filter": [{ "property": "concept", "op": "is-a", "value": code }]
+        }),
+    }
+}
+
+pub fn namaste_contains(code: &NamasteCode, include_designations: bool) -> Value {
+    let mut entry = json!({
+        "system": CodeSystemKind::Namaste.url(),
+        "code": code.parse_codes().0,
+        "display": code.namc_term
+    });
+    if include_designations {
+        entry["designation"] = Value::Array(namaste_designations(code));
+    }
+    entry
+}
+
+pub fn icd_contains(kind: CodeSystemKind, code: &IcdCode) -> Value {
+    let mut entry = json!({
+        "system": kind.url(),
+        "code": code.concept_code(),
+        "display": code.title
+    });
+    if let Some(version) = code.release() {
+        entry["version"] = json!(version);
+    }
+    entry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fhir::{ICD11_TM2_SYSTEM, NAMASTE_SYSTEM};
+
+    #[test]
+    fn test_implicit_value_set_urls() {
+        assert_eq!(
+            ImplicitValueSet::from_url(&format!("{}?fhir_vs", NAMASTE_SYSTEM)),
+            Some(ImplicitValueSet::All(CodeSystemKind::Namaste))
+        );
+        let isa = ImplicitValueSet::from_url(&format!("{}?fhir_vs=isa/SR1", ICD11_TM2_SYSTEM)).unwrap();
+        assert_eq!(isa, ImplicitValueSet::IsA(CodeSystemKind::Icd11Tm2, "SR1".to_string()));
+        assert_eq!(isa.url(), format!("{}?fhir_vs=isa/SR1", ICD11_TM2_SYSTEM));
+        assert_eq!(ImplicitValueSet::from_url(&format!("{}?fhir_vs=refset/x", ICD11_TM2_SYSTEM)), None);
+        assert_eq!(ImplicitValueSet::from_url("http://example.org?fhir_vs"), None);
+    }
+}
@@ -0,0 +1,285 @@
+// Inverted index with BM25 scoring summed over weighted fields (title over definition, ...).
+// Queries are only ever tokenised, never interpreted, so any user input is safe
+use std::collections::{BTreeMap, HashMap};
+
+use super::fuzzy;
+
+const K1: f32 = 1.2;
+const B: f32 = 0.75;
+// Longest query we score; the rest is ignored
+const MAX_QUERY_TERMS: usize = 32;
+// Dictionary terms the final, possibly unfinished, query word expands to
+const MAX_PREFIX_EXPANSIONS: usize = 50;
+const PREFIX_WEIGHT: f32 = 0.5;
+
+// Lowercased words split on whitespace and ASCII punctuation. Devanagari vowel signs and
+// IAST diacritics stay inside their word
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split(|c: char| c.is_whitespace() || c.is_ascii_punctuation())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// A field's boost and the function that extracts its text
+pub type Field<'a, T> = (f32, &'a dyn Fn(&T) -> String);
+
+struct Posting {
+    doc: usize,
+    field: usize,
+    frequency: u32,
+}
+
+pub struct Bm25Index<T> {
+    boosts: Vec<f32>,
+    documents: Vec<T>,
+    // Tokens per field of each document
+    lengths: Vec<Vec<u32>>,
+    average_lengths: Vec<f32>,
+    postings: BTreeMap<String, Vec<Posting>>,
+    // Dictionary terms, and the terms containing each trigram by position in it, so typo
+    // candidates are looked up instead of scanning the dictionary
+    terms: Vec<String>,
+    trigrams: HashMap<String, Vec<u32>>,
+}
+
+impl<T> Bm25Index<T> {
+    pub fn build(documents: Vec<T>, fields: &[Field<T>]) -> Self {
+        let mut postings: BTreeMap<String, Vec<Posting>> = BTreeMap::new();
+        let mut lengths = Vec::with_capacity(documents.len());
+        let mut totals = vec![0u64; fields.len()];
+
+        for (doc, document) in documents.iter().enumerate() {
+            let mut doc_lengths = Vec::with_capacity(fields.len());
+            for (field, (_, extract)) in fields.iter().enumerate() {
+                let tokens = tokenize(&extract(document));
+                doc_lengths.push(tokens.len() as u32);
+                totals[field] += tokens.len() as u64;
+
+                let mut frequencies: HashMap<String, u32> = HashMap::new();
+                for token in tokens {
+                    *frequencies.entry(token).or_default() += 1;
+                }
+                for (term, frequency) in frequencies {
+                    postings.entry(term).or_default().push(Posting { doc, field, frequency });
+                }
+            }
+            lengths.push(doc_lengths);
+        }
+
+        let terms: Vec<String> = postings.keys().cloned().collect();
+        let mut trigrams: HashMap<String, Vec<u32>> = HashMap::new();
+        for (i, term) in terms.iter().enumerate() {
+            for gram in fuzzy::trigrams(term) {
+                trigrams.entry(gram).or_default().push(i as u32);
+            }
+        }
+
+        let count = documents.len().max(1) as f32;
+        Bm25Index {
+            boosts: fields.iter().map(|(boost, _)| *boost).collect(),
+            documents,
+            lengths,
+            average_lengths: totals.iter().map(|total| (*total as f32 / count).max(1.0)).collect(),
+            postings,
+            terms,
+            trigrams,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    fn document_frequency(postings: &[Posting]) -> usize {
+        let mut docs: Vec<usize> = postings.iter().map(|p| p.doc).collect();
+        docs.dedup();
+        docs.len()
+    }
+
+    fn add_term(&self, scores: &mut HashMap<usize, f32>, postings: &[Posting], weight: f32) {
+        let n = self.documents.len() as f32;
+        let df = Self::document_frequency(postings) as f32;
+        let idf = (1.0 + (n - df + 0.5) / (df + 0.5)).ln();
+        for posting in postings {
+            let tf = posting.frequency as f32;
+            let length = self.lengths[posting.doc][posting.field] as f32;
+            let norm = 1.0 - B + B * length / self.average_lengths[posting.field];
+            let score = idf * tf * (K1 + 1.0) / (tf + K1 * norm);
+            *scores.entry(posting.doc).or_default() += weight * self.boosts[posting.field] * score;
+        }
+    }
+
+    fn prefix_expansions<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = (&'a String, &'a Vec<Posting>)> + 'a {
+        self.postings
+            .range::<str, _>((std::ops::Bound::Excluded(prefix), std::ops::Bound::Unbounded))
+            .take_while(move |(term, _)| term.starts_with(prefix))
+            .take(MAX_PREFIX_EXPANSIONS)
+    }
+
+    // Dictionary terms within the typo budget of `term`, with their distance. Candidates share
+    // enough trigrams with it and are then checked by edit distance. A final query word may
+    // still be unfinished, so it is compared against term prefixes
+    fn typo_matches(&self, term: &str, as_prefix: bool) -> Vec<(&String, usize)> {
+        let max = fuzzy::max_typos(term.chars().count());
+        if max == 0 {
+            return Vec::new();
+        }
+        let grams = fuzzy::trigrams(term);
+        let mut shared: HashMap<u32, usize> = HashMap::new();
+        for gram in &grams {
+            for candidate in self.trigrams.get(gram).into_iter().flatten() {
+                *shared.entry(*candidate).or_default() += 1;
+            }
+        }
+
+        let needed = fuzzy::min_shared_trigrams(grams.len(), max);
+        let mut matches: Vec<(&String, usize)> = shared
+            .into_iter()
+            .filter(|(_, count)| *count >= needed)
+            .filter_map(|(candidate, _)| {
+                let candidate = &self.terms[candidate as usize];
+                let typos = if as_prefix {
+                    fuzzy::prefix_distance(term, candidate, max)
+                } else {
+                    fuzzy::distance(term, candidate, max)
+                };
+                typos.map(|typos| (candidate, typos))
+            })
+            .collect();
+        // Dictionary order, so ties in suggest() do not depend on hashing
+        matches.sort();
+        matches
+    }
+
+    // Documents matching any query word, best first, skipping those `keep` rejects.
+    // The last word also matches as a prefix so partially typed terms still find results;
+    // words found nowhere in the dictionary match near spellings at a typo penalty
+    pub fn search(&self, query: &str, limit: Option<usize>, keep: impl Fn(&T) -> bool) -> Vec<(&T, f32)> {
+        let terms: Vec<String> = tokenize(query).into_iter().take(MAX_QUERY_TERMS).collect();
+        let mut scores: HashMap<usize, f32> = HashMap::new();
+        for (i, term) in terms.iter().enumerate() {
+            let is_last = i + 1 == terms.len();
+            let mut found = false;
+            if let Some(postings) = self.postings.get(term) {
+                self.add_term(&mut scores, postings, 1.0);
+                found = true;
+            }
+            if is_last && term.chars().count() >= 2 {
+                for (_, postings) in self.prefix_expansions(term) {
+                    self.add_term(&mut scores, postings, PREFIX_WEIGHT);
+                    found = true;
+                }
+            }
+            if !found {
+                for (candidate, typos) in self.typo_matches(term, is_last) {
+                    let weight = if is_last { PREFIX_WEIGHT } else { 1.0 };
+                    self.add_term(&mut scores, &self.postings[candidate], weight * fuzzy::typo_weight(typos));
+                }
+            }
+        }
+
+        let mut ranked: Vec<(usize, f32)> = scores
+            .into_iter()
+            .filter(|(doc, _)| keep(&self.documents[*doc]))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked
+            .into_iter()
+            .take(limit.unwrap_or(usize::MAX))
+            .map(|(doc, score)| (&self.documents[doc], score))
+            .collect()
+    }
+
+    // The query with each unknown word replaced by its nearest dictionary term, preferring
+    // common terms on ties, and the number of typos corrected. None when nothing changes
+    pub fn suggest(&self, query: &str) -> Option<(String, usize)> {
+        let mut corrected = Vec::new();
+        let mut total = 0;
+        for term in tokenize(query).into_iter().take(MAX_QUERY_TERMS) {
+            if self.postings.contains_key(&term) {
+                corrected.push(term);
+                continue;
+            }
+            let best = self.typo_matches(&term, false)
+                .into_iter()
+                .min_by_key(|(candidate, typos)| (*typos, std::cmp::Reverse(Self::document_frequency(&self.postings[*candidate]))));
+            match best {
+                Some((candidate, typos)) => {
+                    corrected.push(candidate.clone());
+                    total += typos;
+                },
+                None => corrected.push(term),
+            }
+        }
+        (total > 0).then(|| (corrected.join(" "), total))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Entry {
+        title: &'static str,
+        definition: &'static str,
+    }
+
+    fn index() -> Bm25Index<Entry> {
+        let entries = vec![
+            Entry { title: "Typhoid fever", definition: "Infection by Salmonella typhi" },
+            Entry { title: "Malaria", definition: "Fever caused by Plasmodium parasites" },
+            Entry { title: "Vataja jvara", definition: "Fever due to vata dosha" },
+            Entry { title: "Diabetes mellitus", definition: "Raised blood glucose" },
+        ];
+        let title = |e: &Entry| e.title.to_string();
+        let definition = |e: &Entry| e.definition.to_string();
+        Bm25Index::build(entries, &[(3.0, &title), (1.0, &definition)])
+    }
+
+    #[test]
+    fn test_title_matches_outrank_definition_matches() {
+        let index = index();
+        let results = index.search("fever", None, |_| true);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].0.title, "Typhoid fever");
+        assert!(results[0].1 > results[1].1);
+
+        let titles: Vec<&str> = index.search("jvara fever", Some(1), |_| true).iter().map(|(e, _)| e.title).collect();
+        assert_eq!(titles, vec!["Vataja jvara"]);
+        assert_eq!(index.search("fever", None, |e| e.title != "Malaria").len(), 2);
+    }
+
+    #[test]
+    fn test_queries_are_tokenised_not_interpreted() {
+        let index = index();
+        // Partial final word
+        assert_eq!(index.search("diab", None, |_| true)[0].0.title, "Diabetes mellitus");
+        // Regex metacharacters and catastrophic patterns are just punctuation
+        assert_eq!(index.search("(a+)+$ typhi.*", None, |_| true)[0].0.title, "Typhoid fever");
+        assert!(index.search("[[[", None, |_| true).is_empty());
+        assert!(index.search("", None, |_| true).is_empty());
+        assert_eq!(tokenize("Vyādhi-viniścayaḥ  ज्वरः"), vec!["vyādhi", "viniścayaḥ", "ज्वरः"]);
+    }
+
+    #[test]
+    fn test_typos_match_at_a_penalty_and_drive_suggestions() {
+        let index = index();
+        let typo = index.search("malraia", None, |_| true);
+        assert_eq!(typo[0].0.title, "Malaria");
+        assert!(typo[0].1 < index.search("malaria", None, |_| true)[0].1);
+        // Unfinished last word with a transposition
+        assert_eq!(index.search("diabte", None, |_| true)[0].0.title, "Diabetes mellitus");
+        assert!(index.search("xyzzy", None, |_| true).is_empty());
+
+        assert_eq!(index.suggest("typhiod fevr"), Some(("typhoid fever".to_string(), 2)));
+        assert_eq!(index.suggest("typhoid fever"), None);
+        assert_eq!(index.suggest("qqqqqq"), None);
+
+        // Candidates come from shared trigrams: "salmonela" shares most of "salmonella"'s
+        let candidates: Vec<&String> = index.typo_matches("salmonela", false).into_iter().map(|(term, _)| term).collect();
+        assert_eq!(candidates, vec!["salmonella"]);
+        assert!(index.trigrams["^sa"].iter().any(|i| index.terms[*i as usize] == "salmonella"));
+    }
+}
@@ -0,0 +1,85 @@
+// Typo tolerance shared by the BM25 index and autocomplete: edit distance with adjacent
+// transpositions ("pnuemonia" is one typo from "pneumonia") and trigram candidate lookup
+
+// Typos tolerated in a word of this many characters: none below 4, one up to 7, then two
+pub fn max_typos(length: usize) -> usize {
+    match length {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+// Relevance multiplier for a match that needed `typos` corrections
+pub fn typo_weight(typos: usize) -> f32 {
+    0.5f32.powi(typos as i32)
+}
+
+// Optimal string alignment distance over characters, or None once it must exceed `max`
+pub fn distance(a: &str, b: &str, max: usize) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+    let mut rows: Vec<Vec<usize>> = vec![(0..=b.len()).collect()];
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        let previous = &rows[i - 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1).min(current[j - 1] + 1).min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        if current.iter().min().is_some_and(|best| *best > max) {
+            return None;
+        }
+        rows.push(current);
+    }
+    Some(rows[a.len()][b.len()]).filter(|d| *d <= max)
+}
+
+// Fewest typos between `query` and some prefix of `word`, for words still being typed
+pub fn prefix_distance(query: &str, word: &str, max: usize) -> Option<usize> {
+    let length = query.chars().count();
+    let word: Vec<char> = word.chars().collect();
+    (length.saturating_sub(max)..=(length + max).min(word.len()))
+        .filter_map(|end| distance(query, &word[..end].iter().collect::<String>(), max))
+        .min()
+}
+
+// Character trigrams with a start marker, so the first letters count as a gram of their own
+pub fn trigrams(word: &str) -> Vec<String> {
+    let chars: Vec<char> = std::iter::once('^').chain(word.chars()).collect();
+    let mut grams: Vec<String> = chars.windows(3).map(|w| w.iter().collect()).collect();
+    grams.sort();
+    grams.dedup();
+    grams
+}
+
+// Of a query's `grams` trigrams, how many a word within `max` typos must share. A
+// substitution spoils up to three trigrams, a transposition up to four
+pub fn min_shared_trigrams(grams: usize, max: usize) -> usize {
+    grams.saturating_sub(4 * max).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distances_with_transpositions_and_prefixes() {
+        assert_eq!(distance("pnuemonia", "pneumonia", 2), Some(1));
+        assert_eq!(distance("fever", "fever", 0), Some(0));
+        assert_eq!(distance("diabetis", "diabetes", 2), Some(1));
+        assert_eq!(distance("malaria", "anaemia", 2), None);
+        assert_eq!(prefix_distance("pnuem", "pneumonia", 1), Some(1));
+        assert_eq!(prefix_distance("pneu", "pneumonia", 1), Some(0));
+        assert_eq!(prefix_distance("xyz", "pneumonia", 1), None);
+        assert_eq!(trigrams("pneu"), vec!["^pn", "neu", "pne"]);
+        assert_eq!(max_typos(3), 0);
+        assert!(typo_weight(1) < typo_weight(0));
+    }
+}
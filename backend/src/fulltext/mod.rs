@@ -0,0 +1,133 @@
+// BM25 full-text indexes over both code systems, built from the terminology storage at
+// startup. Code searches use them once loaded and fall back to the storage's literal
+// substring search until then
+pub mod bm25;
+pub mod fuzzy;
+
+use std::sync::{OnceLock, RwLock};
+
+use crate::codecs::icd::{IcdCode, IcdCodec};
+use crate::codecs::namaste::{NamasteCode, NamasteCodec};
+use crate::codecs::transliterate;
+use bm25::Bm25Index;
+
+// Field boosts: title over synonyms over definition over inclusions
+const TITLE_BOOST: f32 = 3.0;
+const CODE_BOOST: f32 = 3.0;
+const SYNONYM_BOOST: f32 = 2.0;
+const DEFINITION_BOOST: f32 = 1.0;
+const INCLUSION_BOOST: f32 = 0.5;
+
+static ICD_INDEX: OnceLock<RwLock<Option<Bm25Index<IcdCode>>>> = OnceLock::new();
+static NAMASTE_INDEX: OnceLock<RwLock<Option<Bm25Index<NamasteCode>>>> = OnceLock::new();
+
+fn icd_index() -> &'static RwLock<Option<Bm25Index<IcdCode>>> {
+    ICD_INDEX.get_or_init(|| RwLock::new(None))
+}
+
+fn namaste_index() -> &'static RwLock<Option<Bm25Index<NamasteCode>>> {
+    NAMASTE_INDEX.get_or_init(|| RwLock::new(None))
+}
+
+pub fn build_icd(codes: Vec<IcdCode>) -> Bm25Index<IcdCode> {
+    let title = |c: &IcdCode| c.title.clone();
+    let code = |c: &IcdCode| c.code.clone();
+    let synonyms = |c: &IcdCode| c.synonyms.clone().unwrap_or_default();
+    let definition = |c: &IcdCode| c.definition.clone().unwrap_or_default();
+    let inclusions = |c: &IcdCode| c.inclusions.clone().unwrap_or_default();
+    Bm25Index::build(codes, &[
+        (TITLE_BOOST, &title),
+        (CODE_BOOST, &code),
+        (SYNONYM_BOOST, &synonyms),
+        (DEFINITION_BOOST, &definition),
+        (INCLUSION_BOOST, &inclusions),
+    ])
+}
+
+// NAMASTE fields and queries are indexed in their script-independent folded form
+pub fn build_namaste(codes: Vec<NamasteCode>) -> Bm25Index<NamasteCode> {
+    let term = |c: &NamasteCode| transliterate::search_forms(&[&c.namc_term, &c.namc_term_diacritical, &c.namc_term_devanagari]);
+    let code = |c: &NamasteCode| transliterate::fold(&c.namc_code);
+    let short_definition = |c: &NamasteCode| transliterate::fold(c.short_definition.as_deref().unwrap_or_default());
+    let long_definition = |c: &NamasteCode| transliterate::fold(c.long_definition.as_deref().unwrap_or_default());
+    Bm25Index::build(codes, &[
+        (TITLE_BOOST, &term),
+        (CODE_BOOST, &code),
+        (DEFINITION_BOOST, &short_definition),
+        (INCLUSION_BOOST, &long_definition),
+    ])
+}
+
+pub async fn load_icd() -> Result<usize, Box<dyn std::error::Error>> {
+    let codes = IcdCodec::new().get_all_codes(None).await?;
+    let index = build_icd(codes);
+    let count = index.len();
+    *icd_index().write().unwrap() = Some(index);
+    Ok(count)
+}
+
+pub async fn load_namaste() -> Result<usize, Box<dyn std::error::Error>> {
+    let codes = NamasteCodec::new().get_all_codes(None).await?;
+    let index = build_namaste(codes);
+    let count = index.len();
+    *namaste_index().write().unwrap() = Some(index);
+    Ok(count)
+}
+
+fn report(name: &str, result: Result<usize, Box<dyn std::error::Error>>) {
+    match result {
+        Ok(count) => println!("📚 Full-text index {} ready with {} codes", name, count),
+        Err(e) => println!("⚠️  Full-text index {} failed to load: {} (code search will use Mongo)", name, e),
+    }
+}
+
+// Build both indexes in the background
+pub fn spawn_load_all() {
+    actix_web::rt::spawn(async { report("icd11", load_icd().await) });
+    actix_web::rt::spawn(async { report("namaste", load_namaste().await) });
+}
+
+// Rebuild the NAMASTE index, e.g. after an import replaced the collection
+pub fn spawn_reload_namaste() {
+    actix_web::rt::spawn(async { report("namaste", load_namaste().await) });
+}
+
+// Ranked matches, or None when the index has not loaded
+pub fn search_icd(query: &str, limit: Option<usize>, keep: impl Fn(&IcdCode) -> bool) -> Option<Vec<(IcdCode, f32)>> {
+    let index = icd_index().read().unwrap();
+    let hits = index.as_ref()?.search(query, limit, keep);
+    Some(hits.into_iter().map(|(code, score)| (code.clone(), score)).collect())
+}
+
+pub fn search_namaste(query: &str, limit: Option<usize>, keep: impl Fn(&NamasteCode) -> bool) -> Option<Vec<(NamasteCode, f32)>> {
+    let index = namaste_index().read().unwrap();
+    let hits = index.as_ref()?.search(&transliterate::search_forms(&[query]), limit, keep);
+    Some(hits.into_iter().map(|(code, score)| (code.clone(), score)).collect())
+}
+
+// Spelling correction for a query from the ICD vocabulary
+pub fn suggest_icd(query: &str) -> Option<(String, usize)> {
+    icd_index().read().unwrap().as_ref()?.suggest(query)
+}
+
+// Spelling correction from the NAMASTE vocabulary, which is held in folded form
+pub fn suggest_namaste(query: &str) -> Option<(String, usize)> {
+    namaste_index().read().unwrap().as_ref()?.suggest(&transliterate::fold(query))
+}
+
+// "Did you mean" for a search over both code systems: the correction needing fewer typos
+pub fn did_you_mean(query: &str) -> Option<String> {
+    [suggest_icd(query), suggest_namaste(query)]
+        .into_iter()
+        .flatten()
+        .min_by_key(|(_, typos)| *typos)
+        .map(|(suggestion, _)| suggestion)
+}
+
+// Indexed code counts, for status endpoints
+pub fn sizes() -> Vec<(&'static str, Option<usize>)> {
+    vec![
+        ("icd11", icd_index().read().unwrap().as_ref().map(Bm25Index::len)),
+        ("namaste", namaste_index().read().unwrap().as_ref().map(Bm25Index::len)),
+    ]
+}
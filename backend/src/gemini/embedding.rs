@@ -1,444 +1,325 @@
 // src/gemini/embedding.rs
 
 use actix_web::{HttpResponse, Result};
-use mongodb::bson::{doc, Document};
-use mongodb::options::UpdateOptions;
-use std::env;
-use std::sync::Arc;
-use tokio::sync::Semaphore;
+use mongodb::bson;
+use std::collections::HashMap;
 use futures::stream::{self, StreamExt};
 
 use crate::codecs::icd::{IcdCodec, IcdCode};
 use crate::codecs::namaste::{NamasteCodec, NamasteCode};
-
-/// Call Gemini embedding API with the given api_key and input text, return embedding vector
-pub async fn call_gemini_embedding_api(api_key: &str, input_text: &str) -> anyhow::Result<Vec<f32>> {
-    let client = reqwest::Client::new();
-    let url = "https://generativelanguage.googleapis.com/v1beta/models/text-embedding-004:embedContent";
-    
-    let request_body = serde_json::json!({
-        "model": "models/text-embedding-004",
-        "content": {
-            "parts": [{
-                "text": input_text
-            }]
-        }
-    });
-
-    let resp = client
-        .post(url)
-        .header("x-goog-api-key", api_key)
-        .json(&request_body)
-        .send()
-        .await?
-        .error_for_status()?;
-
-    let json_resp: serde_json::Value = resp.json().await?;
-
-    let embedding_array = json_resp["embedding"]["values"]
-        .as_array()
-        .ok_or_else(|| anyhow::anyhow!("Invalid embedding response from Gemini"))?;
-
-    let embedding: Vec<f32> = embedding_array
-        .iter()
-        .map(|v| v.as_f64().unwrap_or(0.0) as f32)
-        .collect();
-
-    Ok(embedding)
+use crate::embedding::stored::{self, StoredEmbedding};
+use crate::embedding::template::{self, Templates};
+use crate::embedding::{self, client, EmbeddingProvider};
+use crate::repository::{self, EmbeddingState};
+use crate::vector::{self, IndexSpec};
+use crate::api::jobs;
+use crate::jobs::{JobContext, JobKind};
+use serde::{Deserialize, Serialize};
+
+// A code that still needs an embedding: its key in the storage and vector index, a label
+// for the logs and the text to embed
+struct Pending {
+    key: String,
+    label: String,
+    text: String,
 }
 
-/// Check if a document already has embeddings in MongoDB
-async fn has_embeddings_icd(code_id: &str) -> anyhow::Result<bool> {
-    let client = crate::dbcodes::mongo::MongoClient::get_instance().await?;
-    let db = client.get_database_by_name("icd11_database");
-    let collection = db.collection::<Document>("icd11_entities");
-
-    let filter = doc! {
-        "id": code_id,
-        "embedding": { "$exists": true, "$ne": [] }
-    };
-
-    let count = collection.count_documents(filter, None).await?;
-    Ok(count > 0)
+enum Prepared {
+    Pending(Pending),
+    // Has a vector of this model made from a different text
+    Outdated(Pending),
+    Existing,
+    Skipped,
 }
 
-/// Find NAMASTE document by matching against the NamasteCode data
-async fn find_and_check_namaste_embedding(code: &NamasteCode) -> anyhow::Result<(bool, Option<Document>)> {
-    let client = crate::dbcodes::mongo::MongoClient::get_instance().await?;
-    let db = client.get_database_by_name("ayurveda_db");
-    let collection = db.collection::<Document>("namc_codes");
+#[derive(Debug)]
+enum ProcessResult {
+    Success,
+    Failed,
+    Skipped,
+    AlreadyExists,
+}
 
-    // Try to find by the ID field (field_1 appears to be the ID based on your structure)
-    let filter = doc! { "field_1": code.namc_id };
-    
-    if let Ok(Some(document)) = collection.find_one(filter.clone(), None).await {
-        // Found the document, now check if it has embeddings
-        let has_embedding = document.get_array("embedding")
-            .map(|arr| !arr.is_empty())
-            .unwrap_or(false);
-        
-        println!("🔍 Found NAMASTE code {} using field_1 filter", code.namc_id);
-        return Ok((has_embedding, Some(document)));
+/// Compare the text a code would be embedded from with the text behind its vector of the
+/// model: only new or changed texts are embedded. With `only_failed`, only codes whose last
+/// attempt failed are
+fn prepare(state: &EmbeddingState, only_failed: bool, pending: Pending) -> Prepared {
+    if pending.text.trim().is_empty() {
+        return Prepared::Skipped;
     }
-
-    // If not found by field_1, try to match by Sanskrit/Devanagari terms
-    // Look for documents that match any of the term fields
-    let possible_filters = vec![
-        doc! { "vyAdhi-viniScayaH": &code.namc_term },
-        doc! { "vyādhi-viniścayaḥ": &code.namc_term },
-        doc! { "व्याधि-विनिश्चयः": &code.namc_term },
-    ];
-
-    for filter in possible_filters {
-        if let Ok(Some(document)) = collection.find_one(filter.clone(), None).await {
-            let has_embedding = document.get_array("embedding")
-                .map(|arr| !arr.is_empty())
-                .unwrap_or(false);
-            
-            println!("🔍 Found NAMASTE code {} using term filter: {}", code.namc_id, filter);
-            return Ok((has_embedding, Some(document)));
-        }
+    let embedded = state.text_hash.as_deref();
+    if embedded == Some(stored::text_hash(&pending.text).as_str()) {
+        return Prepared::Existing;
     }
-
-    // Document not found with any strategy
-    Ok((false, None))
-}
-
-/// Extract text content from MongoDB NAMASTE document for embedding
-fn extract_namaste_text_from_document(document: &Document, fallback_term: &str) -> String {
-    let mut text_parts = Vec::new();
-
-    // Try to extract text from various fields in the document
-    if let Ok(sanskrit) = document.get_str("vyAdhi-viniScayaH") {
-        if !sanskrit.is_empty() {
-            text_parts.push(sanskrit.to_string());
-        }
+    if only_failed && !state.failed {
+        return Prepared::Skipped;
     }
-
-    if let Ok(sanskrit_iast) = document.get_str("vyādhi-viniścayaḥ") {
-        if !sanskrit_iast.is_empty() {
-            text_parts.push(sanskrit_iast.to_string());
-        }
+    match embedded {
+        Some(_) => Prepared::Outdated(pending),
+        None => Prepared::Pending(pending),
     }
+}
 
-    if let Ok(devanagari) = document.get_str("व्याधि-विनिश्चयः") {
-        if !devanagari.is_empty() {
-            text_parts.push(devanagari.to_string());
-        }
-    }
+/// Sort a chunk of codes into those with a current embedding and those still to embed, with
+/// one storage lookup for the whole chunk. Codes the storage no longer has are skipped
+async fn prepare_chunk(spec: &IndexSpec, chunk: Vec<Pending>, model_key: &str, only_failed: bool) -> anyhow::Result<Vec<Prepared>> {
+    let keys: Vec<String> = chunk.iter().map(|pending| pending.key.clone()).collect();
+    let states = repository::get().embedding_states(spec, &keys, model_key).await?;
+    Ok(chunk
+        .into_iter()
+        .map(|pending| match states.get(&pending.key) {
+            Some(state) => prepare(state, only_failed, pending),
+            None => Prepared::Skipped,
+        })
+        .collect())
+}
 
-    // Add the AYU field if it exists
-    if let Ok(ayu) = document.get_str("AYU") {
-        if !ayu.is_empty() {
-            text_parts.push(ayu.to_string());
-        }
+fn icd_pending(code: &IcdCode, by_id: &HashMap<&str, &IcdCode>, template: &str) -> Pending {
+    Pending {
+        key: code.id.clone(),
+        label: format!("ICD code {}", code.id),
+        text: template::render(template, &template::icd_fields(code, &template::parent_titles(code, by_id))),
     }
+}
 
-    // If no text found in document, use the fallback term from NamasteCode
-    if text_parts.is_empty() {
-        text_parts.push(fallback_term.to_string());
+fn namaste_pending(code: &NamasteCode, template: &str) -> Pending {
+    // Rendered from the sheet's columns, as stored
+    let mut text = bson::to_document(code)
+        .map(|document| template::render(template, &template::namaste_fields(&document)))
+        .unwrap_or_default();
+    if text.is_empty() {
+        text = code.namc_term.clone();
+    }
+    Pending {
+        key: code.namc_id.to_string(),
+        label: format!("NAMASTE code {} - {}", code.namc_id, text.chars().take(50).collect::<String>()),
+        text,
     }
-
-    text_parts.join(" ")
 }
 
-/// Process a single ICD code for embeddings
-async fn process_icd_code(
-    code: IcdCode,
-    api_key: String,
-    semaphore: Arc<Semaphore>,
-) -> anyhow::Result<ProcessResult> {
-    let _permit = semaphore.acquire().await.unwrap();
-
-    match has_embeddings_icd(&code.id).await {
-        Ok(has_embedding) => {
-            if has_embedding {
-                return Ok(ProcessResult::AlreadyExists);
+/// Embeddings for one batch, or why each text failed. When the provider rejects the batch
+/// as a whole its texts are sent one by one, so a single bad text does not fail the rest
+async fn embed_batch(provider: &dyn EmbeddingProvider, batch: &[Pending]) -> Vec<Result<Vec<f32>, String>> {
+    let texts: Vec<String> = batch.iter().map(|pending| pending.text.clone()).collect();
+    match provider.embed_batch(&texts).await {
+        Ok(embeddings) => embeddings.into_iter().map(Ok).collect(),
+        Err(e) if batch.len() > 1 && client::is_rejected(&e) => {
+            println!("⚠️  Batch of {} texts rejected ({}), embedding them one by one", batch.len(), e);
+            let mut results = Vec::with_capacity(batch.len());
+            for text in &texts {
+                results.push(provider.embed(text).await.map_err(|e| e.to_string()));
             }
+            results
         },
-        Err(e) => {
-            println!("⚠️  Failed to check embedding status for ICD code {}: {}", code.id, e);
-        }
-    }
-
-    let combined_text = format!(
-        "{} {} {}",
-        code.title,
-        code.definition.clone().unwrap_or_default(),
-        code.code
-    );
-
-    if combined_text.trim().is_empty() {
-        return Ok(ProcessResult::Skipped);
+        Err(e) => vec![Err(e.to_string()); batch.len()],
     }
+}
 
-    match call_gemini_embedding_api(&api_key, &combined_text).await {
+/// Store an embedding with its metadata next to those of other models, clearing the model's
+/// earlier failure. A failure is recorded under the model's entry so that a run of the same
+/// model with "only_failed" picks the code up again
+async fn store(
+    spec: &IndexSpec,
+    provider: &dyn EmbeddingProvider,
+    pending: &Pending,
+    result: Result<Vec<f32>, String>,
+) -> ProcessResult {
+    let repository = repository::get();
+    let model_key = stored::key_of(provider);
+    match result {
         Ok(embedding) => {
-            let client = crate::dbcodes::mongo::MongoClient::get_instance().await?;
-            let db = client.get_database_by_name("icd11_database");
-            let collection = db.collection::<Document>("icd11_entities");
-
-            let filter = doc! { "id": &code.id };
-            let update = doc! { "$set": { "embedding": &embedding } };
-            let options = UpdateOptions::builder().upsert(false).build();
-
-            match collection.update_one(filter, update, options).await {
-                Ok(update_result) => {
-                    if update_result.modified_count > 0 {
-                        println!("✅ Processed ICD code: {} (embedding size: {})", code.id, embedding.len());
-                        Ok(ProcessResult::Success)
-                    } else {
-                        Ok(ProcessResult::AlreadyExists)
-                    }
+            let entry = StoredEmbedding::new(provider, &pending.text, embedding);
+            match repository.store_embedding(spec, &pending.key, &model_key, &entry).await {
+                Ok(true) => {
+                    vector::upsert(spec, &model_key, pending.key.clone(), &entry.vector);
+                    println!("✅ Processed {} (embedding size: {})", pending.label, entry.vector.len());
+                    ProcessResult::Success
                 },
+                Ok(false) => ProcessResult::AlreadyExists,
                 Err(e) => {
-                    println!("❌ Failed to update ICD code {} in MongoDB: {}", code.id, e);
-                    Ok(ProcessResult::Failed)
+                    println!("❌ Failed to store the embedding of {}: {}", pending.label, e);
+                    ProcessResult::Failed
                 }
             }
         },
-        Err(e) => {
-            println!("❌ Failed to generate embedding for ICD code {}: {}", code.id, e);
-            Ok(ProcessResult::Failed)
+        Err(error) => {
+            println!("❌ Failed to generate embedding for {}: {}", pending.label, error);
+            if let Err(e) = repository.record_embedding_failure(spec, &pending.key, &model_key, &error).await {
+                println!("⚠️  Could not record the failure of {}: {}", pending.label, e);
+            }
+            ProcessResult::Failed
         }
     }
 }
 
-/// Process a single NAMASTE code for embeddings
-async fn process_namaste_code(
-    code: NamasteCode,
-    api_key: String,
-    semaphore: Arc<Semaphore>,
-) -> anyhow::Result<ProcessResult> {
-    let _permit = semaphore.acquire().await.unwrap();
-
-    // Find the document and check if it has embeddings
-    match find_and_check_namaste_embedding(&code).await {
-        Ok((has_embedding, Some(document))) => {
-            if has_embedding {
-                return Ok(ProcessResult::AlreadyExists);
-            }
-
-            // Document found but no embedding, extract text and proceed
-            let combined_text = extract_namaste_text_from_document(&document, &code.namc_term);
-
-            if combined_text.trim().is_empty() {
-                return Ok(ProcessResult::Skipped);
-            }
-
-            match call_gemini_embedding_api(&api_key, &combined_text).await {
-                Ok(embedding) => {
-                    let client = crate::dbcodes::mongo::MongoClient::get_instance().await?;
-                    let db = client.get_database_by_name("ayurveda_db");
-                    let collection = db.collection::<Document>("namc_codes");
-
-                    // Use the _id from the found document for the update
-                    let filter = doc! { "_id": document.get("_id").unwrap() };
-                    let update = doc! { "$set": { "embedding": &embedding } };
-                    let options = UpdateOptions::builder().upsert(false).build();
-
-                    match collection.update_one(filter, update, options).await {
-                        Ok(update_result) => {
-                            if update_result.modified_count > 0 {
-                                println!("✅ Processed NAMASTE code: {} - {} (embedding size: {})", 
-                                       code.namc_id, combined_text.chars().take(50).collect::<String>(), embedding.len());
-                                Ok(ProcessResult::Success)
-                            } else {
-                                Ok(ProcessResult::AlreadyExists)
-                            }
-                        },
-                        Err(e) => {
-                            println!("❌ Failed to update NAMASTE code {} in MongoDB: {}", code.namc_id, e);
-                            Ok(ProcessResult::Failed)
-                        }
-                    }
-                },
-                Err(e) => {
-                    println!("❌ Failed to generate embedding for NAMASTE code {}: {}", code.namc_id, e);
-                    Ok(ProcessResult::Failed)
-                }
-            }
-        },
-        Ok((_, None)) => {
-            println!("❌ NAMASTE code {} - '{}' not found in database", code.namc_id, code.namc_term);
-            Ok(ProcessResult::Failed)
-        },
-        Err(e) => {
-            println!("❌ Error checking NAMASTE code {}: {}", code.namc_id, e);
-            Ok(ProcessResult::Failed)
-        }
+/// Embed the pending codes of a chunk in batches of the provider's batch size
+async fn embed_pending(
+    spec: &IndexSpec,
+    provider: &dyn EmbeddingProvider,
+    pending: &[Pending],
+    tally: &mut Tally,
+) {
+    let embedded: Vec<_> = stream::iter(pending.chunks(provider.batch_size().max(1)))
+        .map(|batch| async move { (batch, embed_batch(provider, batch).await) })
+        .buffer_unordered(BATCHES_IN_FLIGHT)
+        .collect()
+        .await;
+    let results: Vec<ProcessResult> = stream::iter(
+        embedded
+            .into_iter()
+            .flat_map(|(batch, results)| batch.iter().zip(results))
+            .map(|(item, result)| store(spec, provider, item, result)),
+    )
+    .buffer_unordered(WRITES_IN_FLIGHT)
+    .collect()
+    .await;
+    for result in results {
+        tally.add(result);
     }
 }
 
-#[derive(Debug)]
-enum ProcessResult {
-    Success,
-    Failed,
-    Skipped,
-    AlreadyExists,
+// Codes embedded between checkpoints
+const CHECKPOINT_EVERY: usize = 500;
+// Embedding requests in flight at once; the provider's rate limit still applies
+const BATCHES_IN_FLIGHT: usize = 4;
+// Concurrent embedding writes
+const WRITES_IN_FLIGHT: usize = 50;
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+struct Tally {
+    processed: usize,
+    skipped: usize,
+    existing: usize,
+    failed: usize,
+    // Re-embedded because their text changed since
+    #[serde(default)]
+    outdated: usize,
 }
 
-/// Generate embeddings for all ICD and NAMASTE codes and update MongoDB documents
-pub async fn generate_and_store_embeddings() -> anyhow::Result<()> {
-    println!("🚀 Starting embedding generation process with 100 parallel requests...");
-    
-    let api_key = env::var("GEMINI_KEY")
-        .map_err(|_| anyhow::anyhow!("GEMINI_KEY not found in environment variables"))?;
-    
-    println!("✅ Gemini API key loaded successfully");
-
-    let icd_codec = IcdCodec::new();
-    let namaste_codec = NamasteCodec::new();
-    let semaphore = Arc::new(Semaphore::new(100));
-
-    // Process ICD codes
-    println!("📊 Fetching ICD codes from database...");
-    let icd_codes: Vec<IcdCode> = match icd_codec.get_all_codes(None).await {
-        Ok(codes) => {
-            println!("✅ Successfully fetched {} ICD codes", codes.len());
-            codes
-        },
-        Err(e) => {
-            println!("❌ Failed to fetch ICD codes: {}", e);
-            return Err(anyhow::anyhow!("Failed to fetch ICD codes: {}", e));
-        },
-    };
-
-    println!("🔄 Processing ICD codes for embeddings (up to 100 parallel requests)...");
-    let icd_results = stream::iter(icd_codes.into_iter().enumerate())
-        .map(|(index, code)| {
-            let api_key = api_key.clone();
-            let semaphore = semaphore.clone();
-            
-            tokio::spawn(async move {
-                if index % 100 == 0 {
-                    println!("🔄 Processing ICD batch starting at index {}", index);
-                }
-                process_icd_code(code, api_key, semaphore).await
-            })
-        })
-        .buffer_unordered(100)
-        .collect::<Vec<_>>()
-        .await;
-
-    let mut processed_icd = 0;
-    let mut skipped_icd = 0;
-    let mut failed_icd = 0;
-    let mut existing_icd = 0;
-
-    for result in icd_results {
+impl Tally {
+    fn add(&mut self, result: ProcessResult) {
         match result {
-            Ok(Ok(ProcessResult::Success)) => processed_icd += 1,
-            Ok(Ok(ProcessResult::Skipped)) => skipped_icd += 1,
-            Ok(Ok(ProcessResult::Failed)) => failed_icd += 1,
-            Ok(Ok(ProcessResult::AlreadyExists)) => existing_icd += 1,
-            Ok(Err(e)) => {
-                println!("❌ Task error: {}", e);
-                failed_icd += 1;
-            },
-            Err(e) => {
-                println!("❌ Join error: {}", e);
-                failed_icd += 1;
-            }
+            ProcessResult::Success => self.processed += 1,
+            ProcessResult::Skipped => self.skipped += 1,
+            ProcessResult::Failed => self.failed += 1,
+            ProcessResult::AlreadyExists => self.existing += 1,
         }
     }
 
-    println!("📈 ICD Summary: {} processed, {} skipped, {} existing, {} failed", 
-             processed_icd, skipped_icd, existing_icd, failed_icd);
-
-    // Process NAMASTE codes
-    println!("📊 Fetching NAMASTE codes from database...");
-    let namaste_codes: Vec<NamasteCode> = match namaste_codec.get_all_codes(None).await {
-        Ok(codes) => {
-            println!("✅ Successfully fetched {} NAMASTE codes", codes.len());
-            codes
-        },
-        Err(e) => {
-            println!("❌ Failed to fetch NAMASTE codes: {}", e);
-            return Err(anyhow::anyhow!("Failed to fetch NAMASTE codes: {}", e));
-        },
-    };
+    fn total(&self) -> usize {
+        self.processed + self.skipped + self.existing + self.failed
+    }
+}
 
-    println!("🔄 Processing NAMASTE codes for embeddings (up to 100 parallel requests)...");
-    let namaste_results = stream::iter(namaste_codes.into_iter().enumerate())
-        .map(|(index, code)| {
-            let api_key = api_key.clone();
-            let semaphore = semaphore.clone();
-            
-            tokio::spawn(async move {
-                if index % 100 == 0 {
-                    println!("🔄 Processing NAMASTE batch starting at index {}", index);
-                }
-                process_namaste_code(code, api_key, semaphore).await
-            })
-        })
-        .buffer_unordered(100)
-        .collect::<Vec<_>>()
-        .await;
+// Where a resumed run continues: the system being worked through, how many of its codes
+// (in id order) are done, and the counts so far. A checkpoint of another model is ignored
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Checkpoint {
+    // Model key the run embeds for
+    #[serde(default)]
+    model: String,
+    system: String,
+    offset: usize,
+    icd: Tally,
+    namaste: Tally,
+}
 
-    let mut processed_namaste = 0;
-    let mut skipped_namaste = 0;
-    let mut failed_namaste = 0;
-    let mut existing_namaste = 0;
+const SYSTEMS: [&str; 2] = ["icd", "namaste"];
+
+/// Generate embeddings for all ICD and NAMASTE codes and store them with the concepts, as a
+/// background job that checkpoints every CHECKPOINT_EVERY codes and stops when cancelled.
+/// Params: "only_failed" retries just the codes whose embedding failed in an earlier run
+pub async fn run_job(context: &JobContext) -> anyhow::Result<serde_json::Value> {
+    let only_failed = context.params.get("only_failed").and_then(serde_json::Value::as_bool).unwrap_or(false);
+    let provider = embedding::provider()
+        .ok_or_else(|| anyhow::anyhow!("No embedding provider configured (set EMBEDDING_PROVIDER or GEMINI_KEY)"))?;
+    println!("✅ Using {} embedding provider ({}, {} texts per request)", provider.name(), provider.model(), provider.batch_size());
+    let model_key = stored::key_of(provider.as_ref());
+    // Changing a template changes the texts, so the affected concepts are embedded again
+    let templates = Templates::load().map_err(|e| anyhow::anyhow!("Invalid embedding templates: {}", e))?;
+
+    let mut checkpoint: Checkpoint = context
+        .checkpoint
+        .clone()
+        .and_then(|value| serde_json::from_value::<Checkpoint>(value).ok())
+        .filter(|checkpoint| checkpoint.model == model_key)
+        .unwrap_or_default();
+    checkpoint.model = model_key.clone();
+    if !checkpoint.system.is_empty() {
+        println!("🔁 Resuming embedding generation at {} code {}", checkpoint.system, checkpoint.offset);
+    }
 
-    for result in namaste_results {
-        match result {
-            Ok(Ok(ProcessResult::Success)) => processed_namaste += 1,
-            Ok(Ok(ProcessResult::Skipped)) => skipped_namaste += 1,
-            Ok(Ok(ProcessResult::Failed)) => failed_namaste += 1,
-            Ok(Ok(ProcessResult::AlreadyExists)) => existing_namaste += 1,
-            Ok(Err(e)) => {
-                println!("❌ Task error: {}", e);
-                failed_namaste += 1;
-            },
-            Err(e) => {
-                println!("❌ Join error: {}", e);
-                failed_namaste += 1;
+    // Codes in a stable order, so an offset means the same thing after a restart
+    let mut icd_codes = IcdCodec::new().get_all_codes(None).await
+        .map_err(|e| anyhow::anyhow!("Failed to fetch ICD codes: {}", e))?;
+    icd_codes.sort_by(|a, b| a.id.cmp(&b.id));
+    let mut namaste_codes = NamasteCodec::new().get_all_codes(None).await
+        .map_err(|e| anyhow::anyhow!("Failed to fetch NAMASTE codes: {}", e))?;
+    namaste_codes.sort_by_key(|code| code.namc_id);
+    // For the parent chain in ICD texts
+    let icd_by_id: HashMap<&str, &IcdCode> = icd_codes.iter().map(|code| (code.id.as_str(), code)).collect();
+    let total = icd_codes.len() + namaste_codes.len();
+    println!("📊 {} ICD and {} NAMASTE codes to check for embeddings", icd_codes.len(), namaste_codes.len());
+
+    let resume_at = SYSTEMS.iter().position(|s| *s == checkpoint.system).unwrap_or(0);
+    for system in &SYSTEMS[resume_at..] {
+        let count = if *system == "icd" { icd_codes.len() } else { namaste_codes.len() };
+        let start = if checkpoint.system == *system { checkpoint.offset } else { 0 };
+        checkpoint.system = system.to_string();
+
+        let mut offset = start.min(count);
+        while offset < count {
+            context.ensure_active()?;
+            let end = (offset + CHECKPOINT_EVERY).min(count);
+            println!("🔄 Embedding {} codes {}..{} of {}", system, offset, end, count);
+
+            let tally = if *system == "icd" { &mut checkpoint.icd } else { &mut checkpoint.namaste };
+            let (spec, chunk) = if *system == "icd" {
+                (&vector::ICD, icd_codes[offset..end].iter().map(|code| icd_pending(code, &icd_by_id, &templates.icd)).collect())
+            } else {
+                (&vector::NAMASTE, namaste_codes[offset..end].iter().map(|code| namaste_pending(code, &templates.namaste)).collect())
+            };
+            let prepared = prepare_chunk(spec, chunk, &model_key, only_failed).await?;
+            let mut pending = Vec::new();
+            for item in prepared {
+                match item {
+                    Prepared::Pending(item) => pending.push(item),
+                    Prepared::Outdated(item) => {
+                        tally.outdated += 1;
+                        pending.push(item);
+                    },
+                    Prepared::Existing => tally.add(ProcessResult::AlreadyExists),
+                    Prepared::Skipped => tally.add(ProcessResult::Skipped),
+                }
             }
+            embed_pending(spec, provider.as_ref(), &pending, tally).await;
+
+            offset = end;
+            checkpoint.offset = offset;
+            let done = checkpoint.icd.total() + checkpoint.namaste.total();
+            let detail = serde_json::json!({ "icd": checkpoint.icd, "namaste": checkpoint.namaste });
+            context.report(total, done, detail, Some(serde_json::to_value(&checkpoint)?)).await;
         }
+        println!("📈 {} Summary: {:?}", system, if *system == "icd" { checkpoint.icd } else { checkpoint.namaste });
     }
 
-    println!("📈 NAMASTE Summary: {} processed, {} skipped, {} existing, {} failed", 
-             processed_namaste, skipped_namaste, existing_namaste, failed_namaste);
-
-    let total_processed = processed_icd + processed_namaste;
-    let total_existing = existing_icd + existing_namaste;
-    let total_failed = failed_icd + failed_namaste;
-    let total_codes = (processed_icd + skipped_icd + existing_icd + failed_icd) + 
-                     (processed_namaste + skipped_namaste + existing_namaste + failed_namaste);
-
-    println!("🎉 Parallel embedding generation completed!");
-    println!("📊 Final Summary:");
-    println!("   • Total codes: {}", total_codes);
-    println!("   • Successfully processed: {}", total_processed);
-    println!("   • Already had embeddings: {}", total_existing);
-    println!("   • Failed: {}", total_failed);
-    println!("   • Total coverage: {:.2}%", 
-             ((total_processed + total_existing) as f64 / total_codes as f64) * 100.0);
-
-    Ok(())
+    let embedded = checkpoint.icd.processed + checkpoint.namaste.processed;
+    let existing = checkpoint.icd.existing + checkpoint.namaste.existing;
+    let coverage = if total > 0 { (embedded + existing) as f64 / total as f64 * 100.0 } else { 0.0 };
+    println!("🎉 Embedding generation completed: {} embedded, {} already had embeddings, {:.2}% coverage", embedded, existing, coverage);
+
+    Ok(serde_json::json!({
+        "provider": provider.name(),
+        "model": provider.model(),
+        "total_codes": total,
+        "icd": checkpoint.icd,
+        "namaste": checkpoint.namaste,
+        "coverage_percent": coverage,
+    }))
 }
 
-/// Actix-web handler to trigger embedding generation via API call
+/// Start embedding generation as a background job, followed at /jobs/{id}
 pub async fn generate_embeddings_handler() -> Result<HttpResponse> {
-    println!("🌐 Parallel embedding generation endpoint called");
-    
-    match generate_and_store_embeddings().await {
-        Ok(_) => {
-            println!("✅ Parallel embedding generation completed successfully");
-            Ok(HttpResponse::Ok().json(serde_json::json!({
-                "status": "success",
-                "message": "Gemini embeddings generated and stored in MongoDB with 100 parallel requests",
-                "timestamp": chrono::Utc::now().to_rfc3339()
-            })))
-        },
-        Err(err) => {
-            println!("❌ Parallel embedding generation failed: {:?}", err);
-            Ok(HttpResponse::InternalServerError().json(serde_json::json!({
-                "status": "error",
-                "message": format!("Failed to generate embeddings: {}", err),
-                "timestamp": chrono::Utc::now().to_rfc3339()
-            })))
-        }
-    }
+    println!("🌐 Embedding generation requested");
+    Ok(jobs::start_response(JobKind::Embeddings, serde_json::json!({})).await)
 }
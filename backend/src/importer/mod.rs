@@ -0,0 +1,412 @@
+// NAMASTE CSV import: parse, validate and normalise the sheet, diff it against the active
+// collection and write it as a new collection version
+pub mod store;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::codecs::namaste::{self, NamasteCode};
+use crate::config;
+use crate::embedding::hashing::fnv1a;
+use crate::jobs::JobContext;
+
+pub use store::import_namaste;
+
+// The sheet as published, without a header row
+pub const DEFAULT_NAMASTE_CSV: &str = "csvs/NAMASTE/NATIONAL AYURVEDA MORBIDITY CODES.csv";
+pub const DEFAULT_UPLOADS_DIR: &str = "uploads";
+
+// Codes listed per diff category in a report; counts are always complete
+const DIFF_SAMPLE: usize = 50;
+
+// Column order of the NAMASTE sheet (see csvs/NAMASTE/fields.txt)
+const COLUMNS: [&str; 9] = [
+    "Sr_No",
+    "NAMC_ID",
+    "NAMC_CODE",
+    "NAMC_term",
+    "NAMC_term_diacritical",
+    "NAMC_term_DEVANAGARI",
+    "Short_definition",
+    "Long_definition",
+    "Ontology_branches",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RowIssue {
+    pub line: usize,
+    pub code: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+pub struct ParsedSheet {
+    pub codes: Vec<NamasteCode>,
+    pub rows_read: usize,
+    pub errors: Vec<RowIssue>,
+    pub warnings: Vec<RowIssue>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangedCode {
+    pub code: String,
+    pub fields: Vec<&'static str>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DiffSummary {
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+    pub unchanged: usize,
+    pub added_codes: Vec<String>,
+    pub removed_codes: Vec<String>,
+    pub changed_codes: Vec<ChangedCode>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportReport {
+    pub source: String,
+    pub dry_run: bool,
+    pub version: Option<i64>,
+    pub collection: Option<String>,
+    pub rows_read: usize,
+    pub rows_valid: usize,
+    pub errors: Vec<RowIssue>,
+    pub warnings: Vec<RowIssue>,
+    pub diff: DiffSummary,
+    pub embeddings_kept: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImportOptions {
+    // Report what would change without writing
+    pub dry_run: bool,
+    // Refuse to import when any row is invalid, instead of skipping it
+    pub strict: bool,
+}
+
+fn upload_hash(csv: &str) -> String {
+    format!("{:016x}", fnv1a(csv.as_bytes()))
+}
+
+// Write a posted sheet to `dir` and return the job's "upload" param (path, hash and size),
+// so the job record, which GET /jobs returns, does not carry the whole sheet
+pub fn stage_upload(dir: &Path, csv: &str) -> std::io::Result<Value> {
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!("namaste-{}.csv", uuid::Uuid::new_v4()));
+    std::fs::write(&path, csv)?;
+    Ok(json!({ "path": path.to_string_lossy(), "hash": upload_hash(csv), "bytes": csv.len() }))
+}
+
+// The staged sheet, refused if it changed since it was posted
+fn read_upload(upload: &Value) -> anyhow::Result<String> {
+    let path = upload["path"].as_str().ok_or_else(|| anyhow::anyhow!("upload has no path"))?;
+    let csv = std::fs::read_to_string(path).map_err(|e| anyhow::anyhow!("Uploaded sheet {} is unreadable: {}", path, e))?;
+    if upload["hash"].as_str() != Some(upload_hash(&csv).as_str()) {
+        anyhow::bail!("Uploaded sheet {} changed after it was posted", path);
+    }
+    Ok(csv)
+}
+
+// Import as a background job. Params: "upload" (from stage_upload, or absent for the configured
+// sheet, files.namaste_csv) and "strict". The new version is only swapped in at the end, so an interrupted
+// import is simply run again; the staged sheet is kept until then and removed once the job ends
+pub async fn run_job(context: &JobContext) -> anyhow::Result<Value> {
+    context.ensure_active()?;
+    let strict = context.params.get("strict").and_then(Value::as_bool).unwrap_or(false);
+    let options = ImportOptions { dry_run: false, strict };
+    let upload = context.params.get("upload").filter(|upload| !upload.is_null());
+    let result = match upload {
+        Some(upload) => {
+            let csv = read_upload(upload)?;
+            let result = import_namaste("uploaded sheet", csv.as_bytes(), options).await;
+            if let Some(path) = upload["path"].as_str()
+                && let Err(e) = std::fs::remove_file(path) {
+                println!("⚠️  Could not remove uploaded sheet {}: {}", path, e);
+            }
+            result
+        },
+        None => {
+            let path = &config::get().files.namaste_csv;
+            match std::fs::File::open(path) {
+                Ok(file) => import_namaste(path, file, options).await,
+                Err(e) => Err(e.into()),
+            }
+        },
+    };
+    let report = result.map_err(|e| anyhow::anyhow!("Import failed: {}", e))?;
+    if strict && !report.errors.is_empty() {
+        anyhow::bail!("Strict import refused: the sheet has {} invalid rows", report.errors.len());
+    }
+    context.report(report.rows_read, report.rows_read, serde_json::json!({ "version": report.version }), None).await;
+    Ok(serde_json::to_value(report)?)
+}
+
+// Collapse runs of whitespace (including the sheet's non-breaking spaces) and trim
+fn clean_text(value: &str) -> String {
+    value.split(|c: char| c.is_whitespace() || c == '\u{a0}').filter(|s| !s.is_empty()).collect::<Vec<_>>().join(" ")
+}
+
+fn optional_text(value: &str) -> Option<String> {
+    Some(clean_text(value)).filter(|s| !s.is_empty())
+}
+
+fn is_namaste_code(code: &str) -> bool {
+    let (stem, rest) = code.split_once('-').unwrap_or((code, ""));
+    !stem.is_empty()
+        && stem.chars().all(|c| c.is_ascii_uppercase())
+        && (code.len() == stem.len()
+            || (!rest.is_empty() && rest.split('.').all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))))
+}
+
+// "SR13(AAA-3)", "SR10\u{a0} (AAA-2.1)" -> "SR13 (AAA-3)", "SR10 (AAA-2.1)"; sheet order is kept
+fn normalise_code(raw: &str) -> Result<String, String> {
+    let code = clean_text(raw).to_uppercase();
+    let Some((first, rest)) = code.split_once('(') else {
+        return if is_namaste_code(&code) { Ok(code) } else { Err(format!("malformed code \"{}\"", code)) };
+    };
+    let first = first.trim();
+    let second = rest.strip_suffix(')').map(str::trim).ok_or_else(|| format!("unbalanced parentheses in \"{}\"", code))?;
+
+    let paired = (namaste::is_icd11_code(first) && is_namaste_code(second))
+        || (is_namaste_code(first) && namaste::is_icd11_code(second));
+    if !paired {
+        return Err(format!("\"{}\" is not a NAMASTE / ICD-11 code pair", code));
+    }
+    Ok(format!("{} ({})", first, second))
+}
+
+fn parse_row(record: &csv::StringRecord) -> Result<NamasteCode, String> {
+    if record.len() < 6 {
+        return Err(format!("expected {} columns, found {}", COLUMNS.len(), record.len()));
+    }
+    let field = |i: usize| record.get(i).unwrap_or_default();
+    let number = |i: usize| {
+        field(i).trim().parse::<i32>().map_err(|_| format!("{} \"{}\" is not a number", COLUMNS[i], field(i)))
+    };
+
+    let devanagari = clean_text(field(5));
+    if !devanagari.chars().any(|c| ('\u{0900}'..='\u{097F}').contains(&c)) {
+        return Err(format!("{} \"{}\" contains no Devanagari", COLUMNS[5], devanagari));
+    }
+
+    Ok(NamasteCode {
+        sr_no: number(0)?,
+        namc_id: number(1)?,
+        namc_code: normalise_code(field(2))?,
+        namc_term: clean_text(field(3)),
+        namc_term_diacritical: clean_text(field(4)),
+        namc_term_devanagari: devanagari,
+        short_definition: optional_text(field(6)),
+        long_definition: optional_text(field(7)),
+        ontology_branches: optional_text(field(8)),
+    })
+}
+
+// Parse the sheet. Rows are matched to columns by position; a leading header row
+// (such as the one pandas leaves: "field_1,field_1_1,AYU,...") is skipped
+pub fn parse_namaste_csv<R: std::io::Read>(input: R) -> Result<ParsedSheet, Box<dyn std::error::Error>> {
+    let mut reader = csv::ReaderBuilder::new().has_headers(false).flexible(true).from_reader(input);
+    let mut sheet = ParsedSheet::default();
+    let mut seen_codes: HashMap<String, usize> = HashMap::new();
+    let mut seen_ids: HashMap<i32, usize> = HashMap::new();
+
+    for (index, record) in reader.records().enumerate() {
+        let record = record?;
+        let line = index + 1;
+        if index == 0 && record.get(0).is_some_and(|first| first.trim().parse::<i32>().is_err()) {
+            continue;
+        }
+        sheet.rows_read += 1;
+
+        let code = match parse_row(&record) {
+            Ok(code) => code,
+            Err(message) => {
+                let raw_code = record.get(2).map(clean_text);
+                sheet.errors.push(RowIssue { line, code: raw_code, message });
+                continue;
+            },
+        };
+
+        let nam_code = code.parse_codes().0;
+        if let Some(first) = seen_codes.get(&nam_code) {
+            sheet.errors.push(RowIssue { line, code: Some(nam_code), message: format!("duplicate code, first seen on line {}", first) });
+            continue;
+        }
+        if let Some(first) = seen_ids.get(&code.namc_id) {
+            sheet.errors.push(RowIssue { line, code: Some(nam_code), message: format!("duplicate NAMC_ID {}, first seen on line {}", code.namc_id, first) });
+            continue;
+        }
+        if code.namc_term.is_empty() {
+            sheet.warnings.push(RowIssue { line, code: Some(nam_code.clone()), message: "NAMC_term is empty".to_string() });
+        }
+        seen_codes.insert(nam_code, line);
+        seen_ids.insert(code.namc_id, line);
+        sheet.codes.push(code);
+    }
+
+    // Every concept below the root should hang off another concept in the sheet
+    let known: HashSet<String> = sheet.codes.iter().map(|code| code.parse_codes().0).collect();
+    for code in &sheet.codes {
+        let nam_code = code.parse_codes().0;
+        if !namaste::ancestor_candidates(&nam_code).is_empty() && namaste::parent_code(&nam_code, &known).is_none() {
+            sheet.warnings.push(RowIssue { line: 0, code: Some(nam_code), message: "no parent concept in the sheet".to_string() });
+        }
+    }
+
+    Ok(sheet)
+}
+
+// Fields of a concept that differ between two versions
+fn changed_fields(old: &NamasteCode, new: &NamasteCode) -> Vec<&'static str> {
+    let mut fields = Vec::new();
+    let pairs: [(&'static str, bool); 8] = [
+        ("NAMC_CODE", old.namc_code != new.namc_code),
+        ("NAMC_ID", old.namc_id != new.namc_id),
+        ("NAMC_term", old.namc_term != new.namc_term),
+        ("NAMC_term_diacritical", old.namc_term_diacritical != new.namc_term_diacritical),
+        ("NAMC_term_DEVANAGARI", old.namc_term_devanagari != new.namc_term_devanagari),
+        ("Short_definition", old.short_definition != new.short_definition),
+        ("Long_definition", old.long_definition != new.long_definition),
+        ("Ontology_branches", old.ontology_branches != new.ontology_branches),
+    ];
+    for (name, differs) in pairs {
+        if differs {
+            fields.push(name);
+        }
+    }
+    fields
+}
+
+// Compare by NAMASTE code; the Sr_No ordering column is not part of the concept
+pub fn diff(current: &[NamasteCode], incoming: &[NamasteCode]) -> DiffSummary {
+    let current: BTreeMap<String, &NamasteCode> = current.iter().map(|c| (c.parse_codes().0, c)).collect();
+    let incoming: BTreeMap<String, &NamasteCode> = incoming.iter().map(|c| (c.parse_codes().0, c)).collect();
+    let mut summary = DiffSummary::default();
+
+    for (code, new) in &incoming {
+        match current.get(code) {
+            None => {
+                summary.added += 1;
+                if summary.added_codes.len() < DIFF_SAMPLE {
+                    summary.added_codes.push(code.clone());
+                }
+            },
+            Some(old) => {
+                let fields = changed_fields(old, new);
+                if fields.is_empty() {
+                    summary.unchanged += 1;
+                } else {
+                    summary.changed += 1;
+                    if summary.changed_codes.len() < DIFF_SAMPLE {
+                        summary.changed_codes.push(ChangedCode { code: code.clone(), fields });
+                    }
+                }
+            },
+        }
+    }
+    for code in current.keys().filter(|code| !incoming.contains_key(*code)) {
+        summary.removed += 1;
+        if summary.removed_codes.len() < DIFF_SAMPLE {
+            summary.removed_codes.push(code.clone());
+        }
+    }
+    summary
+}
+
+// Console summary for the import subcommand
+pub fn print_report(report: &ImportReport) {
+    println!("📄 Source: {}", report.source);
+    println!("📊 Rows read: {}, valid: {}, invalid: {}, warnings: {}",
+        report.rows_read, report.rows_valid, report.errors.len(), report.warnings.len());
+    for issue in &report.errors {
+        println!("   ❌ line {} {}: {}", issue.line, issue.code.as_deref().unwrap_or("-"), issue.message);
+    }
+    for issue in report.warnings.iter().take(DIFF_SAMPLE) {
+        println!("   ⚠️  line {} {}: {}", issue.line, issue.code.as_deref().unwrap_or("-"), issue.message);
+    }
+    let diff = &report.diff;
+    println!("🔀 Diff: +{} added, -{} removed, ~{} changed, {} unchanged", diff.added, diff.removed, diff.changed, diff.unchanged);
+    for code in &diff.added_codes {
+        println!("   + {}", code);
+    }
+    for code in &diff.removed_codes {
+        println!("   - {}", code);
+    }
+    for changed in &diff.changed_codes {
+        println!("   ~ {} ({})", changed.code, changed.fields.join(", "));
+    }
+    match (&report.collection, report.dry_run) {
+        (_, true) => println!("🧪 Dry run: nothing was written"),
+        (Some(collection), false) => println!("✅ Imported version {} into {} ({} embeddings kept)",
+            report.version.unwrap_or_default(), collection, report.embeddings_kept),
+        (None, false) => println!("❌ Nothing was imported"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHEET: &str = "field_1,field_1_1,AYU,vyAdhi-viniScayaH,vyādhi-viniścayaḥ,व्याधि-विनिश्चयः,Unnamed: 6,Unnamed: 7,Unnamed: 8\n\
+        1,1,AYU,vyAdhi-viniScayaH,vyādhi-viniścayaḥ,व्याधि-विनिश्चयः,,,\n\
+        2,2,A,doShavaiShamyam,dōṣavaiṣamyam,दोषवैषम्यम्,,,\n\
+        3,3,AAA,doShAvasthA,dōṣāvasthā,दोषावस्था,,,\n\
+        4,4,SR13(AAA-3),vAtaprakopaH,vātaprakōpaḥ,वातप्रकोपः,,\"It is  characterised\",\n\
+        5,5,SR10\u{a0} (AAA-2.1),,vāta,वात,,,\n\
+        6,6,AAA-3,dup,dup,वात,,,\n\
+        7,7,GGGGGQ,GGGGQ,GGGGQ,GGGQ,QGGGGQ,GGGQ,GGGQ\n";
+
+    #[test]
+    fn test_parse_validates_and_normalises_rows() {
+        let sheet = parse_namaste_csv(SHEET.as_bytes()).unwrap();
+
+        assert_eq!(sheet.rows_read, 7);
+        assert_eq!(sheet.codes.len(), 5);
+        assert_eq!(sheet.codes[3].namc_code, "SR13 (AAA-3)");
+        assert_eq!(sheet.codes[3].long_definition.as_deref(), Some("It is characterised"));
+        assert_eq!(sheet.codes[4].namc_code, "SR10 (AAA-2.1)");
+        assert_eq!(sheet.codes[0].short_definition, None);
+
+        let errors: Vec<&str> = sheet.errors.iter().map(|e| e.message.as_str()).collect();
+        assert!(errors[0].starts_with("duplicate code"));
+        assert!(errors[1].contains("no Devanagari"));
+        // AAA-2.1 has no AAA-2 here but AAA is an ancestor; the only warning is the empty term
+        assert_eq!(sheet.warnings.len(), 1);
+        assert_eq!(sheet.warnings[0].message, "NAMC_term is empty");
+    }
+
+    #[test]
+    fn test_uploads_are_staged_outside_the_job() {
+        let dir = std::env::temp_dir().join(format!("namaste-uploads-{}", uuid::Uuid::new_v4()));
+        let upload = stage_upload(&dir, SHEET).unwrap();
+        assert!(!upload.to_string().contains("vyAdhi"));
+        assert_eq!(upload["bytes"], SHEET.len());
+        assert_eq!(read_upload(&upload).unwrap(), SHEET);
+
+        std::fs::write(upload["path"].as_str().unwrap(), "1,1,AYU,changed,,,,,\n").unwrap();
+        assert!(read_upload(&upload).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_diff_by_namaste_code() {
+        let sheet = parse_namaste_csv(SHEET.as_bytes()).unwrap();
+        let mut incoming = sheet.codes.clone();
+        incoming.remove(2);
+        incoming[2].namc_term = "vAtakopaH".to_string();
+        let mut added = incoming[0].clone();
+        added.namc_code = "AAB".to_string();
+        added.namc_id = 99;
+        incoming.push(added);
+
+        let summary = diff(&sheet.codes, &incoming);
+        assert_eq!((summary.added, summary.removed, summary.changed, summary.unchanged), (1, 1, 1, 3));
+        assert_eq!(summary.removed_codes, vec!["AAA"]);
+        assert_eq!(summary.changed_codes[0].code, "AAA-3");
+        assert_eq!(summary.changed_codes[0].fields, vec!["NAMC_term"]);
+    }
+}
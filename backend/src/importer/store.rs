@@ -0,0 +1,66 @@
+use std::collections::{HashMap, HashSet};
+use serde_json::json;
+
+use crate::codecs::namaste::{Language, NamasteCode, NamasteFilter};
+use crate::jobs::{self, JobKind};
+use crate::fulltext;
+use crate::repository;
+use crate::vector;
+use super::{changed_fields, diff, parse_namaste_csv, ImportOptions, ImportReport};
+
+// Parse the sheet, diff it against the active codes and, unless this is a dry run, swap it
+// in as a new version through the terminology storage. Readers see either the old or the
+// new codes. Embeddings of unchanged concepts are carried over
+pub async fn import_namaste<R: std::io::Read>(
+    source: &str,
+    input: R,
+    options: ImportOptions,
+) -> Result<ImportReport, Box<dyn std::error::Error>> {
+    let sheet = parse_namaste_csv(input)?;
+    let repository = repository::get();
+    let every_code = NamasteFilter { code: None, language: Language::Both, search_term: None };
+    let current = repository.namaste_search(&every_code, None).await?;
+
+    let mut report = ImportReport {
+        source: source.to_string(),
+        dry_run: options.dry_run,
+        version: None,
+        collection: None,
+        rows_read: sheet.rows_read,
+        rows_valid: sheet.codes.len(),
+        diff: diff(&current, &sheet.codes),
+        errors: sheet.errors,
+        warnings: sheet.warnings,
+        embeddings_kept: 0,
+    };
+    if options.dry_run || (options.strict && !report.errors.is_empty()) || sheet.codes.is_empty() {
+        return Ok(report);
+    }
+
+    let current: HashMap<String, &NamasteCode> = current.iter().map(|c| (c.parse_codes().0, c)).collect();
+    let unchanged: HashSet<String> = sheet
+        .codes
+        .iter()
+        .map(|code| (code.parse_codes().0, code))
+        .filter(|(key, code)| current.get(key).is_some_and(|old| changed_fields(old, code).is_empty()))
+        .map(|(key, _)| key)
+        .collect();
+    let written = repository.replace_namaste(&sheet.codes, &unchanged, source, report.errors.len()).await?;
+    report.embeddings_kept = written.embeddings_kept;
+
+    // The swap dropped the embeddings of changed concepts, so the NAMASTE vector index is rebuilt
+    actix_web::rt::spawn(async {
+        if let Err(e) = vector::load(&vector::NAMASTE).await {
+            println!("⚠️  Vector index namaste failed to reload: {}", e);
+        }
+    });
+    fulltext::spawn_reload_namaste();
+    let autocomplete = json!({ "categories": ["namaste"], "full": false });
+    if let Err(e) = jobs::start(JobKind::Autocomplete, autocomplete).await {
+        println!("⚠️  Autocomplete not refreshed after import ({}); POST /autocomplete/initialize to pick it up", e);
+    }
+
+    report.version = Some(written.version);
+    report.collection = written.collection;
+    Ok(report)
+}
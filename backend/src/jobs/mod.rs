@@ -0,0 +1,282 @@
+// Long-running work (embedding generation, autocomplete indexing, imports) as background
+// jobs. Each job is persisted with its progress and a checkpoint it writes as it goes, can
+// be cancelled, and is resumed from that checkpoint when the server restarts mid-run
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use chrono::Utc;
+use mongodb::bson::{self, doc};
+use serde_json::{json, Value};
+
+use crate::codecs::jobs::{JobCodec, JobRecord};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobKind {
+    Embeddings,
+    Autocomplete,
+    ImportNamaste,
+}
+
+impl JobKind {
+    pub const ALL: [JobKind; 3] = [JobKind::Embeddings, JobKind::Autocomplete, JobKind::ImportNamaste];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobKind::Embeddings => "embeddings",
+            JobKind::Autocomplete => "autocomplete",
+            JobKind::ImportNamaste => "import_namaste",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<JobKind> {
+        JobKind::ALL.into_iter().find(|k| k.as_str() == kind)
+    }
+}
+
+// Returned by a job that stopped because it was asked to
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "job was cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+// What a running job sees: its parameters, the checkpoint to resume from, and ways to
+// report progress and notice cancellation
+pub struct JobContext {
+    pub id: String,
+    pub params: Value,
+    pub checkpoint: Option<Value>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl JobContext {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::SeqCst)
+    }
+
+    // Err(Cancelled) once cancellation was requested; call between units of work
+    pub fn ensure_active(&self) -> anyhow::Result<()> {
+        if self.is_cancelled() {
+            return Err(Cancelled.into());
+        }
+        Ok(())
+    }
+
+    // Persist progress and, when given, the checkpoint a resumed run starts from. Failing to
+    // record progress does not stop the job
+    pub async fn report(&self, total: usize, processed: usize, detail: Value, checkpoint: Option<Value>) {
+        let mut fields = doc! {
+            "total": total as i64,
+            "processed": processed as i64,
+            "detail": bson::to_bson(&detail).unwrap_or_default(),
+        };
+        if let Some(checkpoint) = checkpoint {
+            fields.insert("checkpoint", bson::to_bson(&checkpoint).unwrap_or_default());
+        }
+        if let Err(e) = JobCodec::new().update(&self.id, fields).await {
+            println!("⚠️  Could not record progress of job {}: {}", self.id, e);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum StartError {
+    // A job of this kind is already queued or running
+    AlreadyRunning(String),
+    Storage(String),
+}
+
+impl std::fmt::Display for StartError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StartError::AlreadyRunning(id) => write!(f, "job {} of this kind is already running", id),
+            StartError::Storage(e) => write!(f, "could not persist the job: {}", e),
+        }
+    }
+}
+
+// Jobs of this process by id, with their kind and cancellation flag
+type Active = HashMap<String, (JobKind, Arc<AtomicBool>)>;
+
+static ACTIVE: OnceLock<Mutex<Active>> = OnceLock::new();
+
+fn active() -> &'static Mutex<Active> {
+    ACTIVE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// Claim the slot for a kind: one job of each kind at a time
+fn claim(kind: JobKind, id: &str) -> Result<Arc<AtomicBool>, StartError> {
+    let mut active = active().lock().unwrap();
+    if let Some((running, _)) = active.iter().find(|(_, (k, _))| *k == kind) {
+        return Err(StartError::AlreadyRunning(running.clone()));
+    }
+    let cancel = Arc::new(AtomicBool::new(false));
+    active.insert(id.to_string(), (kind, cancel.clone()));
+    Ok(cancel)
+}
+
+// Queue a job and run it in the background
+pub async fn start(kind: JobKind, params: Value) -> Result<JobRecord, StartError> {
+    let id = uuid::Uuid::new_v4().to_string();
+    let cancel = claim(kind, &id)?;
+    let now = Utc::now().to_rfc3339();
+    let record = JobRecord {
+        job_id: id.clone(),
+        kind: kind.as_str().to_string(),
+        params,
+        state: "queued".to_string(),
+        total: 0,
+        processed: 0,
+        detail: json!({}),
+        checkpoint: None,
+        result: None,
+        error: None,
+        attempts: 0,
+        created_at: now.clone(),
+        started_at: None,
+        updated_at: now,
+        finished_at: None,
+    };
+    if let Err(e) = JobCodec::new().insert(&record).await {
+        active().lock().unwrap().remove(&id);
+        return Err(StartError::Storage(e.to_string()));
+    }
+    println!("🧵 Job {} ({}) queued", id, kind.as_str());
+    spawn(kind, record.clone(), cancel);
+    Ok(record)
+}
+
+// Ask a running job to stop. False when this process is not running it
+pub async fn cancel(job_id: &str) -> bool {
+    let flag = active().lock().unwrap().get(job_id).map(|(_, cancel)| cancel.clone());
+    match flag {
+        Some(cancel) => {
+            cancel.store(true, Ordering::SeqCst);
+            if let Err(e) = JobCodec::new().update(job_id, doc! { "state": "cancelling" }).await {
+                println!("⚠️  Could not record cancellation of job {}: {}", job_id, e);
+            }
+            true
+        },
+        None => false,
+    }
+}
+
+fn spawn(kind: JobKind, record: JobRecord, cancel: Arc<AtomicBool>) {
+    actix_web::rt::spawn(async move {
+        let codec = JobCodec::new();
+        let id = record.job_id.clone();
+        let started = doc! {
+            "state": "running",
+            "started_at": Utc::now().to_rfc3339(),
+            "attempts": record.attempts + 1,
+        };
+        if let Err(e) = codec.update(&id, started).await {
+            println!("⚠️  Could not record start of job {}: {}", id, e);
+        }
+
+        let context = JobContext {
+            id: id.clone(),
+            params: record.params,
+            checkpoint: record.checkpoint,
+            cancel,
+        };
+        let outcome = run(kind, &context).await;
+
+        let mut finished = doc! { "finished_at": Utc::now().to_rfc3339() };
+        match &outcome {
+            Ok(result) => {
+                println!("✅ Job {} ({}) completed", id, kind.as_str());
+                finished.insert("state", "completed");
+                finished.insert("result", bson::to_bson(result).unwrap_or_default());
+            },
+            Err(e) if e.is::<Cancelled>() => {
+                println!("🛑 Job {} ({}) cancelled", id, kind.as_str());
+                finished.insert("state", "cancelled");
+            },
+            Err(e) => {
+                println!("❌ Job {} ({}) failed: {}", id, kind.as_str(), e);
+                finished.insert("state", "failed");
+                finished.insert("error", e.to_string());
+            },
+        }
+        if let Err(e) = codec.update(&id, finished).await {
+            println!("⚠️  Could not record end of job {}: {}", id, e);
+        }
+        active().lock().unwrap().remove(&id);
+    });
+}
+
+async fn run(kind: JobKind, context: &JobContext) -> anyhow::Result<Value> {
+    match kind {
+        JobKind::Embeddings => crate::gemini::embedding::run_job(context).await,
+        JobKind::Autocomplete => crate::autocomplete::run_job(context).await,
+        JobKind::ImportNamaste => crate::importer::run_job(context).await,
+    }
+}
+
+// Pick up jobs a previous process did not finish, from their last checkpoint
+pub fn spawn_resume_interrupted() {
+    actix_web::rt::spawn(async {
+        let codec = JobCodec::new();
+        let jobs = match codec.unfinished().await {
+            Ok(jobs) => jobs,
+            Err(e) => {
+                println!("⚠️  Could not look for interrupted jobs: {}", e);
+                return;
+            },
+        };
+        for record in jobs {
+            let id = record.job_id.clone();
+            let (state, error) = match JobKind::parse(&record.kind) {
+                // Cancellation was asked for before the restart
+                _ if record.state == "cancelling" => ("cancelled", None),
+                None => ("failed", Some(format!("unknown job kind {}", record.kind))),
+                Some(kind) => match claim(kind, &id) {
+                    Ok(cancel) => {
+                        println!("🔁 Resuming job {} ({}) from its checkpoint", id, kind.as_str());
+                        spawn(kind, record, cancel);
+                        continue;
+                    },
+                    Err(e) => ("failed", Some(format!("not resumed: {}", e))),
+                },
+            };
+            let mut fields = doc! { "state": state, "finished_at": Utc::now().to_rfc3339() };
+            if let Some(error) = error {
+                fields.insert("error", error);
+            }
+            if let Err(e) = codec.update(&id, fields).await {
+                println!("⚠️  Could not close interrupted job {}: {}", id, e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_one_job_per_kind_and_cancellation_is_recognised() {
+        for kind in JobKind::ALL {
+            assert_eq!(JobKind::parse(kind.as_str()), Some(kind));
+        }
+        assert_eq!(JobKind::parse("reboot"), None);
+
+        let cancel = claim(JobKind::ImportNamaste, "first").unwrap();
+        assert!(matches!(claim(JobKind::ImportNamaste, "second"), Err(StartError::AlreadyRunning(id)) if id == "first"));
+        active().lock().unwrap().remove("first");
+        assert!(claim(JobKind::ImportNamaste, "second").is_ok());
+        active().lock().unwrap().remove("second");
+
+        let context = JobContext { id: "first".to_string(), params: json!({}), checkpoint: None, cancel };
+        assert!(context.ensure_active().is_ok());
+        context.cancel.store(true, Ordering::SeqCst);
+        assert!(context.ensure_active().unwrap_err().is::<Cancelled>());
+    }
+}
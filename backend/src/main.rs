@@ -1,10 +1,57 @@
 mod server;
+mod config;
 mod dbcodes;
 mod api;
 mod codecs;
 mod gemini;
+mod embedding;
+mod fhir;
+mod auth;
+mod audit;
+mod importer;
+mod vector;
+mod fulltext;
+mod autocomplete;
+mod jobs;
+mod repository;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    if let Err(e) = config::init() {
+        return Err(std::io::Error::other(format!("Invalid configuration: {}", e)));
+    }
+    if let Err(e) = repository::init() {
+        return Err(std::io::Error::other(format!("Terminology storage failed to open: {}", e)));
+    }
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("import") {
+        return run_import(&args[1..]).await;
+    }
     server::start_server().await
 }
+
+// backend import [--dry-run] [--strict] [path/to/namaste.csv]
+async fn run_import(args: &[String]) -> std::io::Result<()> {
+    let mut options = importer::ImportOptions::default();
+    let mut path = config::get().files.namaste_csv.clone();
+    for arg in args {
+        match arg.as_str() {
+            "--dry-run" => options.dry_run = true,
+            "--strict" => options.strict = true,
+            flag if flag.starts_with("--") => {
+                return Err(std::io::Error::other(format!("unknown option {} (expected --dry-run, --strict)", flag)));
+            },
+            file => path = file.to_string(),
+        }
+    }
+
+    let file = std::fs::File::open(&path)?;
+    let report = importer::import_namaste(&path, file, options)
+        .await
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+    importer::print_report(&report);
+    if options.strict && !report.errors.is_empty() {
+        return Err(std::io::Error::other("strict import refused: the sheet has invalid rows"));
+    }
+    Ok(())
+}
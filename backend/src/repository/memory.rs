@@ -0,0 +1,375 @@
+// The bundled CSVs held in memory (files.icd_csv / files.namaste_csv), for running and
+// testing the API without a database. Embeddings written here last until the process exits
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+use futures::future::{self, BoxFuture};
+use mongodb::bson::{self, Document};
+
+use crate::codecs::icd::{IcdCode, IcdDiscipline, IcdFilter};
+use crate::codecs::namaste::{NamasteCode, NamasteFilter};
+use crate::embedding::stored::StoredEmbedding;
+use crate::importer;
+use crate::vector::{self, IndexSpec};
+use super::{EmbeddingState, KeyedVectors, NamasteVersion, TerminologyRepository};
+
+// The WHO export sample, with the columns of the Mongo documents (see csvs/ICD-11/fields.txt)
+pub const DEFAULT_ICD_CSV: &str = "csvs/ICD-11/sample icd .csv - Sheet1.csv";
+
+#[derive(Default)]
+struct Embeddings {
+    models: HashMap<String, StoredEmbedding>,
+    // Model keys whose last attempt failed
+    failed: HashSet<String>,
+}
+
+// The NAMASTE codes of one version, replaced whole by an import
+#[derive(Default)]
+struct NamasteSheet {
+    // In sheet order
+    codes: Vec<NamasteCode>,
+    keys: HashMap<String, usize>,
+    // 0 for the sheet loaded at startup
+    version: i64,
+}
+
+impl NamasteSheet {
+    fn new(mut codes: Vec<NamasteCode>, version: i64) -> Self {
+        codes.sort_by_key(|code| code.sr_no);
+        let keys = codes.iter().enumerate().map(|(i, code)| (code.namc_id.to_string(), i)).collect();
+        NamasteSheet { codes, keys, version }
+    }
+}
+
+pub struct MemoryRepository {
+    icd: Vec<IcdCode>,
+    icd_keys: HashMap<String, usize>,
+    namaste: RwLock<NamasteSheet>,
+    // By index name, then key
+    embeddings: RwLock<HashMap<&'static str, HashMap<String, Embeddings>>>,
+}
+
+fn contains(value: &str, needle: &str) -> bool {
+    value.to_lowercase().contains(needle)
+}
+
+fn icd_matches(filter: &IcdFilter, code: &IcdCode) -> bool {
+    let term = filter.search_term.as_ref().map(|term| term.to_lowercase());
+    filter.discipline.as_ref().is_none_or(|d| contains(&code.id, d.id_pattern()))
+        && filter.parent_filter.as_ref().is_none_or(|p| code.parent.as_ref() == Some(p))
+        && term.is_none_or(|term| {
+            contains(&code.title, &term) || contains(code.definition.as_deref().unwrap_or_default(), &term) || contains(&code.code, &term)
+        })
+}
+
+fn namaste_matches(filter: &NamasteFilter, code: &NamasteCode) -> bool {
+    let term = filter.search_term.as_ref().map(|term| term.to_lowercase());
+    filter.code.as_ref().is_none_or(|c| contains(&code.namc_code, &c.to_lowercase()))
+        && term.is_none_or(|term| {
+            [&code.namc_term, &code.namc_term_diacritical, &code.namc_term_devanagari, &code.namc_code]
+                .iter()
+                .any(|field| contains(field, &term))
+        })
+}
+
+fn limited<T: Clone>(matches: impl Iterator<Item = T>, limit: Option<usize>) -> Vec<T> {
+    matches.take(limit.unwrap_or(usize::MAX)).collect()
+}
+
+fn page<T: Clone>(matches: Vec<&T>, offset: usize, count: usize) -> (Vec<T>, u64) {
+    let total = matches.len() as u64;
+    (matches.into_iter().skip(offset).take(count).cloned().collect(), total)
+}
+
+// Entities by column name; rows without an entity URI are not concepts
+fn parse_icd_csv<R: std::io::Read>(input: R) -> anyhow::Result<Vec<IcdCode>> {
+    let mut reader = csv::Reader::from_reader(input);
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| headers.iter().position(|header| header.trim() == name);
+    let Some(id_column) = column("id") else {
+        anyhow::bail!("the ICD-11 sheet has no id column");
+    };
+    let columns: Vec<Option<usize>> = ["code", "title", "definition", "parent", "browserUrl", "codingNote", "synonyms", "exclusions", "inclusions", "isLeaf"]
+        .iter()
+        .map(|name| column(name))
+        .collect();
+
+    let mut codes = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let field = |i: usize| columns[i].and_then(|c| record.get(c)).map(str::trim).unwrap_or_default().to_string();
+        let optional = |i: usize| Some(field(i)).filter(|value| !value.is_empty());
+        let id = record.get(id_column).map(str::trim).unwrap_or_default();
+        if id.is_empty() {
+            continue;
+        }
+        codes.push(IcdCode {
+            id: id.to_string(),
+            code: field(0),
+            title: field(1),
+            definition: optional(2),
+            parent: optional(3),
+            browser_url: optional(4),
+            coding_note: optional(5),
+            synonyms: optional(6),
+            exclusions: optional(7),
+            inclusions: optional(8),
+            is_leaf: optional(9),
+        });
+    }
+    Ok(codes)
+}
+
+impl MemoryRepository {
+    // NAMASTE rows go through the importer's validation; invalid ones are left out
+    pub fn from_csv<I: std::io::Read, N: std::io::Read>(icd: I, namaste: N) -> anyhow::Result<Self> {
+        let icd = parse_icd_csv(icd)?;
+        let sheet = importer::parse_namaste_csv(namaste).map_err(|e| anyhow::anyhow!("{}", e))?;
+        if !sheet.errors.is_empty() {
+            println!("⚠️  {} NAMASTE rows left out as invalid", sheet.errors.len());
+        }
+
+        Ok(MemoryRepository {
+            icd_keys: icd.iter().enumerate().map(|(i, code)| (code.id.clone(), i)).collect(),
+            icd,
+            namaste: RwLock::new(NamasteSheet::new(sheet.codes, 0)),
+            embeddings: RwLock::new(HashMap::new()),
+        })
+    }
+
+    pub fn load(icd_path: &str, namaste_path: &str) -> anyhow::Result<Self> {
+        let open = |path: &str| std::fs::File::open(path).map_err(|e| anyhow::anyhow!("{} could not be read: {}", path, e));
+        Self::from_csv(open(icd_path)?, open(namaste_path)?)
+    }
+
+    pub fn icd_count(&self) -> usize {
+        self.icd.len()
+    }
+
+    pub fn namaste_count(&self) -> usize {
+        self.namaste.read().unwrap().codes.len()
+    }
+
+    // The concept behind a key, as its stored document
+    fn document(&self, spec: &IndexSpec, key: &str) -> Option<Document> {
+        if spec.name == vector::ICD.name {
+            self.icd_keys.get(key).and_then(|&i| bson::to_document(&self.icd[i]).ok())
+        } else {
+            let namaste = self.namaste.read().unwrap();
+            namaste.keys.get(key).and_then(|&i| bson::to_document(&namaste.codes[i]).ok())
+        }
+    }
+
+    fn knows(&self, spec: &IndexSpec, key: &str) -> bool {
+        if spec.name == vector::ICD.name { self.icd_keys.contains_key(key) } else { self.namaste.read().unwrap().keys.contains_key(key) }
+    }
+}
+
+impl TerminologyRepository for MemoryRepository {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn icd_search<'a>(&'a self, filter: &'a IcdFilter, limit: Option<usize>) -> BoxFuture<'a, anyhow::Result<Vec<IcdCode>>> {
+        let matches = self.icd.iter().filter(|code| icd_matches(filter, code)).cloned();
+        Box::pin(future::ready(Ok(limited(matches, limit))))
+    }
+
+    fn icd_page<'a>(&'a self, filter: &'a IcdFilter, offset: usize, count: usize) -> BoxFuture<'a, anyhow::Result<(Vec<IcdCode>, u64)>> {
+        let matches = self.icd.iter().filter(|code| icd_matches(filter, code)).collect();
+        Box::pin(future::ready(Ok(page(matches, offset, count))))
+    }
+
+    fn icd_by_id<'a>(&'a self, id: &'a str) -> BoxFuture<'a, anyhow::Result<Option<IcdCode>>> {
+        let found = self.icd_keys.get(id).map(|&i| self.icd[i].clone());
+        Box::pin(future::ready(Ok(found)))
+    }
+
+    fn icd_by_code<'a>(&'a self, code: &'a str, discipline: Option<&'a IcdDiscipline>) -> BoxFuture<'a, anyhow::Result<Option<IcdCode>>> {
+        let found = self
+            .icd
+            .iter()
+            .find(|entity| entity.concept_code() == code && discipline.is_none_or(|d| contains(&entity.id, d.id_pattern())))
+            .cloned();
+        Box::pin(future::ready(Ok(found)))
+    }
+
+    fn icd_children<'a>(&'a self, parent_ids: &'a [String]) -> BoxFuture<'a, anyhow::Result<Vec<IcdCode>>> {
+        let children = self
+            .icd
+            .iter()
+            .filter(|code| code.parent.as_ref().is_some_and(|parent| parent_ids.contains(parent)))
+            .cloned()
+            .collect();
+        Box::pin(future::ready(Ok(children)))
+    }
+
+    fn namaste_search<'a>(&'a self, filter: &'a NamasteFilter, limit: Option<usize>) -> BoxFuture<'a, anyhow::Result<Vec<NamasteCode>>> {
+        let namaste = self.namaste.read().unwrap();
+        let matches = namaste.codes.iter().filter(|code| namaste_matches(filter, code)).cloned();
+        Box::pin(future::ready(Ok(limited(matches, limit))))
+    }
+
+    fn namaste_page<'a>(&'a self, filter: &'a NamasteFilter, offset: usize, count: usize) -> BoxFuture<'a, anyhow::Result<(Vec<NamasteCode>, u64)>> {
+        let namaste = self.namaste.read().unwrap();
+        let matches = namaste.codes.iter().filter(|code| namaste_matches(filter, code)).collect();
+        Box::pin(future::ready(Ok(page(matches, offset, count))))
+    }
+
+    fn namaste_by_code<'a>(&'a self, code: &'a str) -> BoxFuture<'a, anyhow::Result<Option<NamasteCode>>> {
+        let found = self.namaste.read().unwrap().codes.iter().find(|candidate| candidate.parse_codes().0 == code).cloned();
+        Box::pin(future::ready(Ok(found)))
+    }
+
+    // Embeddings are kept by NAMC_ID, so those of unchanged concepts move to their new key
+    fn replace_namaste<'a>(&'a self, codes: &'a [NamasteCode], unchanged: &'a HashSet<String>, _source: &'a str, _skipped: usize) -> BoxFuture<'a, anyhow::Result<NamasteVersion>> {
+        let mut namaste = self.namaste.write().unwrap();
+        let mut embeddings = self.embeddings.write().unwrap();
+        let mut previous = embeddings.remove(vector::NAMASTE.name).unwrap_or_default();
+        let old_ids: HashMap<String, String> = namaste.codes.iter().map(|old| (old.parse_codes().0, old.namc_id.to_string())).collect();
+        let mut kept = HashMap::new();
+        for code in codes {
+            let key = code.parse_codes().0;
+            if unchanged.contains(&key) && let Some(stored) = old_ids.get(&key).and_then(|id| previous.remove(id)) {
+                kept.insert(code.namc_id.to_string(), stored);
+            }
+        }
+        let embeddings_kept = kept.len();
+        embeddings.insert(vector::NAMASTE.name, kept);
+        *namaste = NamasteSheet::new(codes.to_vec(), namaste.version + 1);
+        Box::pin(future::ready(Ok(NamasteVersion { version: namaste.version, collection: None, embeddings_kept })))
+    }
+
+    fn documents<'a>(&'a self, spec: &'a IndexSpec, keys: &'a [String]) -> BoxFuture<'a, anyhow::Result<HashMap<String, Document>>> {
+        let documents = keys
+            .iter()
+            .filter_map(|key| Some((key.clone(), self.document(spec, key)?)))
+            .collect();
+        Box::pin(future::ready(Ok(documents)))
+    }
+
+    fn embeddings<'a>(&'a self, spec: &'a IndexSpec, model_key: &'a str) -> BoxFuture<'a, anyhow::Result<KeyedVectors>> {
+        let embeddings = self.embeddings.read().unwrap();
+        let vectors = embeddings
+            .get(spec.name)
+            .into_iter()
+            .flatten()
+            .filter_map(|(key, stored)| Some((key.clone(), stored.models.get(model_key)?.vector.clone())))
+            .collect();
+        Box::pin(future::ready(Ok(vectors)))
+    }
+
+    fn embedding_states<'a>(&'a self, spec: &'a IndexSpec, keys: &'a [String], model_key: &'a str) -> BoxFuture<'a, anyhow::Result<HashMap<String, EmbeddingState>>> {
+        let embeddings = self.embeddings.read().unwrap();
+        let stored = embeddings.get(spec.name);
+        let states = keys
+            .iter()
+            .filter(|key| self.knows(spec, key))
+            .map(|key| {
+                let entry = stored.and_then(|stored| stored.get(key));
+                let state = EmbeddingState {
+                    text_hash: entry.and_then(|entry| entry.models.get(model_key)).map(|stored| stored.text_hash.clone()),
+                    failed: entry.is_some_and(|entry| entry.failed.contains(model_key)),
+                };
+                (key.clone(), state)
+            })
+            .collect();
+        Box::pin(future::ready(Ok(states)))
+    }
+
+    fn store_embedding<'a>(&'a self, spec: &'a IndexSpec, key: &'a str, model_key: &'a str, entry: &'a StoredEmbedding) -> BoxFuture<'a, anyhow::Result<bool>> {
+        let known = self.knows(spec, key);
+        if known {
+            let mut embeddings = self.embeddings.write().unwrap();
+            let stored = embeddings.entry(spec.name).or_default().entry(key.to_string()).or_default();
+            stored.models.insert(model_key.to_string(), entry.clone());
+            stored.failed.remove(model_key);
+        }
+        Box::pin(future::ready(Ok(known)))
+    }
+
+    fn record_embedding_failure<'a>(&'a self, spec: &'a IndexSpec, key: &'a str, model_key: &'a str, _message: &'a str) -> BoxFuture<'a, anyhow::Result<()>> {
+        if self.knows(spec, key) {
+            let mut embeddings = self.embeddings.write().unwrap();
+            embeddings.entry(spec.name).or_default().entry(key.to_string()).or_default().failed.insert(model_key.to_string());
+        }
+        Box::pin(future::ready(Ok(())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codecs::namaste::Language;
+    use crate::embedding::hashing::HashingProvider;
+    use crate::embedding::stored;
+
+    #[tokio::test]
+    async fn test_bundled_sheets_answer_searches_lookups_and_embeddings() {
+        let defaults = crate::config::FilesConfig::default();
+        let repository = MemoryRepository::load(&defaults.icd_csv, &defaults.namaste_csv).unwrap();
+        assert!(repository.icd_count() > 30);
+        assert!(repository.namaste_count() > 2000);
+
+        let chapter = "http://id.who.int/icd/release/11/2025-01/mms/1435254666";
+        let filter = IcdFilter { discipline: Some(IcdDiscipline::Biomedicine), search_term: Some("INFECTIOUS".to_string()), parent_filter: None };
+        let found = repository.icd_search(&filter, Some(2)).await.unwrap();
+        assert_eq!(found.len(), 2);
+        assert!(found.iter().all(|code| code.title.to_lowercase().contains("infectious") || code.definition.as_deref().unwrap_or_default().to_lowercase().contains("infectious")));
+        assert_eq!(repository.icd_by_code("1", None).await.unwrap().map(|code| code.id), Some(chapter.to_string()));
+        assert!(repository.icd_by_code("1", Some(&IcdDiscipline::TM2)).await.unwrap().is_none());
+        let children = repository.icd_children(&[chapter.to_string()]).await.unwrap();
+        assert!(children.iter().any(|child| child.title == "Gastroenteritis or colitis of infectious origin"));
+
+        let filter = NamasteFilter { code: None, language: Language::Both, search_term: Some("VIKARA".to_string()) };
+        let (first, total) = repository.namaste_page(&filter, 0, 1).await.unwrap();
+        assert!(total >= 1);
+        assert_eq!(first[0].namc_code, "DIS");
+        assert_eq!(repository.namaste_by_code("DIS").await.unwrap().map(|code| code.namc_id), Some(first[0].namc_id));
+
+        // Embeddings are kept per model and only for known concepts
+        let provider = HashingProvider::new(4);
+        let model_key = stored::key_of(&provider);
+        let key = first[0].namc_id.to_string();
+        let keys = vec![key.clone(), "0".to_string()];
+        repository.record_embedding_failure(&vector::NAMASTE, &key, &model_key, "timed out").await.unwrap();
+        repository.record_embedding_failure(&vector::NAMASTE, &key, "gemini_text-embedding-004", "quota").await.unwrap();
+        let states = repository.embedding_states(&vector::NAMASTE, &keys, &model_key).await.unwrap();
+        assert_eq!(states.len(), 1);
+        assert_eq!(states[&key], EmbeddingState { text_hash: None, failed: true });
+
+        let entry = StoredEmbedding::new(&provider, "vikAraH", vec![0.5, 0.5, 0.5, 0.5]);
+        assert!(repository.store_embedding(&vector::NAMASTE, &key, &model_key, &entry).await.unwrap());
+        assert!(!repository.store_embedding(&vector::NAMASTE, "0", &model_key, &entry).await.unwrap());
+        let states = repository.embedding_states(&vector::NAMASTE, &keys, &model_key).await.unwrap();
+        assert_eq!(states[&key], EmbeddingState { text_hash: Some(stored::text_hash("vikAraH")), failed: false });
+        // Another model's failure outlives this model's success
+        let states = repository.embedding_states(&vector::NAMASTE, &keys, "gemini_text-embedding-004").await.unwrap();
+        assert!(states[&key].failed);
+        assert_eq!(repository.embeddings(&vector::NAMASTE, &model_key).await.unwrap(), vec![(key.clone(), entry.vector.clone())]);
+        assert!(repository.embeddings(&vector::NAMASTE, "gemini_text-embedding-004").await.unwrap().is_empty());
+        assert!(repository.embeddings(&vector::ICD, &model_key).await.unwrap().is_empty());
+        let documents = repository.documents(&vector::NAMASTE, &keys).await.unwrap();
+        assert_eq!(documents[&key].get_str("namc_code").unwrap(), "DIS");
+    }
+
+    #[tokio::test]
+    async fn test_replacing_namaste_keeps_embeddings_of_unchanged_concepts() {
+        let icd = "id,code,title\n";
+        let sheet = "1,10,AAA,jvaraH,jvaraḥ,ज्वरः,,,\n2,11,AAA-1,kAsaH,kāsaḥ,कासः,,,\n";
+        let repository = MemoryRepository::from_csv(icd.as_bytes(), sheet.as_bytes()).unwrap();
+        let provider = HashingProvider::new(4);
+        let model_key = stored::key_of(&provider);
+        for key in ["10", "11"] {
+            let entry = StoredEmbedding::new(&provider, key, vec![1.0, 0.0, 0.0, 0.0]);
+            assert!(repository.store_embedding(&vector::NAMASTE, key, &model_key, &entry).await.unwrap());
+        }
+
+        let next = importer::parse_namaste_csv("1,10,AAA,jvaraH,jvaraḥ,ज्वरः,,,\n2,11,AAA-1,kAsaH,kāsaḥ,कासः,Cough,,\n".as_bytes()).unwrap();
+        let unchanged = HashSet::from(["AAA".to_string()]);
+        let written = repository.replace_namaste(&next.codes, &unchanged, "test", 0).await.unwrap();
+        assert_eq!(written, NamasteVersion { version: 1, collection: None, embeddings_kept: 1 });
+        assert_eq!(repository.namaste_by_code("AAA-1").await.unwrap().unwrap().short_definition.as_deref(), Some("Cough"));
+        let vectors = repository.embeddings(&vector::NAMASTE, &model_key).await.unwrap();
+        assert_eq!(vectors.into_iter().map(|(key, _)| key).collect::<Vec<_>>(), vec!["10".to_string()]);
+    }
+}
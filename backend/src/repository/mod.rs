@@ -0,0 +1,121 @@
+// Terminology storage behind one trait: search, lookup and hierarchy over both code systems,
+// and the stored embeddings. MongoDB in deployments; the bundled CSVs held in memory to run
+// and test the API without a database (terminology.storage)
+pub mod memory;
+pub mod mongo;
+
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+use futures::future::BoxFuture;
+
+use crate::codecs::icd::{IcdCode, IcdDiscipline, IcdFilter};
+use crate::codecs::namaste::{NamasteCode, NamasteFilter};
+use crate::config::{self, AppConfig, Storage};
+use crate::embedding::stored::StoredEmbedding;
+use crate::vector::IndexSpec;
+use memory::MemoryRepository;
+use mongo::MongoRepository;
+
+// Vectors of one model by concept key
+pub type KeyedVectors = Vec<(String, Vec<f32>)>;
+
+// Where a concept stands with one embedding model
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EmbeddingState {
+    // Hash of the text behind its vector of the model, if it has one
+    pub text_hash: Option<String>,
+    // The last attempt to embed it failed
+    pub failed: bool,
+}
+
+// A version of the NAMASTE codes swapped in by an import
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamasteVersion {
+    pub version: i64,
+    // Where the version was written, when the storage keeps each version
+    pub collection: Option<String>,
+    pub embeddings_kept: usize,
+}
+
+// Concepts are identified by their vector index key (see IndexSpec): the entity URI for
+// ICD-11, the NAMC_ID for NAMASTE
+pub trait TerminologyRepository: Send + Sync {
+    // "mongo" or "memory"
+    fn name(&self) -> &'static str;
+
+    // ICD-11 entities matching a filter; the search term is a literal, case-insensitive
+    // substring of the title, definition or code
+    fn icd_search<'a>(&'a self, filter: &'a IcdFilter, limit: Option<usize>) -> BoxFuture<'a, anyhow::Result<Vec<IcdCode>>>;
+    // One page of matches in stable order, plus the total number of matches
+    fn icd_page<'a>(&'a self, filter: &'a IcdFilter, offset: usize, count: usize) -> BoxFuture<'a, anyhow::Result<(Vec<IcdCode>, u64)>>;
+    fn icd_by_id<'a>(&'a self, id: &'a str) -> BoxFuture<'a, anyhow::Result<Option<IcdCode>>>;
+    // By ICD code, or by entity number for entities without a code
+    fn icd_by_code<'a>(&'a self, code: &'a str, discipline: Option<&'a IcdDiscipline>) -> BoxFuture<'a, anyhow::Result<Option<IcdCode>>>;
+    // Direct children of any of the entities
+    fn icd_children<'a>(&'a self, parent_ids: &'a [String]) -> BoxFuture<'a, anyhow::Result<Vec<IcdCode>>>;
+
+    // NAMASTE codes matching a filter; the search term is a literal, case-insensitive
+    // substring of a term or the code
+    fn namaste_search<'a>(&'a self, filter: &'a NamasteFilter, limit: Option<usize>) -> BoxFuture<'a, anyhow::Result<Vec<NamasteCode>>>;
+    // One page of matches in sheet order, plus the total number of matches
+    fn namaste_page<'a>(&'a self, filter: &'a NamasteFilter, offset: usize, count: usize) -> BoxFuture<'a, anyhow::Result<(Vec<NamasteCode>, u64)>>;
+    // By parsed NAMASTE code rather than the raw AYU text
+    fn namaste_by_code<'a>(&'a self, code: &'a str) -> BoxFuture<'a, anyhow::Result<Option<NamasteCode>>>;
+    // Swap in a new version of the NAMASTE codes in one step, keeping the stored embeddings of
+    // the concepts in `unchanged` (by NAMASTE code). `skipped` counts the sheet's invalid rows
+    fn replace_namaste<'a>(&'a self, codes: &'a [NamasteCode], unchanged: &'a HashSet<String>, source: &'a str, skipped: usize) -> BoxFuture<'a, anyhow::Result<NamasteVersion>>;
+
+    // The stored documents of concepts, by key; unknown keys are left out
+    fn documents<'a>(&'a self, spec: &'a IndexSpec, keys: &'a [String]) -> BoxFuture<'a, anyhow::Result<HashMap<String, mongodb::bson::Document>>>;
+    // Every stored vector of a model
+    fn embeddings<'a>(&'a self, spec: &'a IndexSpec, model_key: &'a str) -> BoxFuture<'a, anyhow::Result<KeyedVectors>>;
+    // Embedding state per key with a model; unknown keys are left out
+    fn embedding_states<'a>(&'a self, spec: &'a IndexSpec, keys: &'a [String], model_key: &'a str) -> BoxFuture<'a, anyhow::Result<HashMap<String, EmbeddingState>>>;
+    // Store a vector next to those of other models and clear the model's earlier failure.
+    // False when there is no such concept
+    fn store_embedding<'a>(&'a self, spec: &'a IndexSpec, key: &'a str, model_key: &'a str, entry: &'a StoredEmbedding) -> BoxFuture<'a, anyhow::Result<bool>>;
+    // Record why a model failed to embed a concept, so that a retry of that model's failures
+    // picks it up
+    fn record_embedding_failure<'a>(&'a self, spec: &'a IndexSpec, key: &'a str, model_key: &'a str, message: &'a str) -> BoxFuture<'a, anyhow::Result<()>>;
+}
+
+static REPOSITORY: OnceLock<Box<dyn TerminologyRepository>> = OnceLock::new();
+
+fn open(config: &AppConfig) -> Result<Box<dyn TerminologyRepository>, String> {
+    match config.terminology.storage {
+        Storage::Mongo => Ok(Box::new(MongoRepository)),
+        Storage::Memory => {
+            let files = &config.files;
+            let repository = MemoryRepository::load(&files.icd_csv, &files.namaste_csv).map_err(|e| e.to_string())?;
+            println!("🗂️  Serving {} ICD-11 and {} NAMASTE codes from memory", repository.icd_count(), repository.namaste_count());
+            Ok(Box::new(repository))
+        },
+    }
+}
+
+// Open the configured storage; called at startup so that unreadable CSVs stop the process
+pub fn init() -> Result<&'static dyn TerminologyRepository, String> {
+    if let Some(repository) = REPOSITORY.get() {
+        return Ok(repository.as_ref());
+    }
+    let repository = open(config::get())?;
+    Ok(REPOSITORY.get_or_init(|| repository).as_ref())
+}
+
+// The storage every codec reads
+pub fn get() -> &'static dyn TerminologyRepository {
+    REPOSITORY
+        .get_or_init(|| open(config::get()).unwrap_or_else(|e| panic!("Terminology storage failed to open: {}", e)))
+        .as_ref()
+}
+
+// Tests run against the bundled CSVs, whatever the configuration says
+#[cfg(test)]
+pub fn use_bundled() -> &'static dyn TerminologyRepository {
+    REPOSITORY
+        .get_or_init(|| {
+            let defaults = crate::config::FilesConfig::default();
+            Box::new(MemoryRepository::load(&defaults.icd_csv, &defaults.namaste_csv).unwrap())
+        })
+        .as_ref()
+}
@@ -0,0 +1,333 @@
+// The terminology collections in MongoDB (terminology.icd / terminology.namaste)
+use std::collections::{HashMap, HashSet};
+use futures::future::BoxFuture;
+use futures::stream::TryStreamExt;
+use mongodb::bson::{self, doc, Bson, Document};
+use mongodb::options::{FindOptions, UpdateOptions};
+use mongodb::Collection;
+
+use crate::codecs::escape_regex;
+use crate::codecs::icd::{IcdCode, IcdDiscipline, IcdFilter};
+use crate::codecs::namaste::{NamasteCode, NamasteFilter, LEGACY_FIELDS};
+use crate::config::{self, CollectionLocation};
+use crate::dbcodes::mongo::MongoClient;
+use crate::embedding::stored::{self, StoredEmbedding};
+use crate::vector::IndexSpec;
+use super::{EmbeddingState, KeyedVectors, NamasteVersion, TerminologyRepository};
+
+// One document per imported version: { system, version, collection, source, rows, skipped, imported_at, active }
+const VERSIONS_COLLECTION: &str = "terminology_versions";
+const NAMASTE_SYSTEM: &str = "namaste";
+
+pub struct MongoRepository;
+
+async fn collection<T: Send + Sync>(location: &CollectionLocation) -> anyhow::Result<Collection<T>> {
+    let client = MongoClient::get_instance().await?;
+    Ok(client.collection_at::<T>(location))
+}
+
+async fn documents_of(spec: &IndexSpec) -> anyhow::Result<Collection<Document>> {
+    collection((spec.location)(config::get())).await
+}
+
+fn icd_query(filter: &IcdFilter) -> Document {
+    let mut query = doc! {};
+
+    // Discipline filtering based on URL patterns
+    if let Some(discipline) = &filter.discipline {
+        query.insert("id", doc! { "$regex": discipline.id_pattern(), "$options": "i" });
+    }
+
+    // Literal substring search across multiple fields
+    if let Some(search_term) = &filter.search_term {
+        let pattern = escape_regex(search_term);
+        query.insert("$or", vec![
+            doc! { "title": { "$regex": &pattern, "$options": "i" } },
+            doc! { "definition": { "$regex": &pattern, "$options": "i" } },
+            doc! { "code": { "$regex": &pattern, "$options": "i" } },
+        ]);
+    }
+
+    // Filter by parent code
+    if let Some(parent) = &filter.parent_filter {
+        query.insert("parent", parent);
+    }
+
+    query
+}
+
+fn namaste_query(filter: &NamasteFilter) -> Document {
+    let mut query = doc! {};
+
+    // Literal substring search across the term and code fields
+    if let Some(search_term) = &filter.search_term {
+        let pattern = escape_regex(search_term);
+        query.insert("$or", vec![
+            doc! { "namc_term": { "$regex": &pattern, "$options": "i" } },
+            doc! { "namc_term_diacritical": { "$regex": &pattern, "$options": "i" } },
+            doc! { "namc_term_devanagari": { "$regex": &pattern, "$options": "i" } },
+            doc! { "namc_code": { "$regex": &pattern, "$options": "i" } }
+        ]);
+    }
+
+    if let Some(code) = &filter.code {
+        query.insert("namc_code", bson::Regex {
+            pattern: escape_regex(code),
+            options: "i".to_string(),
+        });
+    }
+
+    query
+}
+
+async fn find<T>(location: &CollectionLocation, query: Document, options: Option<FindOptions>) -> anyhow::Result<Vec<T>>
+where
+    T: serde::de::DeserializeOwned + Unpin + Send + Sync,
+{
+    let cursor = collection::<T>(location).await?.find(query, options).await?;
+    Ok(cursor.try_collect().await?)
+}
+
+// Matches of a query, counted, and one page of them in the given order
+async fn page<T>(location: &CollectionLocation, query: Document, sort: Document, offset: usize, count: usize) -> anyhow::Result<(Vec<T>, u64)>
+where
+    T: serde::de::DeserializeOwned + Unpin + Send + Sync,
+{
+    let total = collection::<T>(location).await?.count_documents(query.clone(), None).await?;
+    let options = FindOptions::builder().sort(sort).skip(offset as u64).limit(count as i64).build();
+    Ok((find(location, query, Some(options)).await?, total))
+}
+
+fn limited(limit: Option<usize>) -> Option<FindOptions> {
+    limit.map(|limit| FindOptions::builder().limit(limit as i64).build())
+}
+
+// Documents of the given keys, keyed as the vector index keys them
+async fn by_keys(spec: &IndexSpec, keys: &[String], options: Option<FindOptions>) -> anyhow::Result<HashMap<String, Document>> {
+    let keys: Vec<Bson> = keys.iter().map(|key| spec.key_bson(key)).collect();
+    let cursor = documents_of(spec).await?.find(doc! { spec.key_field: { "$in": keys } }, options).await?;
+    let documents: Vec<Document> = cursor.try_collect().await?;
+    Ok(documents
+        .into_iter()
+        .filter_map(|document| Some((spec.key_of(&document)?, document)))
+        .collect())
+}
+
+// Rename the sheet's column names in a mongoimported NAMASTE collection to the named fields
+pub async fn rename_legacy_namaste_fields() -> anyhow::Result<u64> {
+    let renames: Document = LEGACY_FIELDS.iter().map(|&(legacy, field)| (legacy.to_string(), Bson::from(field))).collect();
+    let legacy = doc! { "$or": LEGACY_FIELDS.iter().map(|&(legacy, _)| doc! { legacy: { "$exists": true } }).collect::<Vec<_>>() };
+    let collection = collection::<Document>(&config::get().terminology.namaste).await?;
+    let result = collection.update_many(legacy, doc! { "$rename": renames }, None).await?;
+    Ok(result.modified_count)
+}
+
+async fn next_version(db: &mongodb::Database) -> anyhow::Result<i64> {
+    let options = mongodb::options::FindOneOptions::builder().sort(doc! { "version": -1 }).build();
+    let latest = db
+        .collection::<Document>(VERSIONS_COLLECTION)
+        .find_one(doc! { "system": NAMASTE_SYSTEM }, options)
+        .await?;
+    Ok(latest.and_then(|d| d.get_i64("version").ok()).unwrap_or(0) + 1)
+}
+
+// Write the codes to {collection}_v{N} and swap them in with a single $out
+async fn replace_namaste(codes: &[NamasteCode], unchanged: &HashSet<String>, source: &str, skipped: usize) -> anyhow::Result<NamasteVersion> {
+    let location = &config::get().terminology.namaste;
+    let active = location.collection.as_str();
+    let db = MongoClient::get_instance().await?.get_database_by_name(&location.database);
+
+    // Embeddings (of every model) of the active concepts, by NAMASTE code
+    let mut cursor = db.collection::<Document>(active).find(doc! {}, None).await?;
+    let mut embeddings = HashMap::new();
+    while let Some(document) = cursor.try_next().await? {
+        let embedding = document.get(stored::FIELD).cloned();
+        if let (Some(embedding), Ok(code)) = (embedding, bson::from_document::<NamasteCode>(document)) {
+            embeddings.insert(code.parse_codes().0, embedding);
+        }
+    }
+
+    let mut documents = Vec::with_capacity(codes.len());
+    let mut embeddings_kept = 0;
+    for code in codes {
+        let mut document = bson::to_document(code)?;
+        let key = code.parse_codes().0;
+        if unchanged.contains(&key) && let Some(embedding) = embeddings.remove(&key) {
+            document.insert(stored::FIELD, embedding);
+            embeddings_kept += 1;
+        }
+        documents.push(document);
+    }
+
+    let version = next_version(&db).await?;
+    let collection_name = format!("{}_v{}", active, version);
+    let versioned = db.collection::<Document>(&collection_name);
+    versioned.drop(None).await?;
+    versioned.insert_many(documents, None).await?;
+    versioned.aggregate(vec![doc! { "$out": active }], None).await?;
+
+    let versions = db.collection::<Document>(VERSIONS_COLLECTION);
+    versions
+        .update_many(doc! { "system": NAMASTE_SYSTEM }, doc! { "$set": { "active": false } }, None)
+        .await?;
+    versions
+        .insert_one(doc! {
+            "system": NAMASTE_SYSTEM,
+            "version": version,
+            "collection": &collection_name,
+            "source": source,
+            "rows": codes.len() as i64,
+            "skipped": skipped as i64,
+            "imported_at": bson::DateTime::now(),
+            "active": true,
+        }, None)
+        .await?;
+
+    Ok(NamasteVersion { version, collection: Some(collection_name), embeddings_kept })
+}
+
+impl TerminologyRepository for MongoRepository {
+    fn name(&self) -> &'static str {
+        "mongo"
+    }
+
+    fn icd_search<'a>(&'a self, filter: &'a IcdFilter, limit: Option<usize>) -> BoxFuture<'a, anyhow::Result<Vec<IcdCode>>> {
+        Box::pin(async move {
+            let query = icd_query(filter);
+            println!("📊 MongoDB ICD query: {:?}", query);
+            find(&config::get().terminology.icd, query, limited(limit)).await
+        })
+    }
+
+    fn icd_page<'a>(&'a self, filter: &'a IcdFilter, offset: usize, count: usize) -> BoxFuture<'a, anyhow::Result<(Vec<IcdCode>, u64)>> {
+        Box::pin(page(&config::get().terminology.icd, icd_query(filter), doc! { "_id": 1 }, offset, count))
+    }
+
+    fn icd_by_id<'a>(&'a self, id: &'a str) -> BoxFuture<'a, anyhow::Result<Option<IcdCode>>> {
+        Box::pin(async move {
+            let collection = collection::<IcdCode>(&config::get().terminology.icd).await?;
+            Ok(collection.find_one(doc! { "id": id }, None).await?)
+        })
+    }
+
+    fn icd_by_code<'a>(&'a self, code: &'a str, discipline: Option<&'a IcdDiscipline>) -> BoxFuture<'a, anyhow::Result<Option<IcdCode>>> {
+        Box::pin(async move {
+            // mongoimport stores numeric codes (chapters) as integers
+            let mut code_matches = vec![
+                doc! { "code": code },
+                doc! { "id": { "$regex": format!("/{}$", escape_regex(code)) } },
+            ];
+            if let Ok(number) = code.parse::<i64>() {
+                code_matches.push(doc! { "code": number });
+            }
+
+            let mut clauses = vec![doc! { "$or": code_matches }];
+            if let Some(discipline) = discipline {
+                clauses.push(doc! { "id": { "$regex": discipline.id_pattern(), "$options": "i" } });
+            }
+
+            let candidates: Vec<IcdCode> = find(&config::get().terminology.icd, doc! { "$and": clauses }, None).await?;
+            Ok(candidates.into_iter().find(|entity| entity.concept_code() == code))
+        })
+    }
+
+    fn icd_children<'a>(&'a self, parent_ids: &'a [String]) -> BoxFuture<'a, anyhow::Result<Vec<IcdCode>>> {
+        Box::pin(find(&config::get().terminology.icd, doc! { "parent": { "$in": parent_ids } }, None))
+    }
+
+    fn namaste_search<'a>(&'a self, filter: &'a NamasteFilter, limit: Option<usize>) -> BoxFuture<'a, anyhow::Result<Vec<NamasteCode>>> {
+        Box::pin(async move {
+            let query = namaste_query(filter);
+            println!("📊 MongoDB NAMASTE query: {:?}", query);
+            find(&config::get().terminology.namaste, query, limited(limit)).await
+        })
+    }
+
+    fn namaste_page<'a>(&'a self, filter: &'a NamasteFilter, offset: usize, count: usize) -> BoxFuture<'a, anyhow::Result<(Vec<NamasteCode>, u64)>> {
+        Box::pin(page(&config::get().terminology.namaste, namaste_query(filter), doc! { "sr_no": 1 }, offset, count))
+    }
+
+    fn namaste_by_code<'a>(&'a self, code: &'a str) -> BoxFuture<'a, anyhow::Result<Option<NamasteCode>>> {
+        Box::pin(async move {
+            let query = doc! { "namc_code": { "$regex": escape_regex(code) } };
+            let candidates: Vec<NamasteCode> = find(&config::get().terminology.namaste, query, None).await?;
+            Ok(candidates.into_iter().find(|candidate| candidate.parse_codes().0 == code))
+        })
+    }
+
+    fn replace_namaste<'a>(&'a self, codes: &'a [NamasteCode], unchanged: &'a HashSet<String>, source: &'a str, skipped: usize) -> BoxFuture<'a, anyhow::Result<NamasteVersion>> {
+        Box::pin(replace_namaste(codes, unchanged, source, skipped))
+    }
+
+    fn documents<'a>(&'a self, spec: &'a IndexSpec, keys: &'a [String]) -> BoxFuture<'a, anyhow::Result<HashMap<String, Document>>> {
+        Box::pin(by_keys(spec, keys, None))
+    }
+
+    fn embeddings<'a>(&'a self, spec: &'a IndexSpec, model_key: &'a str) -> BoxFuture<'a, anyhow::Result<KeyedVectors>> {
+        Box::pin(async move {
+            let path = stored::path(model_key);
+            let filter = doc! { format!("{}.vector", path): { "$exists": true, "$ne": [] } };
+            let options = FindOptions::builder().projection(doc! { spec.key_field: 1, path: 1 }).build();
+            let mut cursor = documents_of(spec).await?.find(filter, options).await?;
+            let mut vectors = Vec::new();
+            while let Some(document) = cursor.try_next().await? {
+                if let (Some(key), Some(entry)) = (spec.key_of(&document), stored::of(&document, model_key)) {
+                    vectors.push((key, entry.vector));
+                }
+            }
+            Ok(vectors)
+        })
+    }
+
+    fn embedding_states<'a>(&'a self, spec: &'a IndexSpec, keys: &'a [String], model_key: &'a str) -> BoxFuture<'a, anyhow::Result<HashMap<String, EmbeddingState>>> {
+        Box::pin(async move {
+            let options = FindOptions::builder()
+                .projection(doc! {
+                    spec.key_field: 1,
+                    format!("{}.text_hash", stored::path(model_key)): 1,
+                    format!("{}.{}", stored::path(model_key), stored::ERROR): 1,
+                })
+                .build();
+            let documents = by_keys(spec, keys, Some(options)).await?;
+            Ok(documents
+                .into_iter()
+                .map(|(key, document)| {
+                    let state = EmbeddingState {
+                        text_hash: stored::text_hash_of(&document, model_key).map(str::to_string),
+                        failed: stored::failed(&document, model_key),
+                    };
+                    (key, state)
+                })
+                .collect())
+        })
+    }
+
+    // Replaces the model's entry with its error, and the legacy bare "embedding" array and
+    // shared "embedding_error" too
+    fn store_embedding<'a>(&'a self, spec: &'a IndexSpec, key: &'a str, model_key: &'a str, entry: &'a StoredEmbedding) -> BoxFuture<'a, anyhow::Result<bool>> {
+        Box::pin(async move {
+            let update = doc! {
+                "$set": { stored::path(model_key): bson::to_bson(entry)? },
+                "$unset": { "embedding_error": "", "embedding": "" },
+            };
+            let options = UpdateOptions::builder().upsert(false).build();
+            let result = documents_of(spec).await?.update_one(doc! { spec.key_field: spec.key_bson(key) }, update, options).await?;
+            Ok(result.matched_count > 0)
+        })
+    }
+
+    fn record_embedding_failure<'a>(&'a self, spec: &'a IndexSpec, key: &'a str, model_key: &'a str, message: &'a str) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            let error = format!("{}.{}", stored::path(model_key), stored::ERROR);
+            let update = doc! {
+                "$set": {
+                    format!("{}.message", error): message,
+                    format!("{}.at", error): chrono::Utc::now().to_rfc3339(),
+                },
+                "$inc": { format!("{}.attempts", error): 1 },
+            };
+            documents_of(spec).await?.update_one(doc! { spec.key_field: spec.key_bson(key) }, update, None).await?;
+            Ok(())
+        })
+    }
+}
@@ -1,11 +1,57 @@
-use actix_web::{web, App, HttpServer, middleware::Logger, http};
+use actix_web::{web, App, FromRequest, Handler, HttpServer, Responder, middleware::{self, Logger}, http};
+use actix_web::http::Method;
 use crate::dbcodes::{mongo, redis};
 use crate::api;  // Import the api module
 use actix_cors::Cors;
 use crate::gemini::embedding::generate_embeddings_handler;
+use crate::fhir::capability::FhirRoutes;
+use crate::auth::{self, Authenticator};
+use crate::audit;
+use crate::vector;
+use crate::fulltext;
+use crate::jobs;
+use crate::config::{self, Storage};
+use crate::repository;
+
+// /fhir scope that records each route it registers, for the CapabilityStatement
+struct FhirScope {
+    scope: actix_web::Scope,
+    routes: FhirRoutes,
+}
+
+impl FhirScope {
+    fn new() -> Self {
+        FhirScope { scope: web::scope("/fhir"), routes: FhirRoutes::default() }
+    }
+
+    fn route<F, Args>(mut self, method: Method, path: &str, handler: F) -> Self
+    where
+        F: Handler<Args>,
+        Args: FromRequest + 'static,
+        F::Output: Responder + 'static,
+    {
+        self.scope = self.scope.route(path, web::method(method.clone()).to(handler));
+        self.routes.0.push((method, path.to_string()));
+        self
+    }
+}
 
 // Configure all routes
 fn configure_routes(cfg: &mut web::ServiceConfig) {
+    let fhir = FhirScope::new()
+        .route(Method::GET, "/metadata", api::fhir_metadata)
+        .route(Method::POST, "", api::fhir_bundle_ingest)
+        .route(Method::GET, "/AuditEvent", api::fhir_audit_search)
+        .route(Method::GET, "/CodeSystem/$lookup", api::fhir_codesystem_lookup)
+        .route(Method::GET, "/CodeSystem/$validate-code", api::fhir_codesystem_validate_code)
+        .route(Method::GET, "/CodeSystem/{id}", api::fhir_codesystem_read)
+        .route(Method::GET, "/ConceptMap/$translate", api::fhir_conceptmap_translate)
+        .route(Method::GET, "/ConceptMap/{id}", api::fhir_conceptmap_read)
+        .route(Method::GET, "/ValueSet/$expand", api::fhir_valueset_expand)
+        .route(Method::GET, "/ValueSet/{id}/$expand", api::fhir_valueset_expand_by_id)
+        .route(Method::GET, "/ValueSet/$validate-code", api::fhir_valueset_validate_code)
+        .route(Method::GET, "/ValueSet/{id}/$validate-code", api::fhir_valueset_validate_code_by_id);
+
     cfg
         // Health check
         .route("/health", web::get().to(api::health_check))
@@ -20,10 +66,21 @@ fn configure_routes(cfg: &mut web::ServiceConfig) {
             web::scope("/services")
                 .route("/terminology", web::get().to(api::terminology_service))
                 .route("/mapping", web::get().to(api::mapping_service))
+                .route("/mapping", web::post().to(api::mapping_add))
+                .route("/mapping/rebuild", web::post().to(api::mapping_rebuild))
                 .route("/sync", web::get().to(api::sync_service))
                 .route("/audit", web::get().to(api::audit_service))
-                .route("/generate-embeddings", web::get().to(generate_embeddings_handler)) // <-- new route added here
+                .route("/import/namaste", web::post().to(api::import_namaste))
+                .route("/generate-embeddings", web::post().to(generate_embeddings_handler))
+        )
 
+        // Background jobs
+        .service(
+            web::scope("/jobs")
+                .route("", web::post().to(api::job_start))
+                .route("", web::get().to(api::job_list))
+                .route("/{id}", web::get().to(api::job_status))
+                .route("/{id}/cancel", web::post().to(api::job_cancel))
         )
 
         // Core Components
@@ -70,16 +127,20 @@ fn configure_routes(cfg: &mut web::ServiceConfig) {
                 .route("/search", web::get().to(api::namaste_search))
                 .route("/all", web::get().to(api::namaste_all))
         )
+        // FHIR R4 terminology resources
+        .app_data(web::Data::new(fhir.routes))
+        .service(fhir.scope)
         .service(
            web::scope("/autocomplete")
             .route("/suggestions", web::get().to(api::autocomplete_suggestions))
             .route("/initialize", web::post().to(api::initialize_autocomplete_data))
+            .route("/status", web::get().to(api::autocomplete_status))
         );
 }
 
 
 // Create the application
-pub fn create_app() -> App<
+pub fn create_app(authenticator: web::Data<Authenticator>) -> App<
     impl actix_web::dev::ServiceFactory<
         actix_web::dev::ServiceRequest,
         Response = actix_web::dev::ServiceResponse<impl actix_web::body::MessageBody>,
@@ -88,14 +149,10 @@ pub fn create_app() -> App<
         InitError = (),
     >
 > {
+    // Origins come from the configuration (server.cors_*)
     let cors = Cors::default()
-    .allowed_origin("http://localhost:5173")
     .allowed_origin_fn(|origin, _req_head| {
-        let origin_str = origin.to_str().unwrap_or("");
-        // Allow localhost variants and trycloudflare.com subdomains
-        origin_str.contains("localhost") || 
-        origin_str.contains("127.0.0.1") ||
-        origin_str.ends_with(".trycloudflare.com")
+        origin.to_str().is_ok_and(|origin| config::get().allows_origin(origin))
     })
     .allowed_methods(vec!["GET", "POST", "PUT", "DELETE", "OPTIONS"])
     .allowed_headers(vec![
@@ -108,6 +165,9 @@ pub fn create_app() -> App<
 
 
     App::new()
+        .app_data(authenticator)
+        .wrap(middleware::from_fn(auth::bearer_auth))
+        .wrap(middleware::from_fn(audit::audit_trail))
         .wrap(cors)
         .configure(configure_routes)
         .wrap(Logger::default())
@@ -129,15 +189,48 @@ pub async fn start_server() -> std::io::Result<()> {
         Ok(_) => println!("✅ MongoDB connection initialized"),
         Err(e) => println!("⚠️  MongoDB connection failed: {} (server will still start)", e),
     }
+
+    // NAMASTE documents mongoimport wrote with the sheet's column names get the named fields
+    if config::get().terminology.storage == Storage::Mongo {
+        match repository::mongo::rename_legacy_namaste_fields().await {
+            Ok(0) => {},
+            Ok(renamed) => println!("🔄 Renamed the legacy fields of {} NAMASTE documents", renamed),
+            Err(e) => println!("⚠️  Legacy NAMASTE fields not renamed: {}", e),
+        }
+    }
+
+    // Build the semantic search indexes from the stored embeddings in the background
+    vector::spawn_load_all();
+    fulltext::spawn_load_all();
+
+    // Jobs a previous run left unfinished continue from their checkpoints
+    jobs::spawn_resume_interrupted();
     
     // Initialize Redis connection
     match redis::init_redis().await {
         Ok(_) => println!("✅ Redis connection initialized"),
         Err(e) => println!("⚠️  Redis connection failed: {} (server will still start)", e),
     }
+
+    // Load the token issuer's signing keys; a misconfigured issuer must not leave the server open
+    let authenticator = match Authenticator::from_config().await {
+        Ok(authenticator) => authenticator,
+        Err(e) => return Err(std::io::Error::other(format!("OAuth configuration failed: {}", e))),
+    };
+    match authenticator.config() {
+        Some(config) => println!("🔐 OAuth 2.0 bearer tokens required (issuer {}, audience {})", config.issuer, config.audience),
+        None => println!("⚠️  oauth.jwks_file / oauth.jwks_url not set, authentication is disabled"),
+    }
+    let authenticator = web::Data::new(authenticator);
+
+    match crate::embedding::provider() {
+        Some(provider) => println!("🧬 Embedding provider: {} ({})", provider.name(), provider.model()),
+        None => println!("⚠️  No embedding provider configured, semantic search falls back to regex"),
+    }
     
-    println!("📊 Server running on http://127.0.0.1:8080");
-    println!("🏥 Health check: http://127.0.0.1:8080/health");
+    let (host, port) = config::get().bind_address();
+    println!("📊 Server running on http://{}:{}", host, port);
+    println!("🏥 Health check: http://{}:{}/health", host, port);
     println!();
     println!("📋 Available API Endpoints:");
 
@@ -153,8 +246,19 @@ pub async fn start_server() -> std::io::Result<()> {
     println!("   🛠️  SERVICES:");
     println!("      GET  /services/terminology       - Terminology service status");
     println!("      GET  /services/mapping           - Mapping service status");
+    println!("      POST /services/mapping           - Add a curated NAMASTE -> ICD-11 mapping");
+    println!("      POST /services/mapping/rebuild   - Re-derive NAMASTE -> TM2 mappings");
     println!("      GET  /services/sync              - Sync service status");
-    println!("      GET  /services/audit             - Audit service status");
+    println!("      GET  /services/audit             - Audit service status (recorded events)");
+    println!("      POST /services/import/namaste    - Import NAMASTE CSV as a new version (?dry_run=true&strict=true)");
+    println!("      POST /services/generate-embeddings - Generate missing embeddings (background job)");
+
+    // Background jobs
+    println!("   🧵 JOBS:");
+    println!("      POST /jobs                       - Start a job (embeddings, autocomplete, import_namaste)");
+    println!("      GET  /jobs                       - Recent jobs (?kind=&limit=)");
+    println!("      GET  /jobs/{{id}}                  - Job status and progress");
+    println!("      POST /jobs/{{id}}/cancel           - Cancel a running job");
     
     // Core Components
     println!("   🧠 CORE:");
@@ -195,6 +299,23 @@ pub async fn start_server() -> std::io::Result<()> {
     println!("      GET  /icd/biomedicine?limit=N     - ICD-11 Biomedicine codes");
     println!("      GET  /icd/tm2?limit=N             - ICD-11 Traditional Medicine codes");
     
+
+    // FHIR R4 resources
+    println!("   🔥 FHIR R4:");
+    println!("      GET  /fhir/metadata[?mode=terminology]   - CapabilityStatement / TerminologyCapabilities");
+    println!("      GET  /fhir/AuditEvent?date=geYYYY-MM-DD&actor=sub&action=R|C|U|D|E - Audit trail");
+    println!("      POST /fhir                               - Ingest transaction/batch Bundle (Condition, List)");
+    println!("      GET  /fhir/CodeSystem/namaste            - NAMASTE CodeSystem");
+    println!("      GET  /fhir/CodeSystem/icd11-tm2          - ICD-11 TM2 CodeSystem");
+    println!("      GET  /fhir/CodeSystem/icd11-biomedicine  - ICD-11 Biomedicine CodeSystem");
+    println!("      GET  /fhir/CodeSystem/$lookup?system=url&code=X - Concept details (Parameters)");
+    println!("      GET  /fhir/CodeSystem/$validate-code?url=url&code=X&display=text");
+    println!("      GET  /fhir/ConceptMap/namaste-icd11      - NAMASTE -> ICD-11 ConceptMap");
+    println!("      GET  /fhir/ConceptMap/$translate?system=url&code=X&target=url&reverse=true");
+    println!("      GET  /fhir/ValueSet/$expand?url=[system]?fhir_vs[=isa/code]&filter=..&count=N&offset=N");
+    println!("      GET  /fhir/ValueSet/{{id}}/$expand        - Expand namaste | icd11-tm2 | icd11-biomedicine");
+    println!("      GET  /fhir/ValueSet/$validate-code?url=vs&system=url&code=X&display=text");
+
     println!();
     println!("📝 Query Parameters:");
     println!("   • search=<term>     - Search in titles, definitions, codes");
@@ -207,9 +328,43 @@ pub async fn start_server() -> std::io::Result<()> {
 
     
     
-    HttpServer::new(|| create_app())
-        .bind("0.0.0.0:8080")?
+    HttpServer::new(move || create_app(authenticator.clone()))
+        .bind((host, port))?
         .run()
         .await
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test;
+
+    // The terminology API end to end, served from the bundled CSVs without any database
+    #[actix_web::test]
+    async fn test_api_serves_the_bundled_sheets_without_databases() {
+        crate::repository::use_bundled();
+        let app = test::init_service(App::new().configure(configure_routes)).await;
+
+        let request = test::TestRequest::get().uri("/icd/search?search=cholera&discipline=biomedicine").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(body["results"][0]["code"], "1A00");
+        assert_eq!(body["results"][0]["title"], "Cholera");
+
+        let request = test::TestRequest::get().uri("/namaste/search?search=prANavAta").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        assert!(body["results"].as_array().unwrap().iter().any(|result| result["nam_code"] == "AAA-2.2"), "{}", body);
+
+        // Hierarchy: the parent comes from the codes the storage holds
+        let uri = format!("/fhir/CodeSystem/$lookup?system={}&code=AAA-2.2", crate::fhir::NAMASTE_SYSTEM);
+        let response = test::call_service(&app, test::TestRequest::get().uri(&uri).to_request()).await;
+        assert!(response.status().is_success());
+        let parameters: serde_json::Value = test::read_body_json(response).await;
+        let text = parameters.to_string();
+        assert!(text.contains("prANavAtakopaH") && text.contains("AAA-2"), "{}", text);
+
+        let request = test::TestRequest::get().uri("/services/terminology").to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, request).await;
+        assert_eq!(body["storage"], "memory");
+    }
+}
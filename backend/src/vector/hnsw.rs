@@ -0,0 +1,299 @@
+// Hierarchical Navigable Small World graph (Malkov & Yashunin) over cosine similarity.
+// Vectors are normalised on insert so the distance is 1 - dot product
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::hash::Hash;
+
+#[derive(Debug, Clone, Copy)]
+pub struct HnswParams {
+    // Links per node above layer 0; layer 0 keeps twice as many
+    pub m: usize,
+    pub ef_construction: usize,
+    pub ef_search: usize,
+}
+
+impl Default for HnswParams {
+    fn default() -> Self {
+        Self { m: 16, ef_construction: 100, ef_search: 64 }
+    }
+}
+
+struct Node<K> {
+    key: K,
+    vector: Vec<f32>,
+    links: Vec<Vec<usize>>,
+    // Replaced by a newer vector for the same key; still routes searches
+    deleted: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct Candidate {
+    distance: f32,
+    id: usize,
+}
+
+impl Eq for Candidate {}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance.total_cmp(&other.distance).then(self.id.cmp(&other.id))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+pub struct Hnsw<K> {
+    params: HnswParams,
+    dimension: usize,
+    nodes: Vec<Node<K>>,
+    by_key: HashMap<K, usize>,
+    entry: Option<usize>,
+    top_layer: usize,
+    level_factor: f64,
+    rng: u64,
+}
+
+fn normalise(vector: &[f32]) -> Option<Vec<f32>> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    (norm > 0.0 && norm.is_finite()).then(|| vector.iter().map(|x| x / norm).collect())
+}
+
+fn distance(a: &[f32], b: &[f32]) -> f32 {
+    1.0 - a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>()
+}
+
+impl<K: Clone + Eq + Hash> Hnsw<K> {
+    pub fn new(dimension: usize, params: HnswParams) -> Self {
+        Self {
+            params,
+            dimension,
+            nodes: Vec::new(),
+            by_key: HashMap::new(),
+            entry: None,
+            top_layer: 0,
+            level_factor: 1.0 / (params.m.max(2) as f64).ln(),
+            rng: 0x9E37_79B9_7F4A_7C15,
+        }
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    // Live vectors, not counting replaced ones
+    pub fn len(&self) -> usize {
+        self.by_key.len()
+    }
+
+    fn random_level(&mut self) -> usize {
+        // xorshift64*; the graph only needs a geometric distribution, not strong randomness
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        let bits = self.rng.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11;
+        let uniform = (bits as f64 + 1.0) / (1u64 << 53) as f64;
+        (-uniform.ln() * self.level_factor) as usize
+    }
+
+    fn max_links(&self, layer: usize) -> usize {
+        if layer == 0 { self.params.m * 2 } else { self.params.m }
+    }
+
+    fn distance_to(&self, query: &[f32], id: usize) -> f32 {
+        distance(query, &self.nodes[id].vector)
+    }
+
+    // Best `ef` nodes reachable from the entry points on one layer, nearest first
+    fn search_layer(&self, query: &[f32], entry_points: &[usize], ef: usize, layer: usize) -> Vec<Candidate> {
+        let mut visited: HashSet<usize> = entry_points.iter().copied().collect();
+        let mut frontier = BinaryHeap::new();
+        let mut found = BinaryHeap::new();
+        for &id in entry_points {
+            let candidate = Candidate { distance: self.distance_to(query, id), id };
+            frontier.push(std::cmp::Reverse(candidate));
+            found.push(candidate);
+        }
+
+        while let Some(std::cmp::Reverse(nearest)) = frontier.pop() {
+            let furthest = found.peek().map_or(f32::MAX, |c: &Candidate| c.distance);
+            if nearest.distance > furthest && found.len() >= ef {
+                break;
+            }
+            for &neighbour in self.nodes[nearest.id].links.get(layer).into_iter().flatten() {
+                if !visited.insert(neighbour) {
+                    continue;
+                }
+                let candidate = Candidate { distance: self.distance_to(query, neighbour), id: neighbour };
+                let furthest = found.peek().map_or(f32::MAX, |c| c.distance);
+                if found.len() < ef || candidate.distance < furthest {
+                    frontier.push(std::cmp::Reverse(candidate));
+                    found.push(candidate);
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+        found.into_sorted_vec()
+    }
+
+    // Keep candidates that are closer to the new node than to any neighbour already chosen,
+    // so links spread across clusters; top up with the nearest rejects
+    fn select_neighbours(&self, candidates: &[Candidate], count: usize) -> Vec<usize> {
+        let mut selected: Vec<usize> = Vec::with_capacity(count);
+        let mut rejected = Vec::new();
+        for candidate in candidates {
+            if selected.len() == count {
+                break;
+            }
+            let diverse = selected
+                .iter()
+                .all(|&kept| candidate.distance < distance(&self.nodes[candidate.id].vector, &self.nodes[kept].vector));
+            if diverse {
+                selected.push(candidate.id);
+            } else {
+                rejected.push(candidate.id);
+            }
+        }
+        let missing = count.saturating_sub(selected.len());
+        selected.extend(rejected.into_iter().take(missing));
+        selected
+    }
+
+    fn link(&mut self, from: usize, to: usize, layer: usize) {
+        self.nodes[from].links[layer].push(to);
+        if self.nodes[from].links[layer].len() <= self.max_links(layer) {
+            return;
+        }
+        let origin = self.nodes[from].vector.clone();
+        let mut candidates: Vec<Candidate> = self.nodes[from].links[layer]
+            .iter()
+            .map(|&id| Candidate { distance: self.distance_to(&origin, id), id })
+            .collect();
+        candidates.sort();
+        self.nodes[from].links[layer] = self.select_neighbours(&candidates, self.max_links(layer));
+    }
+
+    // Add or replace the vector for a key. Vectors of another dimension, or all zeros, are ignored
+    pub fn insert(&mut self, key: K, vector: &[f32]) -> bool {
+        if vector.len() != self.dimension {
+            return false;
+        }
+        let Some(vector) = normalise(vector) else {
+            return false;
+        };
+        if let Some(previous) = self.by_key.get(&key) {
+            self.nodes[*previous].deleted = true;
+        }
+
+        let id = self.nodes.len();
+        let level = self.random_level();
+        self.nodes.push(Node { key: key.clone(), vector, links: vec![Vec::new(); level + 1], deleted: false });
+        self.by_key.insert(key, id);
+
+        let Some(mut entry) = self.entry else {
+            self.entry = Some(id);
+            self.top_layer = level;
+            return true;
+        };
+
+        let query = self.nodes[id].vector.clone();
+        for layer in (level + 1..=self.top_layer).rev() {
+            entry = self.search_layer(&query, &[entry], 1, layer)[0].id;
+        }
+        let mut entry_points = vec![entry];
+        for layer in (0..=level.min(self.top_layer)).rev() {
+            let candidates = self.search_layer(&query, &entry_points, self.params.ef_construction, layer);
+            let neighbours = self.select_neighbours(&candidates, self.params.m);
+            for &neighbour in &neighbours {
+                self.link(neighbour, id, layer);
+            }
+            self.nodes[id].links[layer] = neighbours;
+            entry_points = candidates.into_iter().map(|c| c.id).collect();
+        }
+
+        if level > self.top_layer {
+            self.entry = Some(id);
+            self.top_layer = level;
+        }
+        true
+    }
+
+    // Up to `k` nearest keys with their cosine similarity, most similar first
+    pub fn search(&self, query: &[f32], k: usize) -> Vec<(K, f32)> {
+        let (Some(mut entry), Some(query)) = (self.entry, normalise(query)) else {
+            return Vec::new();
+        };
+        if query.len() != self.dimension || k == 0 {
+            return Vec::new();
+        }
+        for layer in (1..=self.top_layer).rev() {
+            entry = self.search_layer(&query, &[entry], 1, layer)[0].id;
+        }
+        // Replaced nodes take up room in the candidate list, so widen it by their share
+        let replaced = self.nodes.len() - self.by_key.len();
+        let ef = self.params.ef_search.max(k) + replaced.min(k);
+        self.search_layer(&query, &[entry], ef, 0)
+            .into_iter()
+            .filter(|c| !self.nodes[c.id].deleted)
+            .take(k)
+            .map(|c| (self.nodes[c.id].key.clone(), 1.0 - c.distance))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_vectors(count: usize, dimension: usize, seed: u64) -> Vec<Vec<f32>> {
+        let mut state = seed;
+        (0..count)
+            .map(|_| {
+                (0..dimension)
+                    .map(|_| {
+                        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                        ((state >> 33) as f32 / (1u64 << 31) as f32) - 0.5
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_recall_against_exact_search_and_replacement() {
+        let vectors = random_vectors(1000, 32, 7);
+        let mut index = Hnsw::new(32, HnswParams::default());
+        for (i, vector) in vectors.iter().enumerate() {
+            assert!(index.insert(i, vector));
+        }
+        assert!(!index.insert(1000, &[0.0; 32]));
+        assert!(!index.insert(1000, &[1.0; 8]));
+
+        let queries = random_vectors(50, 32, 99);
+        let mut hits = 0;
+        for query in &queries {
+            let mut exact: Vec<(usize, f32)> = vectors
+                .iter()
+                .enumerate()
+                .map(|(i, v)| (i, 1.0 - distance(&normalise(query).unwrap(), &normalise(v).unwrap())))
+                .collect();
+            exact.sort_by(|a, b| b.1.total_cmp(&a.1));
+            let expected: HashSet<usize> = exact.iter().take(10).map(|(i, _)| *i).collect();
+            hits += index.search(query, 10).iter().filter(|(i, _)| expected.contains(i)).count();
+        }
+        assert!(hits as f32 / 500.0 >= 0.9, "recall@10 was {}", hits as f32 / 500.0);
+
+        // Re-embedding a key moves it rather than duplicating it
+        index.insert(3, &queries[0]);
+        assert_eq!(index.len(), 1000);
+        let results = index.search(&queries[0], 5);
+        assert_eq!(results[0].0, 3);
+        assert!(results[0].1 > 0.999);
+        assert_eq!(results.iter().filter(|(i, _)| *i == 3).count(), 1);
+    }
+}
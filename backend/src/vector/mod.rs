@@ -0,0 +1,180 @@
+// In-process ANN indexes over the stored embeddings, one per collection, holding the vectors
+// of the configured embedding model only. Loaded from the terminology storage at startup
+// and kept current as embeddings are written; semantic search scans the stored vectors only
+// while an index is still loading
+pub mod hnsw;
+
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+use mongodb::bson::{Bson, Document};
+
+use crate::config::{AppConfig, CollectionLocation};
+use crate::embedding;
+use crate::repository;
+use hnsw::{Hnsw, HnswParams};
+
+// A collection holding stored embeddings, and the field that identifies its documents
+#[derive(Debug)]
+pub struct IndexSpec {
+    pub name: &'static str,
+    // Where the configuration puts the collection
+    pub location: fn(&AppConfig) -> &CollectionLocation,
+    pub key_field: &'static str,
+    // Held for the whole of a load, so that overlapping loads run one after the other
+    loading: tokio::sync::Mutex<()>,
+}
+
+// NAMC_ID, which survives re-imports unlike _id
+pub static NAMASTE: IndexSpec = IndexSpec {
+    name: "namaste",
+    location: |config| &config.terminology.namaste,
+    key_field: "namc_id",
+    loading: tokio::sync::Mutex::const_new(()),
+};
+
+pub static ICD: IndexSpec = IndexSpec {
+    name: "icd11",
+    location: |config| &config.terminology.icd,
+    key_field: "id",
+    loading: tokio::sync::Mutex::const_new(()),
+};
+
+#[derive(Default)]
+struct Slot {
+    // Model key of the vectors in the index
+    model: Option<String>,
+    // None until the first embedding of a loaded collection
+    index: Option<Hnsw<String>>,
+    loaded: bool,
+    // Writes that arrive while the index is being built, replayed once it is ready
+    pending: Option<Vec<(String, Vec<f32>)>>,
+}
+
+static INDEXES: OnceLock<RwLock<HashMap<&'static str, Slot>>> = OnceLock::new();
+
+fn indexes() -> &'static RwLock<HashMap<&'static str, Slot>> {
+    INDEXES.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+impl IndexSpec {
+    // Index key for a document: numbers in decimal, strings as they are
+    pub fn key_of(&self, document: &Document) -> Option<String> {
+        match document.get(self.key_field)? {
+            Bson::String(value) => Some(value.clone()),
+            other => Some(other.to_string()),
+        }
+    }
+
+    // Mongo compares numbers across types, so an Int64 finds an Int32 NAMC_ID too
+    pub fn key_bson(&self, key: &str) -> Bson {
+        match key.parse::<i64>() {
+            Ok(number) => Bson::Int64(number),
+            Err(_) => Bson::String(key.to_string()),
+        }
+    }
+}
+
+// (Re)build an index from the collection's vectors of the configured model and swap it in
+pub async fn load(spec: &'static IndexSpec) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    let _loading = spec.loading.lock().await;
+    let model = embedding::active_model_key();
+    {
+        let mut indexes = indexes().write().unwrap();
+        let slot = indexes.entry(spec.name).or_default();
+        slot.model = model.clone();
+        slot.pending.get_or_insert_with(Vec::new);
+    }
+
+    let result = async {
+        let Some(model) = model else {
+            return Ok(None);
+        };
+        let vectors = repository::get().embeddings(spec, &model).await?;
+        // Building is CPU-bound; keep it off the async workers
+        let index = tokio::task::spawn_blocking(move || build(vectors)).await?;
+        Ok::<_, Box<dyn std::error::Error + Send + Sync>>(index)
+    }
+    .await;
+
+    let mut indexes = indexes().write().unwrap();
+    let slot = indexes.entry(spec.name).or_default();
+    let pending = slot.pending.take().unwrap_or_default();
+    let mut index = result?;
+    for (key, embedding) in pending {
+        insert(&mut index, key, &embedding);
+    }
+    let count = index.as_ref().map_or(0, Hnsw::len);
+    slot.index = index;
+    slot.loaded = true;
+    Ok(count)
+}
+
+fn build(vectors: Vec<(String, Vec<f32>)>) -> Option<Hnsw<String>> {
+    let mut index = None;
+    for (key, embedding) in vectors {
+        insert(&mut index, key, &embedding);
+    }
+    index
+}
+
+// The first vector fixes the dimension; others are skipped, as the scan did
+fn insert(index: &mut Option<Hnsw<String>>, key: String, embedding: &[f32]) {
+    index
+        .get_or_insert_with(|| Hnsw::new(embedding.len(), HnswParams::default()))
+        .insert(key, embedding);
+}
+
+// Load both indexes in the background
+pub fn spawn_load_all() {
+    for spec in [&NAMASTE, &ICD] {
+        actix_web::rt::spawn(async move {
+            match load(spec).await {
+                Ok(count) => println!("🧭 Vector index {} ready with {} embeddings", spec.name, count),
+                Err(e) => println!("⚠️  Vector index {} failed to load: {} (semantic search will scan)", spec.name, e),
+            }
+        });
+    }
+}
+
+// Record a newly stored embedding of a model. Until an index has loaded, the next load
+// picks it up; vectors of another model than the indexed one are left out
+pub fn upsert(spec: &IndexSpec, model_key: &str, key: String, embedding: &[f32]) {
+    let mut indexes = indexes().write().unwrap();
+    let slot = indexes.entry(spec.name).or_default();
+    if slot.model.as_deref() != Some(model_key) {
+        return;
+    }
+    if let Some(pending) = slot.pending.as_mut() {
+        pending.push((key, embedding.to_vec()));
+    } else if slot.loaded {
+        insert(&mut slot.index, key, embedding);
+    }
+}
+
+// Nearest keys and their cosine similarity, or None when the index is not loaded or holds
+// another model's vectors than the query's
+pub fn search(spec: &IndexSpec, model_key: &str, query: &[f32], k: usize) -> Option<Vec<(String, f32)>> {
+    let indexes = indexes().read().unwrap();
+    let slot = indexes
+        .get(spec.name)
+        .filter(|slot| slot.loaded && slot.model.as_deref() == Some(model_key))?;
+    Some(match &slot.index {
+        Some(index) if index.dimension() == query.len() => index.search(query, k),
+        _ => Vec::new(),
+    })
+}
+
+// Loaded vector counts, for status endpoints
+pub fn sizes() -> Vec<(&'static str, Option<usize>)> {
+    let indexes = indexes().read().unwrap();
+    [&NAMASTE, &ICD]
+        .iter()
+        .map(|spec| {
+            let size = indexes
+                .get(spec.name)
+                .filter(|slot| slot.loaded)
+                .map(|slot| slot.index.as_ref().map_or(0, Hnsw::len));
+            (spec.name, size)
+        })
+        .collect()
+}